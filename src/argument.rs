@@ -1,5 +1,10 @@
 //! Traits related to arguments of verbs and conditions
 
+#[cfg(feature = "derive")]
+pub use test_dsl_derive::ParseArguments;
+#[cfg(feature = "derive")]
+pub use test_dsl_derive::VerbArgument;
+
 use crate::ConditionInstance;
 use crate::TestDsl;
 use crate::VerbInstance;
@@ -9,17 +14,122 @@ use crate::error::TestErrorCase;
 /// Types that can be parsed from a node as arguments
 ///
 /// This includes both named/positional parameters as well as child nodes
+///
+/// With the `derive` feature, it can be derived for structs with named fields. Each field is read
+/// from the property with the same name, which can be changed through attributes:
+///
+/// - `#[arg(name = "key")]` reads the field from the property `key` instead
+/// - `#[arg(positional)]` reads the field from the next positional argument
+/// - `#[arg(child)]` parses the field from the child node with its name
+/// - `#[arg(optional)]` uses [`Default::default`] if the field is not given
+/// - `#[arg(default = expr)]` uses `expr` if the field is not given
+///
+/// ```
+/// # #[cfg(feature = "derive")] {
+/// use test_dsl::argument::ParseArguments;
+///
+/// // Parses `request "/" method="POST" retries=3`
+/// #[derive(Debug, Clone, ParseArguments)]
+/// struct Request {
+///     #[arg(positional)]
+///     url: String,
+///     method: Option<String>,
+///     #[arg(name = "retries", default = 1)]
+///     attempts: usize,
+/// }
+/// # }
+/// ```
 pub trait ParseArguments<H>: std::fmt::Debug + Clone + Sized + 'static {
     /// Do the parsing and return an instance
     ///
     /// See [`VerbInstance`] and
     /// [`ConditionInstance`] for how to get an instance from a node.
     fn parse(test_dsl: &TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, TestErrorCase>;
+
+    /// Describe the positional arguments and children these arguments are parsed from
+    ///
+    /// Returns `None` by default, as the shape of the arguments is not known. The tuples of
+    /// [`VerbArgument`]s and the children types of this module return their [`Signature`].
+    fn signature() -> Option<Signature> {
+        None
+    }
+
+    /// Check the parsed arguments, without running anything
+    ///
+    /// It is called by [`TestCase::validate`](crate::test_case::TestCase::validate), for checks
+    /// that go beyond parsing each argument on its own. Returns `Ok(())` by default. The children
+    /// types of this module validate their parameters and each of their children.
+    fn validate(&self) -> miette::Result<()> {
+        Ok(())
+    }
+}
+
+/// A description of the arguments a verb or condition takes
+///
+/// It is returned by [`ParseArguments::signature`] and
+/// [`TestDsl::verb_signature`](crate::TestDsl::verb_signature), for example to show a reference
+/// of the available verbs.
+///
+/// ```
+/// let dsl = test_dsl::TestDsl::<()>::new();
+///
+/// let signature = dsl.verb_signature("repeat").unwrap();
+/// assert_eq!(signature.arguments(), ["usize"]);
+/// assert!(signature.takes_children());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    arguments: Vec<&'static str>,
+    required_arguments: usize,
+    takes_children: bool,
+}
+
+impl Signature {
+    /// Create a signature from the type names of the positional arguments
+    ///
+    /// All of the arguments are required, use
+    /// [`with_required_arguments`](Self::with_required_arguments) if some of them can be left
+    /// out.
+    pub fn new(arguments: Vec<&'static str>) -> Self {
+        Signature {
+            required_arguments: arguments.len(),
+            arguments,
+            takes_children: false,
+        }
+    }
+
+    /// Set how many of the positional arguments have to be given
+    pub fn with_required_arguments(mut self, required_arguments: usize) -> Self {
+        self.required_arguments = required_arguments.min(self.arguments.len());
+        self
+    }
+
+    /// Set whether the node takes children
+    pub fn with_children(mut self, takes_children: bool) -> Self {
+        self.takes_children = takes_children;
+        self
+    }
+
+    /// The type names of the positional arguments, as shown in error messages
+    pub fn arguments(&self) -> &[&'static str] {
+        &self.arguments
+    }
+
+    /// How many of the positional arguments have to be given
+    pub fn required_arguments(&self) -> usize {
+        self.required_arguments
+    }
+
+    /// Whether the node takes children
+    pub fn takes_children(&self) -> bool {
+        self.takes_children
+    }
 }
 
 pub(crate) trait BoxedArguments<H>: std::fmt::Debug + std::any::Any {
     fn clone_box(&self) -> Box<dyn BoxedArguments<H>>;
     fn as_dyn_any(&self) -> &dyn std::any::Any;
+    fn validate_box(&self) -> miette::Result<()>;
 }
 
 impl<H: 'static> Clone for Box<dyn BoxedArguments<H>> {
@@ -36,12 +146,154 @@ impl<H, A: ParseArguments<H>> BoxedArguments<H> for A {
     fn as_dyn_any(&self) -> &dyn std::any::Any {
         self
     }
+
+    fn validate_box(&self) -> miette::Result<()> {
+        self.validate()
+    }
 }
 
 impl<H> ParseArguments<H> for ((),) {
-    fn parse(_test_dsl: &TestDsl<H>, _node: &kdl::KdlNode) -> Result<Self, TestErrorCase> {
+    fn parse(_test_dsl: &TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, TestErrorCase> {
+        parse_end(&mut positional_entries(node), 0)?;
+
         Ok(((),))
     }
+
+    fn signature() -> Option<Signature> {
+        Some(Signature::new(vec![]))
+    }
+}
+
+#[doc(hidden)]
+pub fn positional_entries(node: &kdl::KdlNode) -> impl Iterator<Item = &kdl::KdlEntry> + Clone {
+    node.iter().filter(|entry| entry.name().is_none())
+}
+
+#[doc(hidden)]
+pub fn parse_end(
+    args: &mut dyn Iterator<Item = &kdl::KdlEntry>,
+    total_count: usize,
+) -> Result<(), TestErrorCase> {
+    match args.next() {
+        None => Ok(()),
+        Some(arg) => Err(TestErrorCase::UnexpectedArgument {
+            argument: arg.span(),
+            expected: match total_count {
+                0 => String::from("This verb takes no arguments."),
+                1 => String::from("This verb takes only 1 argument."),
+                count => format!("This verb takes only {count} arguments."),
+            },
+        }),
+    }
+}
+
+fn parse_positional<T: VerbArgument>(
+    node: &kdl::KdlNode,
+    args: &mut dyn Iterator<Item = &kdl::KdlEntry>,
+    total_count: usize,
+    position: usize,
+) -> Result<T, TestErrorCase> {
+    let Some(arg) = args.next() else {
+        return T::from_missing().ok_or_else(|| TestErrorCase::MissingArgument {
+            parent: node.span(),
+            missing: format!(
+                "This verb takes {total_count} arguments, you're missing the {position}th argument."
+            ),
+        });
+    };
+
+    parse_entry(node, arg)
+}
+
+#[doc(hidden)]
+pub fn parse_named<T: VerbArgument>(node: &kdl::KdlNode, key: &str) -> Result<T, TestErrorCase> {
+    let Some(arg) = node.entry(key) else {
+        return T::from_missing().ok_or_else(|| TestErrorCase::MissingArgument {
+            parent: node.span(),
+            missing: format!("This node requires the `{key}` property."),
+        });
+    };
+
+    parse_entry(node, arg)
+}
+
+#[doc(hidden)]
+pub fn parse_named_child<H, T: ParseArguments<H>>(
+    test_dsl: &TestDsl<H>,
+    node: &kdl::KdlNode,
+    key: &str,
+) -> Result<T, TestErrorCase> {
+    let child = node
+        .iter_children()
+        .find(|child| child.name().value() == key)
+        .ok_or_else(|| TestErrorCase::MissingArgument {
+            parent: node.span(),
+            missing: format!("This node requires a `{key}` child."),
+        })?;
+
+    T::parse(test_dsl, child)
+}
+
+pub(crate) fn parse_entry<T: VerbArgument>(
+    node: &kdl::KdlNode,
+    arg: &kdl::KdlEntry,
+) -> Result<T, TestErrorCase> {
+    T::from_value_detailed(arg).map_err(|reason| {
+        if arg.value().is_null() {
+            return TestErrorCase::NullArgument {
+                parent: node.name().span(),
+                argument: arg.span(),
+                expected: format!(
+                    "This verb takes a '{}' as its argument here, which cannot be null.",
+                    T::get_error_type_name()
+                ),
+            };
+        }
+
+        let mut expected = format!(
+            "This verb takes a '{}' as its argument here.",
+            T::get_error_type_name()
+        );
+
+        if let [values @ .., last] = T::accepted_values() {
+            expected.push_str(" It has to be ");
+            if !values.is_empty() {
+                let values = values
+                    .iter()
+                    .map(|value| format!("`{value}`"))
+                    .collect::<Vec<_>>();
+                expected.push_str(&format!("one of {} or ", values.join(", ")));
+            }
+            expected.push_str(&format!("`{last}`."));
+        }
+
+        if let Some(annotation) = T::annotation() {
+            expected.push_str(&format!(" It has to be annotated with `({annotation})`."));
+        }
+
+        if let Some(reason) = reason {
+            expected.push_str(&format!(" {reason}"));
+        }
+
+        TestErrorCase::WrongArgumentType {
+            parent: node.name().span(),
+            argument: arg.span(),
+            expected_type: T::get_error_type_name(),
+            found: value_kind(arg.value()),
+            expected,
+        }
+    })
+}
+
+/// The kind of a KDL value, as shown in error messages
+fn value_kind(value: &kdl::KdlValue) -> &'static str {
+    match value {
+        kdl::KdlValue::String(_) => "string",
+        kdl::KdlValue::Integer(_) => "integer",
+        kdl::KdlValue::Float(_) => "float",
+        kdl::KdlValue::Bool(_) => "bool",
+        kdl::KdlValue::Null => "null",
+    }
 }
 
 macro_rules! impl_parse_arguments {
@@ -56,7 +308,7 @@ macro_rules! impl_parse_arguments {
                 ($($ty,)* $last,): std::fmt::Debug,
         {
             fn parse(_test_dsl: &TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, TestErrorCase> {
-                let mut args = node.iter();
+                let mut args = positional_entries(node);
 
                 let total_count = 1
                     $(
@@ -73,38 +325,25 @@ macro_rules! impl_parse_arguments {
                 let mut running_count = 1;
 
                 $(
-                    let arg = args.next().ok_or_else(|| TestErrorCase::MissingArgument {
-                        parent: node.span(),
-                        missing: format!("This verb takes {} arguments, you're missing the {}th argument.", total_count, running_count),
-                    })?;
-
-                    let $ty = <$ty as VerbArgument>::from_value(arg).ok_or_else(|| {
-                        TestErrorCase::WrongArgumentType {
-                            parent: node.name().span(),
-                            argument: arg.span(),
-                            expected: format!("This verb takes a '{}' as its argument here.", <$ty as VerbArgument>::get_error_type_name()),
-                        }
-                    })?;
+                    let $ty = <$ty as VerbArgument>::parse_positional(node, &mut args, total_count, running_count)?;
                     running_count += 1;
                 )*
 
-                let _ = running_count;
-
-                let arg = args.next().ok_or_else(|| TestErrorCase::MissingArgument {
-                    parent: node.span(),
-                    missing: format!("This verb takes {tc} arguments, you're missing the {tc}th argument.", tc = total_count),
-                })?;
-                let $last = <$last as VerbArgument>::from_value(arg).ok_or_else(|| {
-                    TestErrorCase::WrongArgumentType {
-                        parent: node.name().span(),
-                        argument: arg.span(),
-                        expected: format!("This verb takes a '{}' as its argument here.", <$last as VerbArgument>::get_error_type_name()),
-                    }
-                })?;
+                let $last = <$last as VerbArgument>::parse_positional(node, &mut args, total_count, running_count)?;
 
+                parse_end(&mut args, total_count)?;
 
                 Ok(($($ty,)* $last,))
             }
+
+            fn signature() -> Option<Signature> {
+                let required = [$(<$ty as VerbArgument>::from_missing().is_none(),)* <$last as VerbArgument>::from_missing().is_none()];
+
+                Some(
+                    Signature::new(vec![$(<$ty as VerbArgument>::get_error_type_name(),)* <$last as VerbArgument>::get_error_type_name()])
+                        .with_required_arguments(required.iter().filter(|required| **required).count()),
+                )
+            }
         }
     };
 }
@@ -112,6 +351,25 @@ macro_rules! impl_parse_arguments {
 all_the_tuples!(impl_parse_arguments);
 
 /// A type that can be used as an argument of Verbs and Conditions
+///
+/// With the `derive` feature, it can be derived for enums without fields. A string argument is
+/// matched against the names of the variants, ignoring their case. Use `#[arg(rename = "name")]`
+/// on a variant to match against another name instead.
+///
+/// ```
+/// # #[cfg(feature = "derive")] {
+/// use test_dsl::argument::VerbArgument;
+///
+/// // Parses `paint red` or `paint "dark-blue"`
+/// #[derive(Debug, Clone, VerbArgument)]
+/// enum Color {
+///     Red,
+///     Green,
+///     #[arg(rename = "dark-blue")]
+///     DarkBlue,
+/// }
+/// # }
+/// ```
 pub trait VerbArgument: Clone {
     /// A human-readable typename
     ///
@@ -120,11 +378,124 @@ pub trait VerbArgument: Clone {
         std::any::type_name::<Self>()
     }
 
+    /// The values that are accepted, if there is only a fixed set of them
+    ///
+    /// They are listed in the error message if a value could not be converted. By default, no
+    /// values are listed.
+    fn accepted_values() -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The type annotation the value has to be written with, if any
+    ///
+    /// It is mentioned in the error message if a value could not be converted.
+    fn annotation() -> Option<&'static str> {
+        None
+    }
+
     /// Convert from a [`KdlEntry`](kdl::KdlEntry) to the value
     ///
     /// Implementations are free to accept more than a single way of interpreting values. E.g. a
     /// string and a integer.
     fn from_value(value: &kdl::KdlEntry) -> Option<Self>;
+
+    /// Convert from a [`KdlEntry`](kdl::KdlEntry) to the value, explaining why it was rejected
+    ///
+    /// The reason is added to the help of the error reported for the argument, after the expected
+    /// type. By default, this calls [`from_value`](Self::from_value) and gives no reason.
+    ///
+    /// ```
+    /// # use test_dsl::argument::VerbArgument;
+    /// # use test_dsl::kdl;
+    /// # use test_dsl::verb::FunctionVerb;
+    /// #[derive(Debug, Clone)]
+    /// struct Port(u16);
+    ///
+    /// impl VerbArgument for Port {
+    ///     fn get_error_type_name() -> &'static str {
+    ///         "port"
+    ///     }
+    ///
+    ///     fn from_value(value: &kdl::KdlEntry) -> Option<Self> {
+    ///         Self::from_value_detailed(value).ok()
+    ///     }
+    ///
+    ///     fn from_value_detailed(value: &kdl::KdlEntry) -> Result<Self, Option<String>> {
+    ///         let port = value.value().as_integer().ok_or(None)?;
+    ///
+    ///         match u16::try_from(port) {
+    ///             Ok(port) if port > 0 => Ok(Port(port)),
+    ///             _ => Err(Some(String::from("A port has to be between 1 and 65535."))),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut dsl = test_dsl::TestDsl::<()>::new();
+    /// dsl.add_verb("listen", FunctionVerb::new(|_: &mut (), _port: Port| Ok(())));
+    ///
+    /// let error = dsl.parse_testcase("testcase { listen 70000 }").unwrap_err();
+    /// assert!(format!("{error:?}").contains("A port has to be between 1 and 65535."));
+    /// ```
+    fn from_value_detailed(value: &kdl::KdlEntry) -> Result<Self, Option<String>> {
+        Self::from_value(value).ok_or(None)
+    }
+
+    /// The value to use if the argument was not given at all
+    ///
+    /// Arguments are required by default, so this returns `None` which reports the argument as
+    /// missing.
+    fn from_missing() -> Option<Self> {
+        None
+    }
+
+    #[doc(hidden)]
+    fn parse_positional(
+        node: &kdl::KdlNode,
+        args: &mut dyn Iterator<Item = &kdl::KdlEntry>,
+        total_count: usize,
+        position: usize,
+    ) -> Result<Self, TestErrorCase> {
+        parse_positional(node, args, total_count, position)
+    }
+}
+
+/// Optional arguments
+///
+/// If an entry is present it is parsed as `T`, otherwise the argument is `None`. An explicit
+/// `#null` is `None` as well. Since positional arguments are consumed in order, this is mostly
+/// useful for trailing arguments.
+impl<T: VerbArgument> VerbArgument for Option<T> {
+    fn get_error_type_name() -> &'static str {
+        T::get_error_type_name()
+    }
+
+    fn accepted_values() -> &'static [&'static str] {
+        T::accepted_values()
+    }
+
+    fn annotation() -> Option<&'static str> {
+        T::annotation()
+    }
+
+    fn from_value(value: &kdl::KdlEntry) -> Option<Self> {
+        if value.value().is_null() {
+            return Some(None);
+        }
+
+        T::from_value(value).map(Some)
+    }
+
+    fn from_value_detailed(value: &kdl::KdlEntry) -> Result<Self, Option<String>> {
+        if value.value().is_null() {
+            return Ok(None);
+        }
+
+        T::from_value_detailed(value).map(Some)
+    }
+
+    fn from_missing() -> Option<Self> {
+        Some(None)
+    }
 }
 
 impl VerbArgument for String {
@@ -133,21 +504,363 @@ impl VerbArgument for String {
     }
 }
 
-impl VerbArgument for usize {
+impl VerbArgument for char {
     fn from_value(value: &kdl::KdlEntry) -> Option<Self> {
-        value.value().as_integer().map(|i| i as usize)
+        let mut chars = value.value().as_string()?.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
     }
 }
 
+macro_rules! impl_integer_argument {
+    ($($ty:ty),*) => {
+        $(
+            impl VerbArgument for $ty {
+                fn from_value(value: &kdl::KdlEntry) -> Option<Self> {
+                    value
+                        .value()
+                        .as_integer()
+                        .and_then(|i| <$ty>::try_from(i).ok())
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_argument!(usize, u8, u16, u32, u64, isize, i8, i16, i32, i64);
+
 impl VerbArgument for f64 {
     fn from_value(value: &kdl::KdlEntry) -> Option<Self> {
         value.value().as_float()
     }
 }
 
+impl VerbArgument for f32 {
+    fn from_value(value: &kdl::KdlEntry) -> Option<Self> {
+        let value = value.value().as_float()?;
+        let narrowed = value as f32;
+
+        if value.is_finite() && !narrowed.is_finite() {
+            return None;
+        }
+
+        Some(narrowed)
+    }
+}
+
+/// Booleans
+///
+/// Besides KDL booleans, the strings `true`, `yes` and `on` as well as `false`, `no` and `off` are
+/// accepted, ignoring their case.
 impl VerbArgument for bool {
     fn from_value(value: &kdl::KdlEntry) -> Option<Self> {
-        value.value().as_bool()
+        let value = value.value();
+
+        if let Some(value) = value.as_bool() {
+            return Some(value);
+        }
+
+        let value = value.as_string()?;
+
+        if ["true", "yes", "on"]
+            .iter()
+            .any(|word| value.eq_ignore_ascii_case(word))
+        {
+            Some(true)
+        } else if ["false", "no", "off"]
+            .iter()
+            .any(|word| value.eq_ignore_ascii_case(word))
+        {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/// All remaining positional arguments
+///
+/// When used as the last argument, it consumes every remaining positional entry, parsing each of
+/// them as `T`. If no entries are left, it is empty.
+///
+/// ```
+/// # use test_dsl::argument::Rest;
+/// # use test_dsl::verb::FunctionVerb;
+/// let mut dsl = test_dsl::TestDsl::<usize>::new();
+///
+/// dsl.add_verb(
+///     "sum",
+///     FunctionVerb::new(|h: &mut usize, Rest(numbers): Rest<usize>| {
+///         *h = numbers.iter().sum();
+///         Ok(())
+///     }),
+/// );
+///
+/// let testcases = dsl.parse_testcase("testcase { sum 1 2 3 }").unwrap();
+///
+/// let mut total = 0;
+/// testcases[0].run(&mut total).unwrap();
+/// assert_eq!(total, 6);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Rest<T>(pub Vec<T>);
+
+impl<T: VerbArgument> VerbArgument for Rest<T> {
+    fn get_error_type_name() -> &'static str {
+        T::get_error_type_name()
+    }
+
+    fn accepted_values() -> &'static [&'static str] {
+        T::accepted_values()
+    }
+
+    fn annotation() -> Option<&'static str> {
+        T::annotation()
+    }
+
+    fn from_value(value: &kdl::KdlEntry) -> Option<Self> {
+        T::from_value(value).map(|value| Rest(vec![value]))
+    }
+
+    fn from_value_detailed(value: &kdl::KdlEntry) -> Result<Self, Option<String>> {
+        T::from_value_detailed(value).map(|value| Rest(vec![value]))
+    }
+
+    fn parse_positional(
+        node: &kdl::KdlNode,
+        args: &mut dyn Iterator<Item = &kdl::KdlEntry>,
+        _total_count: usize,
+        _position: usize,
+    ) -> Result<Self, TestErrorCase> {
+        args.map(|arg| parse_entry(node, arg))
+            .collect::<Result<_, _>>()
+            .map(Rest)
+    }
+}
+
+/// The name of a KDL type annotation, used by [`Typed`]
+pub trait TypeAnnotation: std::fmt::Debug + Clone + 'static {
+    /// The annotation, without parentheses
+    const NAME: &'static str;
+}
+
+/// An argument that has to be written with the type annotation `A`
+///
+/// KDL values can be annotated with a type, like `(port)8080`. A value with a different or
+/// without an annotation is rejected, and the error mentions the expected annotation.
+///
+/// ```
+/// # use test_dsl::argument::TypeAnnotation;
+/// # use test_dsl::argument::Typed;
+/// # use test_dsl::verb::FunctionVerb;
+/// #[derive(Debug, Clone)]
+/// struct Port;
+///
+/// impl TypeAnnotation for Port {
+///     const NAME: &'static str = "port";
+/// }
+///
+/// let mut dsl = test_dsl::TestDsl::<u16>::new();
+///
+/// dsl.add_verb(
+///     "listen",
+///     FunctionVerb::new(|h: &mut u16, Typed(port, _): Typed<u16, Port>| {
+///         *h = port;
+///         Ok(())
+///     }),
+/// );
+///
+/// assert!(dsl.parse_testcase("testcase { listen (port)8080 }").is_ok());
+/// assert!(dsl.parse_testcase("testcase { listen 8080 }").is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Typed<T, A>(pub T, pub std::marker::PhantomData<A>);
+
+impl<T: VerbArgument, A: TypeAnnotation> VerbArgument for Typed<T, A> {
+    fn get_error_type_name() -> &'static str {
+        T::get_error_type_name()
+    }
+
+    fn accepted_values() -> &'static [&'static str] {
+        T::accepted_values()
+    }
+
+    fn annotation() -> Option<&'static str> {
+        Some(A::NAME)
+    }
+
+    fn from_value(value: &kdl::KdlEntry) -> Option<Self> {
+        if value.ty()?.value() != A::NAME {
+            return None;
+        }
+
+        T::from_value(value).map(|value| Typed(value, std::marker::PhantomData))
+    }
+
+    fn from_value_detailed(value: &kdl::KdlEntry) -> Result<Self, Option<String>> {
+        if value.ty().map(|ty| ty.value()) != Some(A::NAME) {
+            return Err(None);
+        }
+
+        T::from_value_detailed(value).map(|value| Typed(value, std::marker::PhantomData))
+    }
+}
+
+/// An argument that is parsed from a string using its [`FromStr`](std::str::FromStr) implementation
+///
+/// This allows using your own types as arguments without implementing [`VerbArgument`] for them.
+/// If parsing fails, the argument is reported as having the wrong type.
+///
+/// ```
+/// # use std::net::Ipv4Addr;
+/// # use test_dsl::argument::FromStrArg;
+/// # use test_dsl::verb::FunctionVerb;
+/// let mut dsl = test_dsl::TestDsl::<Vec<Ipv4Addr>>::new();
+///
+/// dsl.add_verb(
+///     "connect",
+///     FunctionVerb::new(|h: &mut Vec<Ipv4Addr>, FromStrArg(ip): FromStrArg<Ipv4Addr>| {
+///         h.push(ip);
+///         Ok(())
+///     }),
+/// );
+///
+/// let testcases = dsl
+///     .parse_testcase(r#"testcase { connect "127.0.0.1" }"#)
+///     .unwrap();
+///
+/// let mut connections = vec![];
+/// testcases[0].run(&mut connections).unwrap();
+/// assert_eq!(connections, [Ipv4Addr::LOCALHOST]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct FromStrArg<T>(pub T);
+
+impl<T: std::str::FromStr + Clone> VerbArgument for FromStrArg<T> {
+    fn get_error_type_name() -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn from_value(value: &kdl::KdlEntry) -> Option<Self> {
+        value
+            .value()
+            .as_string()
+            .and_then(|value| T::from_str(value).ok())
+            .map(FromStrArg)
+    }
+}
+
+/// A number of bytes, given either as an integer or as a string with a unit
+///
+/// Strings consist of an integer followed by one of the units `B`, `KB`, `MB`, `GB`, `TB`
+/// (powers of 1000) or `KiB`, `MiB`, `GiB`, `TiB` (powers of 1024), e.g. `"10MB"` or `"512KiB"`.
+/// Plain integers are interpreted as bytes.
+///
+/// ```
+/// # use test_dsl::argument::ByteSize;
+/// # use test_dsl::verb::FunctionVerb;
+/// let mut dsl = test_dsl::TestDsl::<u64>::new();
+///
+/// dsl.add_verb(
+///     "limit",
+///     FunctionVerb::new(|h: &mut u64, ByteSize(bytes): ByteSize| {
+///         *h += bytes;
+///         Ok(())
+///     }),
+/// );
+///
+/// let testcases = dsl
+///     .parse_testcase(r#"testcase { limit "10MB"; limit "512KiB"; limit 12 }"#)
+///     .unwrap();
+///
+/// let mut total = 0;
+/// testcases[0].run(&mut total).unwrap();
+/// assert_eq!(total, 10_000_000 + 512 * 1024 + 12);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    const UNITS: &[(&str, u64)] = &[
+        ("B", 1),
+        ("KB", 1000),
+        ("MB", 1000 * 1000),
+        ("GB", 1000 * 1000 * 1000),
+        ("TB", 1000 * 1000 * 1000 * 1000),
+        ("KiB", 1 << 10),
+        ("MiB", 1 << 20),
+        ("GiB", 1 << 30),
+        ("TiB", 1 << 40),
+    ];
+
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let split = value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len());
+        let (number, unit) = value.split_at(split);
+
+        let number: u64 = number.parse().ok()?;
+        let unit = unit.trim_start();
+
+        if unit.is_empty() {
+            return Some(ByteSize(number));
+        }
+
+        let (_, factor) = Self::UNITS.iter().find(|(name, _)| *name == unit)?;
+
+        number.checked_mul(*factor).map(ByteSize)
+    }
+}
+
+impl VerbArgument for ByteSize {
+    fn get_error_type_name() -> &'static str {
+        "byte size (e.g. 512, \"10MB\" or \"512KiB\"; units: B, KB, MB, GB, TB, KiB, MiB, GiB, TiB)"
+    }
+
+    fn from_value(value: &kdl::KdlEntry) -> Option<Self> {
+        if let Some(bytes) = value.value().as_integer() {
+            return u64::try_from(bytes).ok().map(ByteSize);
+        }
+
+        value.value().as_string().and_then(ByteSize::parse)
+    }
+}
+
+/// A duration is either an integer of milliseconds, or a string with a unit
+///
+/// Valid units are `ms`, `s` and `m`, for example `"1500ms"` or `"2s"`.
+impl VerbArgument for std::time::Duration {
+    fn get_error_type_name() -> &'static str {
+        "duration (e.g. 500, \"1500ms\" or \"2s\"; units: ms, s, m)"
+    }
+
+    fn from_value(value: &kdl::KdlEntry) -> Option<Self> {
+        if let Some(millis) = value.value().as_integer() {
+            return u64::try_from(millis)
+                .ok()
+                .map(std::time::Duration::from_millis);
+        }
+
+        let value = value.value().as_string()?.trim();
+        let split = value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len());
+        let (number, unit) = value.split_at(split);
+
+        let number: u64 = number.parse().ok()?;
+
+        let millis = match unit.trim_start() {
+            "ms" => number,
+            "s" => number.checked_mul(1000)?,
+            "m" => number.checked_mul(60 * 1000)?,
+            _ => return None,
+        };
+
+        Some(std::time::Duration::from_millis(millis))
     }
 }
 
@@ -187,27 +900,190 @@ impl<H, A> ConditionChildren<H, A> {
     }
 }
 
+/// Collect all values, or all errors if there are any
+fn collect_all<T>(
+    results: impl Iterator<Item = Result<T, TestErrorCase>>,
+) -> Result<Vec<T>, TestErrorCase> {
+    let mut values = vec![];
+    let mut errors = vec![];
+
+    for result in results {
+        match result {
+            Ok(value) => values.push(value),
+            Err(error) => errors.push(error),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(values)
+    } else {
+        Err(TestErrorCase::from_many(errors))
+    }
+}
+
+/// Combine both results, keeping the errors of both if they failed
+pub(crate) fn join<A, B>(
+    a: Result<A, TestErrorCase>,
+    b: Result<B, TestErrorCase>,
+) -> Result<(A, B), TestErrorCase> {
+    match (a, b) {
+        (Ok(a), Ok(b)) => Ok((a, b)),
+        (a, b) => Err(TestErrorCase::from_many(
+            a.err().into_iter().chain(b.err()).collect(),
+        )),
+    }
+}
+
 impl<H: 'static, A: ParseArguments<H>> ParseArguments<H> for ConditionChildren<H, A> {
     fn parse(test_dsl: &TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, error::TestErrorCase> {
-        let arguments = A::parse(test_dsl, node)?;
-
-        let children = node
-            .iter_children()
-            .map(|node| ConditionInstance::with_test_dsl(test_dsl, node))
-            .collect::<Result<_, _>>()?;
+        let (arguments, children) = join(
+            A::parse(test_dsl, node),
+            collect_all(
+                node.iter_children()
+                    .map(|node| ConditionInstance::with_test_dsl(test_dsl, node)),
+            ),
+        )?;
 
         Ok(ConditionChildren {
             parameters: arguments,
             children,
         })
     }
-}
 
-/// Parameters with a list of nodes that are verbs
-pub struct VerbChildren<H, A> {
-    parameters: A,
-    children: Vec<VerbInstance<H>>,
-}
+    fn signature() -> Option<Signature> {
+        A::signature().map(|signature| signature.with_children(true))
+    }
+
+    fn validate(&self) -> miette::Result<()> {
+        self.parameters.validate()?;
+
+        for child in &self.children {
+            child.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// [`ConditionChildren`] that are all either checked now or waited for
+///
+/// Parsing fails if one of the conditions does not support this, so that a testcase like
+/// `assert { some_event }` is rejected before it runs.
+pub(crate) struct SupportedConditionChildren<H, A, const WAIT: bool>(ConditionChildren<H, A>);
+
+/// The conditions of verbs like `assert`, which are checked now
+pub(crate) type NowConditionChildren<H, A> = SupportedConditionChildren<H, A, false>;
+
+/// The conditions of verbs like `wait_for`, which are waited for
+pub(crate) type WaitConditionChildren<H, A> = SupportedConditionChildren<H, A, true>;
+
+impl<H, A: std::fmt::Debug, const WAIT: bool> std::fmt::Debug
+    for SupportedConditionChildren<H, A, WAIT>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SupportedConditionChildren")
+            .field(&self.0)
+            .finish()
+    }
+}
+
+impl<H: 'static, A: Clone, const WAIT: bool> Clone for SupportedConditionChildren<H, A, WAIT> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<H, A, const WAIT: bool> std::ops::Deref for SupportedConditionChildren<H, A, WAIT> {
+    type Target = ConditionChildren<H, A>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<H: 'static, A: ParseArguments<H>, const WAIT: bool> ParseArguments<H>
+    for SupportedConditionChildren<H, A, WAIT>
+{
+    fn parse(test_dsl: &TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, error::TestErrorCase> {
+        let children = ConditionChildren::parse(test_dsl, node)?;
+
+        collect_all(children.children().iter().map(|child| {
+            if WAIT {
+                child.ensure_wait()
+            } else {
+                child.ensure_now()
+            }
+        }))?;
+
+        Ok(SupportedConditionChildren(children))
+    }
+
+    fn signature() -> Option<Signature> {
+        ConditionChildren::<H, A>::signature()
+    }
+
+    fn validate(&self) -> miette::Result<()> {
+        self.0.validate()
+    }
+}
+
+/// [`NowConditionChildren`] with exactly one condition, like the one of `not`
+///
+/// Parsing fails if there is no condition or more than one.
+pub(crate) struct SingleConditionChild<H, A>(NowConditionChildren<H, A>);
+
+impl<H, A: std::fmt::Debug> std::fmt::Debug for SingleConditionChild<H, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SingleConditionChild")
+            .field(&self.0)
+            .finish()
+    }
+}
+
+impl<H: 'static, A: Clone> Clone for SingleConditionChild<H, A> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<H, A> SingleConditionChild<H, A> {
+    pub(crate) fn parameters(&self) -> &A {
+        self.0.parameters()
+    }
+
+    pub(crate) fn child(&self) -> &ConditionInstance<H> {
+        &self.0.children()[0]
+    }
+}
+
+impl<H: 'static, A: ParseArguments<H>> ParseArguments<H> for SingleConditionChild<H, A> {
+    fn parse(test_dsl: &TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, error::TestErrorCase> {
+        let children = NowConditionChildren::<H, A>::parse(test_dsl, node)?;
+
+        match children.children() {
+            [_] => Ok(SingleConditionChild(children)),
+            [] => Err(TestErrorCase::ExpectedCondition { span: node.span() }),
+            [_, extra, ..] => Err(TestErrorCase::UnexpectedArgument {
+                argument: extra.node().span(),
+                expected: format!("`{}` takes exactly one condition.", node.name().value()),
+            }),
+        }
+    }
+
+    fn signature() -> Option<Signature> {
+        NowConditionChildren::<H, A>::signature()
+    }
+
+    fn validate(&self) -> miette::Result<()> {
+        self.0.validate()
+    }
+}
+
+/// Parameters with a list of nodes that are verbs
+pub struct VerbChildren<H, A> {
+    parameters: A,
+    children: Vec<VerbInstance<H>>,
+}
 
 impl<H, A: std::fmt::Debug> std::fmt::Debug for VerbChildren<H, A> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -241,16 +1117,593 @@ impl<H, A> VerbChildren<H, A> {
 
 impl<H: 'static, A: ParseArguments<H>> ParseArguments<H> for VerbChildren<H, A> {
     fn parse(test_dsl: &TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, error::TestErrorCase> {
-        let arguments = A::parse(test_dsl, node)?;
-
-        let children = node
-            .iter_children()
-            .map(|node| VerbInstance::with_test_dsl(test_dsl, node))
-            .collect::<Result<_, _>>()?;
+        let (arguments, children) = join(
+            A::parse(test_dsl, node),
+            collect_all(
+                node.iter_children()
+                    .map(|node| VerbInstance::with_test_dsl(test_dsl, node)),
+            ),
+        )?;
 
         Ok(VerbChildren {
             parameters: arguments,
             children,
         })
     }
+
+    fn signature() -> Option<Signature> {
+        A::signature().map(|signature| signature.with_children(true))
+    }
+
+    fn validate(&self) -> miette::Result<()> {
+        self.parameters.validate()?;
+
+        for child in &self.children {
+            child.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parameters with the children of the node, as they are written
+///
+/// The children are not interpreted in any way, they do not have to be verbs or conditions.
+#[derive(Debug, Clone)]
+pub struct RawChildren<A> {
+    parameters: A,
+    children: Vec<kdl::KdlNode>,
+}
+
+impl<A> RawChildren<A> {
+    /// Get the parameters
+    pub fn parameters(&self) -> &A {
+        &self.parameters
+    }
+
+    /// Get the children
+    pub fn children(&self) -> &[kdl::KdlNode] {
+        &self.children
+    }
+}
+
+impl<H: 'static, A: ParseArguments<H>> ParseArguments<H> for RawChildren<A> {
+    fn parse(test_dsl: &TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, error::TestErrorCase> {
+        Ok(RawChildren {
+            parameters: A::parse(test_dsl, node)?,
+            children: node.iter_children().cloned().collect(),
+        })
+    }
+
+    fn signature() -> Option<Signature> {
+        A::signature().map(|signature| signature.with_children(true))
+    }
+
+    fn validate(&self) -> miette::Result<()> {
+        self.parameters.validate()
+    }
+}
+
+/// Parameters with a list of children that are each parsed as `C`
+///
+/// Contrary to [`VerbChildren`], the children do not have to be registered verbs, their names
+/// are ignored. This allows verbs to take structured data as their children:
+///
+/// ```
+/// # use test_dsl::argument::TypedChildren;
+/// # use test_dsl::context::TestContext;
+/// # use test_dsl::verb::Verb;
+/// #[derive(Debug, Clone)]
+/// struct SumRows;
+///
+/// impl Verb<usize> for SumRows {
+///     type Arguments = TypedChildren<((),), (usize, usize)>;
+///
+///     fn run(
+///         &self,
+///         total: &mut usize,
+///         _context: &mut TestContext,
+///         rows: &Self::Arguments,
+///     ) -> miette::Result<()> {
+///         *total = rows.children().iter().map(|(a, b)| a * b).sum();
+///         Ok(())
+///     }
+/// }
+///
+/// let mut dsl = test_dsl::TestDsl::<usize>::new();
+/// dsl.add_verb("sum_rows", SumRows);
+///
+/// let testcases = dsl
+///     .parse_testcase(
+///         r#"
+///         testcase {
+///             sum_rows {
+///                 row 1 2
+///                 row 3 4
+///             }
+///         }
+///         "#,
+///     )
+///     .unwrap();
+///
+/// let mut total = 0;
+/// testcases[0].run(&mut total).unwrap();
+/// assert_eq!(total, 14);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TypedChildren<A, C> {
+    parameters: A,
+    children: Vec<C>,
+}
+
+impl<A, C> TypedChildren<A, C> {
+    /// Get the parameters
+    pub fn parameters(&self) -> &A {
+        &self.parameters
+    }
+
+    /// Get the parsed children
+    pub fn children(&self) -> &[C] {
+        &self.children
+    }
+}
+
+impl<H: 'static, A: ParseArguments<H>, C: ParseArguments<H>> ParseArguments<H>
+    for TypedChildren<A, C>
+{
+    fn parse(test_dsl: &TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, error::TestErrorCase> {
+        let (parameters, children) = join(
+            A::parse(test_dsl, node),
+            collect_all(node.iter_children().map(|child| C::parse(test_dsl, child))),
+        )?;
+
+        Ok(TypedChildren {
+            parameters,
+            children,
+        })
+    }
+
+    fn signature() -> Option<Signature> {
+        A::signature().map(|signature| signature.with_children(true))
+    }
+
+    fn validate(&self) -> miette::Result<()> {
+        self.parameters.validate()?;
+
+        for child in &self.children {
+            child.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parameters with a list of children that are each a single value
+///
+/// A child is either just a value, like `"a"`, whose name is used, or a node with a single
+/// argument, like `- 1`, whose argument is used. Each of them is parsed as `T`:
+///
+/// ```
+/// # use test_dsl::argument::ValueChildren;
+/// # use test_dsl::context::TestContext;
+/// # use test_dsl::verb::Verb;
+/// #[derive(Debug, Clone)]
+/// struct Allow;
+///
+/// impl Verb<Vec<String>> for Allow {
+///     type Arguments = ValueChildren<((),), String>;
+///
+///     fn run(
+///         &self,
+///         allowed: &mut Vec<String>,
+///         _context: &mut TestContext,
+///         values: &Self::Arguments,
+///     ) -> miette::Result<()> {
+///         allowed.extend_from_slice(values.children());
+///         Ok(())
+///     }
+/// }
+///
+/// let mut dsl = test_dsl::TestDsl::<Vec<String>>::new();
+/// dsl.add_verb("allow", Allow);
+///
+/// let testcases = dsl
+///     .parse_testcase(r#"testcase { allow { "a"; "b"; - "c" } }"#)
+///     .unwrap();
+///
+/// let mut allowed = vec![];
+/// testcases[0].run(&mut allowed).unwrap();
+/// assert_eq!(allowed, ["a", "b", "c"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ValueChildren<A, T> {
+    parameters: A,
+    children: Vec<T>,
+}
+
+impl<A, T> ValueChildren<A, T> {
+    /// Get the parameters
+    pub fn parameters(&self) -> &A {
+        &self.parameters
+    }
+
+    /// Get the values of the children
+    pub fn children(&self) -> &[T] {
+        &self.children
+    }
+}
+
+fn parse_value_child<T: VerbArgument>(child: &kdl::KdlNode) -> Result<T, TestErrorCase> {
+    if let Some(grandchild) = child.iter_children().next() {
+        return Err(TestErrorCase::UnexpectedArgument {
+            argument: grandchild.span(),
+            expected: String::from("A value cannot have children."),
+        });
+    }
+
+    let mut entries = child.iter();
+
+    let Some(entry) = entries.next() else {
+        let name = kdl::KdlEntry::new(child.name().value());
+
+        return T::from_value_detailed(&name).map_err(|reason| {
+            let mut expected = format!("This child has to be a '{}'.", T::get_error_type_name());
+
+            if let Some(reason) = reason {
+                expected.push_str(&format!(" {reason}"));
+            }
+
+            TestErrorCase::WrongArgumentType {
+                parent: child.name().span(),
+                argument: child.name().span(),
+                expected_type: T::get_error_type_name(),
+                found: value_kind(name.value()),
+                expected,
+            }
+        });
+    };
+
+    if entry.name().is_some() {
+        return Err(TestErrorCase::UnexpectedArgument {
+            argument: entry.span(),
+            expected: String::from("A value cannot have properties."),
+        });
+    }
+
+    parse_end(&mut entries, 1)?;
+
+    parse_entry(child, entry)
+}
+
+impl<H: 'static, A: ParseArguments<H>, T: VerbArgument + std::fmt::Debug + 'static>
+    ParseArguments<H> for ValueChildren<A, T>
+{
+    fn parse(test_dsl: &TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, error::TestErrorCase> {
+        let (parameters, children) = join(
+            A::parse(test_dsl, node),
+            collect_all(node.iter_children().map(parse_value_child)),
+        )?;
+
+        Ok(ValueChildren {
+            parameters,
+            children,
+        })
+    }
+
+    fn signature() -> Option<Signature> {
+        A::signature().map(|signature| signature.with_children(true))
+    }
+
+    fn validate(&self) -> miette::Result<()> {
+        self.parameters.validate()
+    }
+}
+
+/// Parameters with a leading condition node followed by a list of verb nodes
+///
+/// This is the building block for verbs that execute their children depending on a condition.
+/// The first child has to be a condition, all following children are verbs.
+pub struct ConditionThenVerbs<H, A> {
+    parameters: A,
+    condition: ConditionInstance<H>,
+    verbs: Vec<VerbInstance<H>>,
+}
+
+impl<H, A: std::fmt::Debug> std::fmt::Debug for ConditionThenVerbs<H, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConditionThenVerbs")
+            .field("parameters", &self.parameters)
+            .field("condition", &self.condition)
+            .field("verbs", &self.verbs)
+            .finish()
+    }
+}
+
+impl<H: 'static, A: Clone> Clone for ConditionThenVerbs<H, A> {
+    fn clone(&self) -> Self {
+        Self {
+            parameters: self.parameters.clone(),
+            condition: self.condition.clone(),
+            verbs: self.verbs.clone(),
+        }
+    }
+}
+
+impl<H, A> ConditionThenVerbs<H, A> {
+    /// Get the parameters
+    pub fn parameters(&self) -> &A {
+        &self.parameters
+    }
+
+    /// Get the leading condition
+    pub fn condition(&self) -> &ConditionInstance<H> {
+        &self.condition
+    }
+
+    /// Get the verbs following the condition
+    pub fn verbs(&self) -> &[VerbInstance<H>] {
+        &self.verbs
+    }
+}
+
+impl<H: 'static, A: ParseArguments<H>> ParseArguments<H> for ConditionThenVerbs<H, A> {
+    fn parse(test_dsl: &TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, error::TestErrorCase> {
+        let mut children = node.iter_children();
+
+        let condition = parse_condition_child(test_dsl, node, children.next());
+        let verbs = collect_all(children.map(|node| VerbInstance::with_test_dsl(test_dsl, node)));
+
+        let ((arguments, condition), verbs) =
+            join(join(A::parse(test_dsl, node), condition), verbs)?;
+
+        Ok(ConditionThenVerbs {
+            parameters: arguments,
+            condition,
+            verbs,
+        })
+    }
+
+    fn signature() -> Option<Signature> {
+        A::signature().map(|signature| signature.with_children(true))
+    }
+
+    fn validate(&self) -> miette::Result<()> {
+        self.parameters.validate()?;
+        self.condition.validate()?;
+
+        for verb in &self.verbs {
+            verb.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_condition_child<H: 'static>(
+    test_dsl: &TestDsl<H>,
+    node: &kdl::KdlNode,
+    condition_node: Option<&kdl::KdlNode>,
+) -> Result<ConditionInstance<H>, TestErrorCase> {
+    let condition_node =
+        condition_node.ok_or_else(|| TestErrorCase::ExpectedCondition { span: node.span() })?;
+
+    ConditionInstance::with_test_dsl(test_dsl, condition_node).map_err(|error| match error {
+        TestErrorCase::UnknownCondition { condition, .. } => {
+            TestErrorCase::ExpectedCondition { span: condition }
+        }
+        error => error,
+    })
+}
+
+/// The arguments of the built-in `if` verb
+///
+/// The first child is the condition, followed by the verbs to run if it holds. An optional last
+/// `else` child contains the verbs to run otherwise.
+pub(crate) struct IfArguments<H> {
+    condition: ConditionInstance<H>,
+    then_verbs: Vec<VerbInstance<H>>,
+    else_verbs: Vec<VerbInstance<H>>,
+}
+
+impl<H> std::fmt::Debug for IfArguments<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IfArguments")
+            .field("condition", &self.condition)
+            .field("then_verbs", &self.then_verbs)
+            .field("else_verbs", &self.else_verbs)
+            .finish()
+    }
+}
+
+impl<H: 'static> Clone for IfArguments<H> {
+    fn clone(&self) -> Self {
+        Self {
+            condition: self.condition.clone(),
+            then_verbs: self.then_verbs.clone(),
+            else_verbs: self.else_verbs.clone(),
+        }
+    }
+}
+
+impl<H> IfArguments<H> {
+    pub(crate) fn condition(&self) -> &ConditionInstance<H> {
+        &self.condition
+    }
+
+    pub(crate) fn then_verbs(&self) -> &[VerbInstance<H>] {
+        &self.then_verbs
+    }
+
+    pub(crate) fn else_verbs(&self) -> &[VerbInstance<H>] {
+        &self.else_verbs
+    }
+}
+
+impl<H: 'static> ParseArguments<H> for IfArguments<H> {
+    fn parse(test_dsl: &TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, error::TestErrorCase> {
+        let mut children = node.iter_children();
+
+        let condition = parse_condition_child(test_dsl, node, children.next())
+            .and_then(|condition| condition.ensure_now().map(|()| condition));
+
+        let mut then_nodes = vec![];
+        let mut else_node = None;
+        let mut misplaced = vec![];
+
+        for child in children {
+            if else_node.is_some() {
+                misplaced.push(Err(TestErrorCase::UnexpectedArgument {
+                    argument: child.span(),
+                    expected: String::from("`else` has to be the last child of `if`."),
+                }));
+            } else if child.name().value() == "else" {
+                else_node = Some(child);
+            } else {
+                then_nodes.push(child);
+            }
+        }
+
+        let then_verbs = collect_all(
+            then_nodes
+                .into_iter()
+                .map(|node| VerbInstance::with_test_dsl(test_dsl, node)),
+        );
+        let else_verbs = collect_all(
+            else_node
+                .into_iter()
+                .flat_map(|node| node.iter_children())
+                .map(|node| VerbInstance::with_test_dsl(test_dsl, node))
+                .chain(misplaced),
+        );
+
+        let ((((), condition), then_verbs), else_verbs) = join(
+            join(
+                join(parse_end(&mut positional_entries(node), 0), condition),
+                then_verbs,
+            ),
+            else_verbs,
+        )?;
+
+        Ok(IfArguments {
+            condition,
+            then_verbs,
+            else_verbs,
+        })
+    }
+
+    fn signature() -> Option<Signature> {
+        Some(Signature::new(vec![]).with_children(true))
+    }
+
+    fn validate(&self) -> miette::Result<()> {
+        self.condition.validate()?;
+
+        for verb in self.then_verbs.iter().chain(&self.else_verbs) {
+            verb.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The arguments of the built-in `repeat_until` verb
+///
+/// All children but the last are the verbs to repeat, the last child is the condition that ends
+/// the loop. The optional `max` property limits the number of iterations.
+pub(crate) struct RepeatUntilArguments<H> {
+    max: usize,
+    verbs: Vec<VerbInstance<H>>,
+    condition: ConditionInstance<H>,
+    span: miette::SourceSpan,
+}
+
+impl<H> std::fmt::Debug for RepeatUntilArguments<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RepeatUntilArguments")
+            .field("max", &self.max)
+            .field("verbs", &self.verbs)
+            .field("condition", &self.condition)
+            .field("span", &self.span)
+            .finish()
+    }
+}
+
+impl<H: 'static> Clone for RepeatUntilArguments<H> {
+    fn clone(&self) -> Self {
+        Self {
+            max: self.max,
+            verbs: self.verbs.clone(),
+            condition: self.condition.clone(),
+            span: self.span,
+        }
+    }
+}
+
+impl<H> RepeatUntilArguments<H> {
+    /// The number of iterations after which `repeat_until` gives up
+    pub(crate) const DEFAULT_MAX: usize = 5000;
+
+    pub(crate) fn max(&self) -> usize {
+        self.max
+    }
+
+    pub(crate) fn verbs(&self) -> &[VerbInstance<H>] {
+        &self.verbs
+    }
+
+    pub(crate) fn condition(&self) -> &ConditionInstance<H> {
+        &self.condition
+    }
+
+    pub(crate) fn span(&self) -> miette::SourceSpan {
+        self.span
+    }
+}
+
+impl<H: 'static> ParseArguments<H> for RepeatUntilArguments<H> {
+    fn parse(test_dsl: &TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, error::TestErrorCase> {
+        let max = node
+            .entry("max")
+            .map(|entry| parse_entry(node, entry))
+            .transpose()
+            .map(|max| max.unwrap_or(Self::DEFAULT_MAX));
+
+        let mut children: Vec<_> = node.iter_children().collect();
+        let condition = parse_condition_child(test_dsl, node, children.pop())
+            .and_then(|condition| condition.ensure_now().map(|()| condition));
+        let verbs = collect_all(
+            children
+                .into_iter()
+                .map(|node| VerbInstance::with_test_dsl(test_dsl, node)),
+        );
+
+        let ((((), max), verbs), condition) = join(
+            join(
+                join(parse_end(&mut positional_entries(node), 0), max),
+                verbs,
+            ),
+            condition,
+        )?;
+
+        Ok(RepeatUntilArguments {
+            max,
+            verbs,
+            condition,
+            span: node.span(),
+        })
+    }
+
+    fn signature() -> Option<Signature> {
+        Some(Signature::new(vec![]).with_children(true))
+    }
+
+    fn validate(&self) -> miette::Result<()> {
+        for verb in &self.verbs {
+            verb.validate()?;
+        }
+
+        self.condition.validate()?;
+
+        Ok(())
+    }
 }
@@ -5,7 +5,9 @@ use std::marker::PhantomData;
 
 use crate::BoxedArguments;
 use crate::argument::ParseArguments;
+use crate::argument::Signature;
 use crate::argument::VerbArgument;
+use crate::context::TestContext;
 use crate::error::TestErrorCase;
 
 /// A condition check for a given property
@@ -23,7 +25,14 @@ pub trait Condition<H>: std::fmt::Debug + Clone + 'static {
     ///
     /// If the condition cannot properly support the concept of 'checking now' it is ok to simply
     /// return an error.
-    fn check_now(&self, harness: &H, arguments: &Self::Arguments) -> miette::Result<bool>;
+    ///
+    /// The [`TestContext`] contains the values captured by earlier verbs of the same run.
+    fn check_now(
+        &self,
+        harness: &H,
+        context: &TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<bool>;
 
     /// Wait until a given condition evaluates to a meaningful value
     ///
@@ -32,16 +41,103 @@ pub trait Condition<H>: std::fmt::Debug + Clone + 'static {
     ///
     /// If the condition cannot properly support the concept of 'waiting until it has a value', it
     /// is ok to simply return an error.
-    fn wait_until(&self, harness: &H, arguments: &Self::Arguments) -> miette::Result<bool>;
+    fn wait_until(
+        &self,
+        harness: &H,
+        context: &TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<bool>;
+
+    /// Whether [`check_now`](Self::check_now) is implemented
+    ///
+    /// Verbs that check their conditions now, like `assert`, reject conditions returning `false`
+    /// when the testcase is parsed. Defaults to `true`.
+    fn supports_now(&self) -> bool {
+        true
+    }
+
+    /// Whether [`wait_until`](Self::wait_until) is implemented
+    ///
+    /// Verbs that wait for their conditions, like `wait_for`, reject conditions returning `false`
+    /// when the testcase is parsed. Defaults to `true`.
+    fn supports_wait(&self) -> bool {
+        true
+    }
+}
+
+/// A condition check that needs mutable access to the harness
+///
+/// Some properties can only be checked by probing the system, for example by performing an
+/// operation and rolling it back afterwards. A [`MutCondition`] receives the harness mutably to
+/// do so.
+///
+/// **Contract:** Implementations must leave the harness in the same observable state it was in
+/// before the check, regardless of whether the check passed, failed or returned an error.
+/// `test-dsl` cannot verify this, it trusts the implementation to clean up after itself.
+///
+/// Prefer [`Condition`] whenever the check can be done through a shared reference.
+pub trait MutCondition<H>: std::fmt::Debug + Clone + 'static {
+    /// The arguments for this condition
+    type Arguments: ParseArguments<H>;
+
+    /// Run the check now, restoring any state that was changed while doing so
+    fn check_now_mut(
+        &self,
+        harness: &mut H,
+        context: &TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<bool>;
+}
+
+/// The future of an [`AsyncCondition`] that is waited for
+///
+/// It is not required to be [`Send`], so that it can borrow the harness.
+#[cfg(feature = "async")]
+pub type ConditionFuture<'a> = std::pin::Pin<Box<dyn Future<Output = miette::Result<bool>> + 'a>>;
+
+/// A condition that is waited for asynchronously
+///
+/// This is the right fit for event-like properties, for example a message arriving on a channel:
+/// instead of being polled over and over, the returned future completes once the event happened.
+///
+/// Async conditions are added with [`TestDsl::add_async_condition`](crate::TestDsl::add_async_condition)
+/// and awaited by the built-in `wait_for` verb when the testcase is run with
+/// [`TestCase::run_async`](crate::test_case::TestCase::run_async). They cannot be checked now, so
+/// using them anywhere else is an error.
+///
+/// This trait is only available with the `async` feature.
+#[cfg(feature = "async")]
+pub trait AsyncCondition<H>: std::fmt::Debug + Clone + 'static {
+    /// The arguments for this condition
+    type Arguments: ParseArguments<H>;
+
+    /// Wait until the condition evaluates to a meaningful value
+    ///
+    /// If the future returns `Ok(false)`, `wait_for` waits for its interval and then calls this
+    /// method again.
+    fn wait_until(
+        &self,
+        harness: &H,
+        context: &TestContext,
+        arguments: &Self::Arguments,
+    ) -> impl Future<Output = miette::Result<bool>>;
 }
 
 pub(crate) struct ErasedCondition<H> {
     condition: Box<dyn Any>,
     fn_parse_args:
         fn(&crate::TestDsl<H>, &kdl::KdlNode) -> Result<Box<dyn BoxedArguments<H>>, TestErrorCase>,
-    fn_check_now: fn(&dyn Any, &H, &dyn Any) -> miette::Result<bool>,
-    fn_wait_util: fn(&dyn Any, &H, &dyn Any) -> miette::Result<bool>,
+    fn_check_now: fn(&dyn Any, &H, &TestContext, &dyn Any) -> miette::Result<bool>,
+    fn_check_now_mut: Option<fn(&dyn Any, &mut H, &TestContext, &dyn Any) -> miette::Result<bool>>,
+    fn_wait_util: fn(&dyn Any, &H, &TestContext, &dyn Any) -> miette::Result<bool>,
+    #[cfg(feature = "async")]
+    fn_wait_until_async:
+        for<'a> fn(&'a dyn Any, &'a H, &'a TestContext, &'a dyn Any) -> ConditionFuture<'a>,
     fn_clone: fn(&dyn Any) -> Box<dyn Any>,
+    arguments_name: &'static str,
+    signature: Option<Signature>,
+    supports_now: bool,
+    supports_wait: bool,
 }
 
 impl<H> std::fmt::Debug for ErasedCondition<H> {
@@ -50,8 +146,13 @@ impl<H> std::fmt::Debug for ErasedCondition<H> {
             .field("condition", &self.condition)
             .field("fn_parse_args", &self.fn_parse_args)
             .field("fn_check_now", &self.fn_check_now)
+            .field("fn_check_now_mut", &self.fn_check_now_mut)
             .field("fn_wait_util", &self.fn_wait_util)
             .field("fn_clone", &self.fn_clone)
+            .field("arguments_name", &self.arguments_name)
+            .field("signature", &self.signature)
+            .field("supports_now", &self.supports_now)
+            .field("supports_wait", &self.supports_wait)
             .finish()
     }
 }
@@ -62,8 +163,15 @@ impl<H> Clone for ErasedCondition<H> {
             condition: (self.fn_clone)(&*self.condition),
             fn_parse_args: self.fn_parse_args,
             fn_check_now: self.fn_check_now,
+            fn_check_now_mut: self.fn_check_now_mut,
             fn_wait_util: self.fn_wait_util,
+            #[cfg(feature = "async")]
+            fn_wait_until_async: self.fn_wait_until_async,
             fn_clone: self.fn_clone,
+            arguments_name: self.arguments_name,
+            signature: self.signature.clone(),
+            supports_now: self.supports_now,
+            supports_wait: self.supports_wait,
         }
     }
 }
@@ -72,6 +180,55 @@ impl<H> ErasedCondition<H> {
     pub(crate) fn erase<C>(condition: C) -> Self
     where
         C: Condition<H>,
+    {
+        let condition_supports_now = condition.supports_now();
+        let condition_supports_wait = condition.supports_wait();
+
+        ErasedCondition {
+            condition: Box::new(condition),
+            fn_parse_args: |test_dsl, node| {
+                <C::Arguments as ParseArguments<H>>::parse(test_dsl, node).map(|a| {
+                    let args = Box::new(a);
+                    args as _
+                })
+            },
+            fn_check_now: |this, harness, context, arguments| {
+                let this: &C = this.downcast_ref().unwrap();
+                let arguments: &C::Arguments = arguments.downcast_ref().unwrap();
+
+                this.check_now(harness, context, arguments)
+            },
+            fn_check_now_mut: None,
+            fn_wait_util: |this, harness, context, arguments| {
+                let this: &C = this.downcast_ref().unwrap();
+                let arguments: &C::Arguments = arguments.downcast_ref().unwrap();
+
+                this.wait_until(harness, context, arguments)
+            },
+            #[cfg(feature = "async")]
+            fn_wait_until_async: |this, harness, context, arguments| {
+                let this: &C = this.downcast_ref().unwrap();
+                let arguments: &C::Arguments = arguments.downcast_ref().unwrap();
+
+                Box::pin(std::future::ready(
+                    this.wait_until(harness, context, arguments),
+                ))
+            },
+            fn_clone: |this| {
+                let this: &C = this.downcast_ref().unwrap();
+
+                Box::new(this.clone())
+            },
+            arguments_name: std::any::type_name::<C::Arguments>(),
+            signature: <C::Arguments as ParseArguments<H>>::signature(),
+            supports_now: condition_supports_now,
+            supports_wait: condition_supports_wait,
+        }
+    }
+
+    pub(crate) fn erase_mut<C>(condition: C) -> Self
+    where
+        C: MutCondition<H>,
     {
         ErasedCondition {
             condition: Box::new(condition),
@@ -81,26 +238,104 @@ impl<H> ErasedCondition<H> {
                     args as _
                 })
             },
-            fn_check_now: |this, harness, arguments| {
+            fn_check_now: |_this, _harness, _context, _arguments| {
+                Err(TestErrorCase::InvalidCondition {
+                    error: miette::miette!("This condition requires mutable access to the harness"),
+                }
+                .into())
+            },
+            fn_check_now_mut: Some(|this, harness, context, arguments| {
                 let this: &C = this.downcast_ref().unwrap();
                 let arguments: &C::Arguments = arguments.downcast_ref().unwrap();
 
-                this.check_now(harness, arguments)
+                this.check_now_mut(harness, context, arguments)
+            }),
+            fn_wait_util: |_this, _harness, _context, _arguments| {
+                Err(TestErrorCase::InvalidCondition {
+                    error: miette::miette!("This condition does not support waiting"),
+                }
+                .into())
+            },
+            #[cfg(feature = "async")]
+            fn_wait_until_async: |_this, _harness, _context, _arguments| {
+                Box::pin(std::future::ready(Err(TestErrorCase::InvalidCondition {
+                    error: miette::miette!("This condition does not support waiting"),
+                }
+                .into())))
+            },
+            fn_clone: |this| {
+                let this: &C = this.downcast_ref().unwrap();
+
+                Box::new(this.clone())
+            },
+            arguments_name: std::any::type_name::<C::Arguments>(),
+            signature: <C::Arguments as ParseArguments<H>>::signature(),
+            supports_now: true,
+            supports_wait: false,
+        }
+    }
+
+    /// Erase a condition that can only be waited for asynchronously
+    #[cfg(feature = "async")]
+    pub(crate) fn erase_async<C>(condition: C) -> Self
+    where
+        C: AsyncCondition<H>,
+    {
+        ErasedCondition {
+            condition: Box::new(condition),
+            fn_parse_args: |test_dsl, node| {
+                <C::Arguments as ParseArguments<H>>::parse(test_dsl, node).map(|a| {
+                    let args = Box::new(a);
+                    args as _
+                })
+            },
+            fn_check_now: |_this, _harness, _context, _arguments| {
+                Err(TestErrorCase::InvalidCondition {
+                    error: miette::miette!("This condition can only be waited for asynchronously"),
+                }
+                .into())
+            },
+            fn_check_now_mut: None,
+            fn_wait_util: |_this, _harness, _context, _arguments| {
+                Err(TestErrorCase::InvalidCondition {
+                    error: miette::miette!("This condition can only be waited for asynchronously"),
+                }
+                .into())
             },
-            fn_wait_util: |this, harness, arguments| {
+            fn_wait_until_async: |this, harness, context, arguments| {
                 let this: &C = this.downcast_ref().unwrap();
                 let arguments: &C::Arguments = arguments.downcast_ref().unwrap();
 
-                this.wait_until(harness, arguments)
+                Box::pin(this.wait_until(harness, context, arguments))
             },
             fn_clone: |this| {
                 let this: &C = this.downcast_ref().unwrap();
 
                 Box::new(this.clone())
             },
+            arguments_name: std::any::type_name::<C::Arguments>(),
+            signature: <C::Arguments as ParseArguments<H>>::signature(),
+            supports_now: false,
+            supports_wait: true,
         }
     }
 
+    pub(crate) fn arguments_name(&self) -> &'static str {
+        self.arguments_name
+    }
+
+    pub(crate) fn signature(&self) -> Option<&Signature> {
+        self.signature.as_ref()
+    }
+
+    pub(crate) fn supports_now(&self) -> bool {
+        self.supports_now
+    }
+
+    pub(crate) fn supports_wait(&self) -> bool {
+        self.supports_wait
+    }
+
     pub(crate) fn parse_args(
         &self,
         test_dsl: &crate::TestDsl<H>,
@@ -109,8 +344,35 @@ impl<H> ErasedCondition<H> {
         (self.fn_parse_args)(test_dsl, node)
     }
 
-    pub(crate) fn check_now(&self, harness: &H, arguments: &dyn Any) -> miette::Result<bool> {
-        (self.fn_check_now)(&*self.condition, harness, arguments)
+    pub(crate) fn wait_until(
+        &self,
+        harness: &H,
+        context: &TestContext,
+        arguments: &dyn Any,
+    ) -> miette::Result<bool> {
+        (self.fn_wait_util)(&*self.condition, harness, context, arguments)
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn wait_until_async<'a>(
+        &'a self,
+        harness: &'a H,
+        context: &'a TestContext,
+        arguments: &'a dyn Any,
+    ) -> ConditionFuture<'a> {
+        (self.fn_wait_until_async)(&*self.condition, harness, context, arguments)
+    }
+
+    pub(crate) fn check_now(
+        &self,
+        harness: &mut H,
+        context: &TestContext,
+        arguments: &dyn Any,
+    ) -> miette::Result<bool> {
+        match self.fn_check_now_mut {
+            Some(check_now_mut) => check_now_mut(&*self.condition, harness, context, arguments),
+            None => (self.fn_check_now)(&*self.condition, harness, context, arguments),
+        }
     }
 }
 
@@ -196,6 +458,26 @@ impl<H, T> std::fmt::Debug for FunctionCondition<H, T> {
 }
 
 impl<H, T> FunctionCondition<H, T> {
+    /// Create a new [`FunctionCondition`] that can be called in direct contexts
+    ///
+    /// This is the same as [`new_now`](Self::new_now), and mirrors [`FunctionVerb::new`](crate::verb::FunctionVerb::new).
+    ///
+    /// ```
+    /// # use test_dsl::condition::FunctionCondition;
+    /// let mut dsl = test_dsl::TestDsl::<()>::new();
+    ///
+    /// dsl.add_condition("always", FunctionCondition::new(|_h: &()| Ok(true)));
+    ///
+    /// let testcases = dsl.parse_testcase("testcase { assert { always } }").unwrap();
+    /// testcases[0].run(&mut ()).unwrap();
+    /// ```
+    pub fn new<C>(now: C) -> Self
+    where
+        C: Checker<H, T>,
+    {
+        Self::new_now(now)
+    }
+
     /// Create a new [`FunctionCondition`] that can be called in direct contexts
     ///
     /// For example the `assert` verb allows you to verify multiple [`Condition`]s (of which [`FunctionCondition`] is one way to create one).
@@ -301,7 +583,12 @@ where
     T: ParseArguments<H>,
 {
     type Arguments = T;
-    fn check_now(&self, harness: &H, arguments: &T) -> miette::Result<bool> {
+    fn check_now(
+        &self,
+        harness: &H,
+        _context: &TestContext,
+        arguments: &T,
+    ) -> miette::Result<bool> {
         let Some(check) = self.now.as_ref().map(|now| now.check(harness, arguments)) else {
             return Err(TestErrorCase::InvalidCondition {
                 error: miette::miette!("FunctionCondition does not implement checking now"),
@@ -312,14 +599,358 @@ where
         check
     }
 
-    fn wait_until(&self, harness: &H, node: &T) -> miette::Result<bool> {
+    fn wait_until(&self, harness: &H, _context: &TestContext, node: &T) -> miette::Result<bool> {
         let Some(check) = self.wait.as_ref().map(|wait| wait.check(harness, node)) else {
             return Err(TestErrorCase::InvalidCondition {
-                error: miette::miette!("FunctionCondition does not implement checking now"),
+                error: miette::miette!("FunctionCondition does not implement waiting"),
             }
             .into());
         };
 
         check
     }
+
+    fn supports_now(&self) -> bool {
+        self.now.is_some()
+    }
+
+    fn supports_wait(&self) -> bool {
+        self.wait.is_some()
+    }
+}
+
+/// A [`MutChecker`] is the instance that executes when a [`FunctionMutCondition`] evaluates
+///
+/// Contrary to a [`Checker`], it receives the harness mutably. It is implemented for closures of
+/// up to 16 arguments.
+pub trait MutChecker<H, T>: Clone + 'static {
+    /// Execute the check with the given node
+    fn check_mut(&self, harness: &mut H, arguments: &T) -> miette::Result<bool>;
+}
+
+impl<H, F> MutChecker<H, ((),)> for F
+where
+    F: Fn(&mut H) -> miette::Result<bool>,
+    F: Clone + 'static,
+{
+    fn check_mut(&self, harness: &mut H, _arguments: &((),)) -> miette::Result<bool> {
+        self(harness)
+    }
+}
+
+macro_rules! impl_mut_callable {
+    (
+        [$($ty:ident),*], $last:ident
+    ) => {
+        #[allow(non_snake_case, unused_mut)]
+        impl<H, F, $($ty,)* $last> MutChecker<H, ($($ty,)* $last,)> for F
+            where
+                F: Fn(&mut H, $($ty,)* $last,) -> miette::Result<bool>,
+                F: Clone + 'static,
+                $( $ty: VerbArgument, )*
+                $last: VerbArgument,
+        {
+            fn check_mut(&self, harness: &mut H, node: &($($ty,)* $last,)) -> miette::Result<bool> {
+                let ($($ty,)* $last,) = node.clone();
+                self(harness, $($ty,)* $last,)
+            }
+        }
+    };
+}
+
+all_the_tuples!(impl_mut_callable);
+
+/// A [`MutCondition`] defined through a closure/function
+///
+/// The closure receives the harness mutably, for harnesses that only expose their state through
+/// `&mut` methods. It has to uphold the contract of [`MutCondition`] and leave the harness in the
+/// same observable state.
+///
+/// ```
+/// # use test_dsl::condition::FunctionMutCondition;
+/// let mut dsl = test_dsl::TestDsl::<Vec<usize>>::new();
+///
+/// dsl.add_mut_condition(
+///     "last_is",
+///     FunctionMutCondition::new(|h: &mut Vec<usize>, expected: usize| {
+///         let last = h.pop();
+///         h.extend(last);
+///         Ok(last == Some(expected))
+///     }),
+/// );
+///
+/// let testcases = dsl
+///     .parse_testcase("testcase { assert { last_is 3 } }")
+///     .unwrap();
+/// testcases[0].run(&mut vec![1, 2, 3]).unwrap();
+/// ```
+pub struct FunctionMutCondition<H, T> {
+    checker: Box<dyn Any>,
+    check_fn: fn(&dyn Any, &mut H, &T) -> miette::Result<bool>,
+    clone_fn: fn(&dyn Any) -> Box<dyn Any>,
+}
+
+impl<H, T> std::fmt::Debug for FunctionMutCondition<H, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FunctionMutCondition")
+            .field("checker", &self.checker)
+            .field("check_fn", &self.check_fn)
+            .field("clone_fn", &self.clone_fn)
+            .finish()
+    }
+}
+
+impl<H, T> Clone for FunctionMutCondition<H, T> {
+    fn clone(&self) -> Self {
+        FunctionMutCondition {
+            checker: (self.clone_fn)(&*self.checker),
+            check_fn: self.check_fn,
+            clone_fn: self.clone_fn,
+        }
+    }
+}
+
+impl<H, T> FunctionMutCondition<H, T> {
+    /// Create a new [`FunctionMutCondition`] using a closure/function
+    pub fn new<C>(checker: C) -> Self
+    where
+        C: MutChecker<H, T>,
+    {
+        FunctionMutCondition {
+            checker: Box::new(checker),
+            check_fn: |this, harness, node| {
+                let this: &C = this.downcast_ref().unwrap();
+
+                this.check_mut(harness, node)
+            },
+            clone_fn: |this| {
+                let this: &C = this.downcast_ref().unwrap();
+
+                Box::new(this.clone())
+            },
+        }
+    }
+}
+
+impl<H, T> MutCondition<H> for FunctionMutCondition<H, T>
+where
+    H: 'static,
+    T: ParseArguments<H>,
+{
+    type Arguments = T;
+    fn check_now_mut(
+        &self,
+        harness: &mut H,
+        _context: &TestContext,
+        arguments: &T,
+    ) -> miette::Result<bool> {
+        (self.check_fn)(&*self.checker, harness, arguments)
+    }
+}
+
+/// A condition that checks that a float of the harness is approximately equal to a value
+///
+/// It takes the expected value and the tolerance as arguments, and holds when
+/// `|actual - expected| <= tolerance`. Otherwise it does not hold, and all three values show up in
+/// the report. A `NaN` value never holds.
+///
+/// ```
+/// # use test_dsl::condition::ApproxEq;
+/// let mut dsl = test_dsl::TestDsl::<f64>::new();
+///
+/// dsl.add_condition("approx_eq", ApproxEq::new(|h: &f64| *h));
+///
+/// let testcases = dsl
+///     .parse_testcase("testcase { assert { approx_eq 3.14159 0.001 } }")
+///     .unwrap();
+///
+/// testcases[0].run(&mut 3.1416).unwrap();
+/// ```
+pub struct ApproxEq<H> {
+    projection: fn(&H) -> f64,
+}
+
+impl<H> std::fmt::Debug for ApproxEq<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApproxEq")
+            .field("projection", &self.projection)
+            .finish()
+    }
+}
+
+impl<H> Clone for ApproxEq<H> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<H> Copy for ApproxEq<H> {}
+
+impl<H> ApproxEq<H> {
+    /// Create a new condition comparing the value returned by `projection`
+    pub fn new(projection: fn(&H) -> f64) -> Self {
+        ApproxEq { projection }
+    }
+}
+
+impl<H: 'static> Condition<H> for ApproxEq<H> {
+    type Arguments = (f64, f64);
+
+    fn check_now(
+        &self,
+        harness: &H,
+        context: &TestContext,
+        &(expected, tolerance): &Self::Arguments,
+    ) -> miette::Result<bool> {
+        let actual = (self.projection)(harness);
+
+        // Comparisons with NaN are always false, so this only holds for actual numbers
+        let within = (actual - expected).abs() <= tolerance;
+
+        if !within {
+            context.note_failure(format!(
+                "It was {actual}, but expected {expected} ± {tolerance}"
+            ));
+        }
+
+        Ok(within)
+    }
+
+    fn wait_until(
+        &self,
+        harness: &H,
+        context: &TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<bool> {
+        self.check_now(harness, context, arguments)
+    }
+}
+
+/// A condition that checks that a text of the harness matches a regular expression
+///
+/// It takes the pattern as its argument, and holds if the pattern matches anywhere in the text.
+/// Otherwise it does not hold, and the report shows the text. A pattern that is not a valid
+/// regular expression is rejected when the testcase is parsed.
+///
+/// This condition is only available with the `regex` feature.
+///
+/// ```
+/// # use test_dsl::condition::Matches;
+/// let mut dsl = test_dsl::TestDsl::<String>::new();
+///
+/// dsl.add_condition("matches", Matches::new(|log: &String| log.as_str()));
+///
+/// let testcases = dsl
+///     .parse_testcase(r#"testcase { assert { matches "ERROR.*timeout" } }"#)
+///     .unwrap();
+///
+/// testcases[0]
+///     .run(&mut String::from("ERROR: request timeout"))
+///     .unwrap();
+/// ```
+#[cfg(feature = "regex")]
+pub struct Matches<H> {
+    projection: fn(&H) -> &str,
+}
+
+#[cfg(feature = "regex")]
+impl<H> std::fmt::Debug for Matches<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Matches")
+            .field("projection", &self.projection)
+            .finish()
+    }
+}
+
+#[cfg(feature = "regex")]
+impl<H> Clone for Matches<H> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+#[cfg(feature = "regex")]
+impl<H> Copy for Matches<H> {}
+
+#[cfg(feature = "regex")]
+impl<H> Matches<H> {
+    /// Create a new condition matching the text returned by `projection`
+    pub fn new(projection: fn(&H) -> &str) -> Self {
+        Matches { projection }
+    }
+}
+
+/// The arguments of a [`Matches`] condition
+#[cfg(feature = "regex")]
+#[derive(Debug, Clone)]
+pub struct MatchesArguments {
+    pattern: regex::Regex,
+}
+
+#[cfg(feature = "regex")]
+impl MatchesArguments {
+    /// The pattern to match against
+    pub fn pattern(&self) -> &regex::Regex {
+        &self.pattern
+    }
+}
+
+#[cfg(feature = "regex")]
+impl<H> ParseArguments<H> for MatchesArguments {
+    fn parse(test_dsl: &crate::TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, TestErrorCase> {
+        let (pattern,) = <(String,) as ParseArguments<H>>::parse(test_dsl, node)?;
+
+        let pattern = regex::Regex::new(&pattern).map_err(|error| {
+            let span = crate::argument::positional_entries(node)
+                .next()
+                .map_or(node.span(), |entry| entry.span());
+
+            TestErrorCase::InvalidCondition {
+                error: miette::miette!(
+                    labels = vec![miette::LabeledSpan::at(span, "this pattern")],
+                    "{error}"
+                ),
+            }
+        })?;
+
+        Ok(MatchesArguments { pattern })
+    }
+
+    fn signature() -> Option<Signature> {
+        <(String,) as ParseArguments<H>>::signature()
+    }
+}
+
+#[cfg(feature = "regex")]
+impl<H: 'static> Condition<H> for Matches<H> {
+    type Arguments = MatchesArguments;
+
+    fn check_now(
+        &self,
+        harness: &H,
+        context: &TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<bool> {
+        let text = (self.projection)(harness);
+
+        let is_match = arguments.pattern.is_match(text);
+
+        if !is_match {
+            context.note_failure(format!(
+                "The text was {text:?}, which does not match `{}`",
+                arguments.pattern
+            ));
+        }
+
+        Ok(is_match)
+    }
+
+    fn wait_until(
+        &self,
+        harness: &H,
+        context: &TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<bool> {
+        self.check_now(harness, context, arguments)
+    }
 }
@@ -0,0 +1,394 @@
+//! State that lives for the duration of a single testcase run
+//!
+//! Verbs can use the [`TestContext`] to pass values to the verbs and conditions that run after
+//! them. Verbs of a testcase run from top to bottom, and children of a verb run when that verb
+//! runs them. A value is visible to everything that runs after it was
+//! [`set`](TestContext::set), and every run starts with an empty context.
+//!
+//! ```
+//! # use test_dsl::context::TestContext;
+//! # use test_dsl::verb::Verb;
+//! #[derive(Debug, Clone)]
+//! struct CreateUser;
+//!
+//! impl Verb<Vec<String>> for CreateUser {
+//!     type Arguments = (String, String);
+//!
+//!     fn run(
+//!         &self,
+//!         users: &mut Vec<String>,
+//!         context: &mut TestContext,
+//!         (name, capture): &Self::Arguments,
+//!     ) -> miette::Result<()> {
+//!         users.push(name.clone());
+//!         context.set(capture, users.len() - 1);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! #[derive(Debug, Clone)]
+//! struct DeleteUser;
+//!
+//! impl Verb<Vec<String>> for DeleteUser {
+//!     type Arguments = (String,);
+//!
+//!     fn run(
+//!         &self,
+//!         users: &mut Vec<String>,
+//!         context: &mut TestContext,
+//!         (id,): &Self::Arguments,
+//!     ) -> miette::Result<()> {
+//!         let id = *context.value::<usize>(id)?;
+//!         users.remove(id);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let mut dsl = test_dsl::TestDsl::<Vec<String>>::new();
+//! dsl.add_verb("create_user", CreateUser);
+//! dsl.add_verb("delete_user", DeleteUser);
+//!
+//! let testcases = dsl
+//!     .parse_testcase(
+//!         r#"
+//!         testcase {
+//!             create_user "alice" "id"
+//!             delete_user "id"
+//!         }
+//!         testcase {
+//!             delete_user "id"
+//!         }
+//!         "#,
+//!     )
+//!     .unwrap();
+//!
+//! let mut users = vec![];
+//! testcases[0].run(&mut users).unwrap();
+//! assert!(users.is_empty());
+//!
+//! // The second testcase never stored an `id`
+//! assert!(testcases[1].run(&mut users).is_err());
+//! ```
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::TestCaseInput;
+use crate::error::ContextValueError;
+use crate::observer::ObserverHandle;
+
+/// The context of a single run of a [`TestCase`](crate::test_case::TestCase)
+///
+/// A fresh context is created for every run and handed to each [`Verb`](crate::verb::Verb) as
+/// it executes.
+#[derive(Debug, Clone)]
+pub struct TestContext {
+    seed: u64,
+    rng_state: u64,
+    verbs_started: u64,
+    verb_seeds: Vec<u64>,
+    values: HashMap<String, Arc<dyn Any>>,
+    observer: Option<ObserverHandle>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    failure_note: RefCell<Option<String>>,
+    output: Option<OutputHandle>,
+    source_code: Option<TestCaseInput>,
+    #[cfg(feature = "serde")]
+    trace: Option<crate::trace::TraceCollector>,
+}
+
+/// Where the `print` verb writes to, shared with the [`TestDsl`](crate::TestDsl) it was set on
+#[derive(Clone)]
+pub(crate) struct OutputHandle(Rc<RefCell<Box<dyn std::io::Write>>>);
+
+impl std::fmt::Debug for OutputHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("OutputHandle").finish_non_exhaustive()
+    }
+}
+
+impl OutputHandle {
+    pub(crate) fn new(output: impl std::io::Write + 'static) -> Self {
+        OutputHandle(Rc::new(RefCell::new(Box::new(output))))
+    }
+
+    pub(crate) fn set(&self, output: impl std::io::Write + 'static) {
+        *self.0.borrow_mut() = Box::new(output);
+    }
+}
+
+impl TestContext {
+    /// Create a new context whose random number generator is seeded with `seed`
+    pub fn new(seed: u64) -> Self {
+        TestContext {
+            seed,
+            rng_state: seed,
+            verbs_started: 0,
+            verb_seeds: Vec::new(),
+            values: HashMap::new(),
+            observer: None,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            failure_note: RefCell::new(None),
+            output: None,
+            source_code: None,
+            #[cfg(feature = "serde")]
+            trace: None,
+        }
+    }
+
+    pub(crate) fn random_seed() -> u64 {
+        std::hash::RandomState::new().hash_one(0u8)
+    }
+
+    /// The seed this run was started with
+    ///
+    /// Passing it to [`TestCase::run_with_seed`](crate::test_case::TestCase::run_with_seed)
+    /// replays the same sequence of random numbers.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The seed of the verb that is currently running
+    ///
+    /// It is derived from the [`seed`](Self::seed) of the run and the number of verbs that
+    /// started before this one. Contrary to [`next_u64`](Self::next_u64), it does not depend on
+    /// how many random numbers earlier verbs drew, so a harness that owns its randomness can seed
+    /// it from this and get the same numbers for the same verb on every run with the same seed.
+    ///
+    /// Outside of a verb, this is the seed of the run.
+    pub fn verb_seed(&self) -> u64 {
+        self.verb_seeds.last().copied().unwrap_or(self.seed)
+    }
+
+    pub(crate) fn enter_verb(&mut self) {
+        self.verbs_started += 1;
+        self.verb_seeds
+            .push(split_mix(self.seed.wrapping_add(self.verbs_started)));
+    }
+
+    pub(crate) fn exit_verb(&mut self) {
+        self.verb_seeds.pop();
+    }
+
+    /// Get the next pseudo-random number of this run
+    ///
+    /// Verbs that want to randomize their behaviour should use this, so that a run can be
+    /// reproduced from its seed. The sequence is not suitable for cryptographic purposes.
+    pub fn next_u64(&mut self) -> u64 {
+        self.rng_state = self.rng_state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        split_mix(self.rng_state)
+    }
+
+    /// Store a value under the given name, replacing any previous value
+    pub fn set<T: Any>(&mut self, name: impl Into<String>, value: T) {
+        self.values.insert(name.into(), Arc::new(value));
+    }
+
+    /// Get the value stored under the given name
+    ///
+    /// Returns `None` if there is no such value, or if it is not a `T`.
+    pub fn get<T: Any>(&self, name: &str) -> Option<&T> {
+        self.values.get(name)?.downcast_ref()
+    }
+
+    /// Get the value stored under the given name, or an error explaining why there is none
+    ///
+    /// This is the same as [`get`](Self::get), but returns a [`ContextValueError`] that can be
+    /// returned from a verb or condition with `?`.
+    pub fn value<T: Any>(&self, name: &str) -> Result<&T, ContextValueError> {
+        let value = self
+            .values
+            .get(name)
+            .ok_or_else(|| ContextValueError::Missing {
+                name: name.to_string(),
+            })?;
+
+        value
+            .downcast_ref()
+            .ok_or_else(|| ContextValueError::WrongType {
+                name: name.to_string(),
+                expected: std::any::type_name::<T>(),
+            })
+    }
+
+    /// The buffer for output of the running verb that is meant for stdout
+    ///
+    /// Verbs that print, for example the output of a process they started, should write it here
+    /// instead of printing it directly. It is passed on to stdout once the verb finished. With
+    /// [`TestDsl::set_capture_output`](crate::TestDsl::set_capture_output), it is attached to the
+    /// error of the verb instead, and discarded if the verb succeeds.
+    pub fn stdout(&mut self) -> &mut Vec<u8> {
+        &mut self.stdout
+    }
+
+    /// The buffer for output of the running verb that is meant for stderr
+    ///
+    /// It behaves like [`stdout`](Self::stdout).
+    pub fn stderr(&mut self) -> &mut Vec<u8> {
+        &mut self.stderr
+    }
+
+    /// Explain why the condition that is being checked does not hold
+    ///
+    /// If the condition then returns [`Ok(false)`](Ok), the note is shown as the help of the
+    /// resulting [`ConditionFailed`](crate::error::TestError::ConditionFailed) error. Otherwise it
+    /// is discarded, so conditions can note the details of every failed check, also while being
+    /// waited for or negated.
+    pub fn note_failure(&self, note: impl Into<String>) {
+        self.failure_note.replace(Some(note.into()));
+    }
+
+    /// Remove the note set with [`note_failure`](Self::note_failure)
+    pub(crate) fn take_failure_note(&self) -> Option<String> {
+        self.failure_note.take()
+    }
+
+    /// The current length of the output buffers, to be passed to
+    /// [`take_output`](Self::take_output)
+    pub(crate) fn output_start(&self) -> (usize, usize) {
+        (self.stdout.len(), self.stderr.len())
+    }
+
+    /// Remove the output written since `start`
+    ///
+    /// If `capture` is set, the output is returned as the help of an error. Otherwise, all
+    /// buffered output is passed on to stdout and stderr.
+    pub(crate) fn take_output(&mut self, start: (usize, usize), capture: bool) -> Option<String> {
+        use std::io::Write;
+
+        if !capture {
+            // Writing to stdout and stderr can only fail if they are closed
+            let _ = std::io::stdout().write_all(&std::mem::take(&mut self.stdout));
+            let _ = std::io::stderr().write_all(&std::mem::take(&mut self.stderr));
+
+            return None;
+        }
+
+        let stdout = self.stdout.split_off(start.0.min(self.stdout.len()));
+        let stderr = self.stderr.split_off(start.1.min(self.stderr.len()));
+
+        let mut help = String::new();
+        for (name, output) in [("stdout", stdout), ("stderr", stderr)] {
+            if output.is_empty() {
+                continue;
+            }
+
+            if !help.is_empty() {
+                help.push('\n');
+            }
+
+            help.push_str(&format!(
+                "Captured {name}:\n{}",
+                String::from_utf8_lossy(&output).trim_end()
+            ));
+        }
+
+        (!help.is_empty()).then_some(help)
+    }
+
+    pub(crate) fn with_observer(mut self, observer: ObserverHandle) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    pub(crate) fn with_output(mut self, output: OutputHandle) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    pub(crate) fn set_source_code(&mut self, source_code: TestCaseInput) {
+        self.source_code = Some(source_code);
+    }
+
+    /// Write a line to the output of the `print` verb, which is stdout unless another one was
+    /// given with [`with_output`](Self::with_output)
+    pub(crate) fn print_line(&self, line: &str) -> std::io::Result<()> {
+        use std::io::Write;
+
+        match &self.output {
+            Some(OutputHandle(output)) => {
+                let mut output = output.borrow_mut();
+                writeln!(output, "{line}")?;
+                output.flush()
+            }
+            None => {
+                let mut output = std::io::stdout().lock();
+                writeln!(output, "{line}")?;
+                output.flush()
+            }
+        }
+    }
+
+    /// The location of `span` in the testcase that is running, as `file:line:column`, or
+    /// `line:column` if it was not read from a file
+    pub(crate) fn location(&self, span: miette::SourceSpan) -> Option<String> {
+        let source_code = self.source_code.as_ref()?;
+        let (line, column) = source_code.line_column(span)?;
+
+        Some(match source_code.filepath() {
+            Some(filepath) => format!("{filepath}:{line}:{column}"),
+            None => format!("{line}:{column}"),
+        })
+    }
+
+    pub(crate) fn observe_start(&self, node: &kdl::KdlNode) {
+        if let Some(observer) = &self.observer {
+            observer.start(node);
+        }
+    }
+
+    pub(crate) fn observe_end(
+        &self,
+        node: &kdl::KdlNode,
+        result: Result<(), &crate::error::TestError>,
+    ) {
+        if let Some(observer) = &self.observer {
+            observer.end(node, result);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn with_trace(mut self, trace: crate::trace::TraceCollector) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    /// Returns the depth of the entered node, which has to be passed to
+    /// [`trace_exit`](Self::trace_exit)
+    #[cfg_attr(not(feature = "serde"), expect(unused_variables))]
+    pub(crate) fn trace_enter(&self, is_verb: bool, node: &kdl::KdlNode) -> usize {
+        #[cfg(feature = "serde")]
+        if let Some(trace) = &self.trace {
+            let kind = if is_verb {
+                crate::trace::TraceKind::Verb
+            } else {
+                crate::trace::TraceKind::Condition
+            };
+
+            return trace.enter(kind, node);
+        }
+
+        0
+    }
+
+    #[cfg_attr(not(feature = "serde"), expect(unused_variables))]
+    pub(crate) fn trace_exit(&self, depth: usize, result: Result<bool, &crate::error::TestError>) {
+        #[cfg(feature = "serde")]
+        if let Some(trace) = &self.trace {
+            trace.exit(depth, result);
+        }
+    }
+}
+
+/// The output function of SplitMix64
+fn split_mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
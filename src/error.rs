@@ -15,6 +15,15 @@ pub struct TestParseError {
     pub(crate) source_code: Option<TestCaseInput>,
 }
 
+impl TestParseError {
+    /// The individual errors that occurred
+    ///
+    /// Their locations can be resolved with [`TestErrorCase::location`].
+    pub fn errors(&self) -> &[TestErrorCase] {
+        &self.errors
+    }
+}
+
 impl From<kdl::KdlError> for TestParseError {
     fn from(source: kdl::KdlError) -> Self {
         TestParseError {
@@ -33,6 +42,67 @@ impl From<TestErrorCase> for TestParseError {
     }
 }
 
+#[derive(Error, Diagnostic, Debug)]
+#[error("An error occurred while parsing the testcases of a directory")]
+/// Errors occurred while parsing the files of a directory
+///
+/// Each file that could not be parsed has its own [`TestParseError`], pointing into that file.
+pub struct TestDirectoryError {
+    #[related]
+    pub(crate) errors: Vec<TestParseError>,
+}
+
+impl TestDirectoryError {
+    /// The errors of the individual files
+    pub fn errors(&self) -> &[TestParseError] {
+        &self.errors
+    }
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[error("A verb, condition or producer named `{name}` is already registered")]
+/// A verb, condition or producer was added under a name that is already taken
+pub struct DuplicateNameError {
+    /// The name that was already taken
+    pub name: String,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[error("Warnings were emitted while parsing testcases")]
+#[diagnostic(severity(Warning))]
+/// Warnings were emitted while parsing testcases
+pub struct TestParseWarning {
+    #[related]
+    pub(crate) warnings: Vec<TestWarningCase>,
+
+    #[source_code]
+    pub(crate) source_code: TestCaseInput,
+}
+
+impl TestParseWarning {
+    /// The individual warnings
+    pub fn warnings(&self) -> &[TestWarningCase] {
+        &self.warnings
+    }
+}
+
+#[derive(Error, Diagnostic, Debug, Clone)]
+/// Warnings that can happen while parsing tests
+pub enum TestWarningCase {
+    /// A verb that was marked as deprecated was used
+    #[error("This verb is deprecated")]
+    #[diagnostic(severity(Warning))]
+    DeprecatedVerb {
+        /// The location of the verb node
+        #[label("deprecated verb used here")]
+        verb: miette::SourceSpan,
+
+        /// How to migrate away from the verb
+        #[help]
+        message: String,
+    },
+}
+
 #[derive(Error, Diagnostic, Debug)]
 /// Errors that can happen related to tests
 pub enum TestErrorCase {
@@ -44,15 +114,75 @@ pub enum TestErrorCase {
         source: kdl::KdlError,
     },
 
+    /// A file could not be read
+    #[error("Could not read the file `{path}`")]
+    Io {
+        /// The path of the file
+        path: String,
+
+        /// The underlying IO error
+        #[source]
+        source: std::io::Error,
+    },
+
     /// An outer node was not a `testcase` node
     #[error("Not a valid test case")]
-    #[diagnostic(help("The outer items must all be `testcase`s"))]
+    #[diagnostic(help("The outer items must all be `{keyword}`s"))]
     NotTestcase {
         /// The location of the offending node
-        #[label("Expected a `testcase`")]
+        #[label("Expected a `{keyword}`")]
+        span: miette::SourceSpan,
+
+        /// The name of testcase nodes, see
+        /// [`TestDsl::set_testcase_keyword`](crate::TestDsl::set_testcase_keyword)
+        keyword: String,
+    },
+
+    /// A top-level node was not known, see [`TestDsl::set_strict`](crate::TestDsl::set_strict)
+    #[error("Unknown top-level node")]
+    UnknownTopLevelNode {
+        /// The location of the offending node
+        #[label("This node is not known")]
+        span: miette::SourceSpan,
+
+        /// Help listing the nodes that are allowed
+        #[help]
+        allowed: String,
+    },
+
+    /// An included file includes itself, directly or through other files
+    #[error("The file is already being included")]
+    IncludeCycle {
+        /// The path of the `include` node closing the cycle
+        #[label("This file includes itself")]
+        path: miette::SourceSpan,
+
+        /// Help listing the files forming the cycle
+        #[help]
+        cycle: String,
+    },
+
+    /// An `include` node was used in a testcase that was not read from a file
+    #[error("Only testcases read from a file can include other files")]
+    #[diagnostic(help("Included paths are resolved against the directory of the including file"))]
+    IncludeWithoutFile {
+        /// The location of the `include` node
+        #[label("in this node")]
         span: miette::SourceSpan,
     },
 
+    /// An error occurred in a file included with `include`
+    #[error("Could not parse the included file")]
+    InIncludedFile {
+        /// The input of the included file
+        #[source_code]
+        source_code: TestCaseInput,
+
+        /// The [`TestErrorCase`] in the included file
+        #[diagnostic_source]
+        error: miette::Error,
+    },
+
     /// An argument was missing from a node
     #[error("An argument was missing")]
     MissingArgument {
@@ -65,6 +195,18 @@ pub enum TestErrorCase {
         missing: String,
     },
 
+    /// A node had more arguments than it takes
+    #[error("An unexpected argument was given")]
+    UnexpectedArgument {
+        /// The first superfluous argument
+        #[label("This argument is not expected")]
+        argument: miette::SourceSpan,
+
+        /// Help text to explain how many arguments were expected
+        #[help]
+        expected: String,
+    },
+
     /// A node had a wrong type in its parameter list
     #[error("An argument was of the wrong type")]
     WrongArgumentType {
@@ -73,20 +215,58 @@ pub enum TestErrorCase {
         parent: miette::SourceSpan,
 
         /// The offending argument
-        #[label("this one")]
+        #[label("expected {expected_type}, found {found}")]
         argument: miette::SourceSpan,
 
+        /// The name of the type that was expected
+        expected_type: &'static str,
+
+        /// The kind of KDL value that was given instead, like `string` or `integer`
+        found: &'static str,
+
         /// Help text to explain what was expected, if possible
         #[help]
         expected: String,
     },
 
+    /// A null value was given for an argument that cannot be null
+    #[error("An argument was null")]
+    NullArgument {
+        /// The parent node
+        #[label("This node has a null argument")]
+        parent: miette::SourceSpan,
+
+        /// The offending argument
+        #[label("this one")]
+        argument: miette::SourceSpan,
+
+        /// Help text to explain what was expected
+        #[help]
+        expected: String,
+    },
+
+    /// The variables of a string argument could not be interpolated
+    #[error("Could not interpolate the variables of this argument")]
+    InvalidInterpolation {
+        /// The offending argument
+        #[label("in this argument")]
+        argument: miette::SourceSpan,
+
+        /// Why the interpolation failed
+        #[help]
+        reason: String,
+    },
+
     /// The given condition could not be found
     #[error("Could not find condition with this name")]
     UnknownCondition {
         /// The location of the condition node
         #[label]
         condition: miette::SourceSpan,
+
+        /// The name of a known condition that is spelled similarly, if any
+        #[help]
+        suggestion: Option<String>,
     },
 
     /// The given verb could not be found
@@ -95,6 +275,46 @@ pub enum TestErrorCase {
         /// The location of the verb node
         #[label]
         verb: miette::SourceSpan,
+
+        /// The name of a known verb that is spelled similarly, if any
+        #[help]
+        suggestion: Option<String>,
+    },
+
+    /// The given producer could not be found
+    #[error("Could not find producer with this name")]
+    UnknownProducer {
+        /// The location of the producer name
+        #[label]
+        producer: miette::SourceSpan,
+    },
+
+    /// A node expected a condition as one of its children
+    #[error("Expected a condition")]
+    #[diagnostic(help("This child of the node has to be a known condition"))]
+    ExpectedCondition {
+        /// The location of the node or its offending first child
+        #[label("Expected a condition here")]
+        span: miette::SourceSpan,
+    },
+
+    /// Several errors occurred in the same node
+    #[error("Multiple errors occurred")]
+    Multiple {
+        /// The individual errors
+        #[related]
+        errors: Vec<TestErrorCase>,
+    },
+
+    /// The condition cannot be used the way its position requires
+    #[error("This condition cannot be {usage}")]
+    UnsupportedCondition {
+        /// The location of the condition node
+        #[label("used here")]
+        condition: miette::SourceSpan,
+
+        /// How the condition would be used, either `checked now` or `waited for`
+        usage: &'static str,
     },
 
     /// The condition is not valid in this position
@@ -104,6 +324,268 @@ pub enum TestErrorCase {
         #[diagnostic_source]
         error: miette::Error,
     },
+
+    /// A thread of [`TestDsl::run_parallel`](crate::TestDsl::run_parallel) parsed a different
+    /// number of testcases than the first parse
+    #[error("Expected {expected} testcases, but a thread parsed {found}")]
+    #[diagnostic(help(
+        "`make_dsl` has to return a `TestDsl` parsing the input the same way every time"
+    ))]
+    TestcaseCountChanged {
+        /// The number of testcases of the first parse
+        expected: usize,
+
+        /// The number of testcases parsed by the thread
+        found: usize,
+    },
+}
+
+impl TestErrorCase {
+    /// Combine the given errors into a single one
+    ///
+    /// `errors` must not be empty.
+    pub(crate) fn from_many(errors: Vec<TestErrorCase>) -> TestErrorCase {
+        let mut errors = errors
+            .into_iter()
+            .flat_map(TestErrorCase::flatten)
+            .collect::<Vec<_>>();
+
+        if errors.len() == 1 {
+            errors.remove(0)
+        } else {
+            TestErrorCase::Multiple { errors }
+        }
+    }
+
+    /// The line and column this error points at, both starting at 1
+    ///
+    /// `input` has to be the input that was parsed. For [`TestErrorCase::Multiple`] this is the
+    /// location of the first error. Errors that do not point into the input, like
+    /// [`TestErrorCase::Io`], return `None`.
+    pub fn location(&self, input: &TestCaseInput) -> Option<(usize, usize)> {
+        match self {
+            TestErrorCase::InIncludedFile { source_code, error } => error
+                .downcast_ref::<TestErrorCase>()
+                .and_then(|error| error.location(source_code)),
+            error => input.line_column(error.span()?),
+        }
+    }
+
+    /// The location in the input this error points at, if any
+    pub(crate) fn span(&self) -> Option<miette::SourceSpan> {
+        match self {
+            TestErrorCase::Kdl { source } => first_label(source),
+            TestErrorCase::Io { .. }
+            | TestErrorCase::InIncludedFile { .. }
+            | TestErrorCase::TestcaseCountChanged { .. } => None,
+            TestErrorCase::NotTestcase { span, .. }
+            | TestErrorCase::UnknownTopLevelNode { span, .. }
+            | TestErrorCase::ExpectedCondition { span } => Some(*span),
+            TestErrorCase::MissingArgument { parent, .. } => Some(*parent),
+            TestErrorCase::IncludeCycle { path, .. } => Some(*path),
+            TestErrorCase::IncludeWithoutFile { span } => Some(*span),
+            TestErrorCase::UnexpectedArgument { argument, .. }
+            | TestErrorCase::WrongArgumentType { argument, .. }
+            | TestErrorCase::NullArgument { argument, .. }
+            | TestErrorCase::InvalidInterpolation { argument, .. } => Some(*argument),
+            TestErrorCase::UnknownCondition { condition, .. }
+            | TestErrorCase::UnsupportedCondition { condition, .. } => Some(*condition),
+            TestErrorCase::UnknownVerb { verb, .. } => Some(*verb),
+            TestErrorCase::UnknownProducer { producer } => Some(*producer),
+            TestErrorCase::Multiple { errors } => errors.iter().find_map(TestErrorCase::span),
+            TestErrorCase::InvalidCondition { error } => first_label(error.as_ref()),
+        }
+    }
+
+    /// Split this error into the individual errors it consists of
+    pub(crate) fn flatten(self) -> Vec<TestErrorCase> {
+        match self {
+            TestErrorCase::Multiple { errors } => errors
+                .into_iter()
+                .flat_map(TestErrorCase::flatten)
+                .collect(),
+            error => vec![error],
+        }
+    }
+}
+
+/// The span of the first label of `diagnostic` or of one of its related diagnostics
+fn first_label(diagnostic: &dyn Diagnostic) -> Option<miette::SourceSpan> {
+    if let Some(label) = diagnostic.labels().and_then(|mut labels| labels.next()) {
+        return Some(*label.inner());
+    }
+
+    diagnostic
+        .related()
+        .and_then(|mut related| related.find_map(first_label))
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("The value did not change")]
+#[diagnostic(help("It was {before} before and {after} after running the verbs"))]
+/// The value observed by an [`AssertChanges`](crate::verb::AssertChanges) verb did not change
+pub struct ValueUnchanged {
+    /// The value before running the verbs
+    pub before: String,
+
+    /// The value after running the verbs
+    pub after: String,
+}
+
+#[derive(Debug, Error, Diagnostic)]
+/// A value could not be read from the [`TestContext`](crate::context::TestContext)
+pub enum ContextValueError {
+    /// No value was stored under the name
+    #[error("No value named `{name}` was stored")]
+    #[diagnostic(help("Values have to be stored by a verb that runs before they are read"))]
+    Missing {
+        /// The name of the value
+        name: String,
+    },
+
+    /// The stored value has a different type
+    #[error("The value named `{name}` is not a `{expected}`")]
+    WrongType {
+        /// The name of the value
+        name: String,
+
+        /// The type that was asked for
+        expected: &'static str,
+    },
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("This verb can only run asynchronously")]
+#[diagnostic(help(
+    "Run the testcase with `TestCase::run_async`, and nest the verb only in `group` or `repeat`"
+))]
+/// An [`AsyncVerb`](crate::verb::AsyncVerb) was run synchronously
+#[cfg(feature = "async")]
+pub struct AsyncOnly;
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("The rest of the testcase was skipped")]
+/// Returned by a verb to stop the testcase without failing it
+///
+/// The remaining verbs of the testcase are not run, even if the verb is nested in other verbs.
+/// The built-in `skip_rest` verb returns it.
+pub struct SkipRest;
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("The testcase was skipped")]
+/// Returned by a verb to stop the testcase and mark it as skipped
+///
+/// Like [`SkipRest`], the remaining verbs of the testcase are not run. The testcase is neither
+/// successful nor failed, see [`TestOutcome::Skipped`](crate::test_case::TestOutcome::Skipped).
+/// The built-in `skip_if` verb returns it.
+pub struct Skipped {
+    /// Why the testcase was skipped, if known
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("Expected a verb to fail, but all of them succeeded")]
+#[diagnostic(help("`assert_fails` passes only if one of its children returns an error or panics"))]
+/// None of the children of an `assert_fails` verb failed
+pub struct ExpectedFailure;
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("{failed} of the {total} conditions failed", failed = .errors.len())]
+/// Some of the conditions of an `assert_all` verb failed
+pub struct ConditionsFailed {
+    total: usize,
+
+    #[related]
+    errors: Vec<TestError>,
+}
+
+impl ConditionsFailed {
+    pub(crate) fn new(total: usize, errors: Vec<TestError>) -> Self {
+        ConditionsFailed { total, errors }
+    }
+
+    /// The errors of the conditions that failed, in order
+    pub fn errors(&self) -> &[TestError] {
+        &self.errors
+    }
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("In group `{label}`")]
+/// A verb inside of a labeled `group` failed
+pub struct GroupError {
+    label: String,
+
+    #[diagnostic_source]
+    error: TestError,
+}
+
+impl GroupError {
+    pub(crate) fn new(label: String, error: TestError) -> Self {
+        GroupError { label, error }
+    }
+
+    /// The label of the group
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// The error of the verb that failed
+    pub fn error(&self) -> &TestError {
+        &self.error
+    }
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("{message}")]
+/// A verb or condition panicked
+///
+/// This is the error of a [`TestError::Panic`]. Its report shows where the panic happened, the
+/// backtrace is available through [`backtrace`](Self::backtrace).
+pub struct PanicError {
+    message: String,
+    location: Option<String>,
+    backtrace: Option<String>,
+
+    #[help]
+    details: Option<String>,
+}
+
+impl PanicError {
+    pub(crate) fn new(
+        message: String,
+        location: Option<String>,
+        backtrace: Option<String>,
+    ) -> Self {
+        let details = location
+            .as_ref()
+            .map(|location| format!("panicked at {location}"));
+
+        PanicError {
+            message,
+            location,
+            backtrace,
+            details,
+        }
+    }
+
+    /// The message the code panicked with
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The location of the panic, as `file:line:column`
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    /// The backtrace of the panic
+    ///
+    /// Backtraces are only captured if they are enabled through the `RUST_BACKTRACE` or
+    /// `RUST_LIB_BACKTRACE` environment variables.
+    pub fn backtrace(&self) -> Option<&str> {
+        self.backtrace.as_deref()
+    }
 }
 
 #[derive(Debug, Error, Diagnostic)]
@@ -119,18 +601,26 @@ pub enum TestError {
         #[label("in this node")]
         /// Which node caused the panic
         span: miette::SourceSpan,
+
+        #[help]
+        /// The output of the verb, if it was captured
+        output: Option<String>,
     },
 
     /// An panic occurred in a verb/condition
     #[error("A panic occurred")]
     Panic {
         #[diagnostic_source]
-        /// The message of the panic if it had one
+        /// The [`PanicError`] with the message and location of the panic
         error: miette::Error,
 
         #[label("in this node")]
         /// Which node caused the panic
         span: miette::SourceSpan,
+
+        #[help]
+        /// The output of the verb, if it was captured
+        output: Option<String>,
     },
 
     /// The evaluated condition failed
@@ -139,5 +629,101 @@ pub enum TestError {
         #[label("in this node")]
         /// Which node caused the panic
         span: miette::SourceSpan,
+
+        #[help]
+        /// Why the condition did not hold, if it noted it with
+        /// [`TestContext::note_failure`](crate::context::TestContext::note_failure)
+        note: Option<String>,
     },
+
+    /// A condition did not hold before its timeout
+    #[error("The condition did not hold within {timeout:?}")]
+    Timeout {
+        /// How long was waited for the condition
+        timeout: std::time::Duration,
+
+        #[label("this condition")]
+        /// Which condition was waited for
+        span: miette::SourceSpan,
+    },
+
+    /// A verb returned [`SkipRest`]
+    ///
+    /// This is passed on by the verbs it is nested in, and the testcase counts as successful.
+    #[error("The rest of the testcase was skipped")]
+    SkipRest {
+        #[label("skipped here")]
+        /// Which verb skipped the rest of the testcase
+        span: miette::SourceSpan,
+    },
+
+    /// A verb returned [`Skipped`]
+    ///
+    /// This is passed on by the verbs it is nested in, and the testcase counts as skipped.
+    #[error("The testcase was skipped")]
+    Skipped {
+        #[label("skipped here")]
+        /// Which verb skipped the testcase
+        span: miette::SourceSpan,
+
+        #[help]
+        /// Why the testcase was skipped, if known
+        reason: Option<String>,
+    },
+
+    /// A loop did not finish within its maximum number of iterations
+    #[error("The loop did not finish after {max} iterations")]
+    #[diagnostic(help("Increase its `max` property if more iterations are expected"))]
+    IterationLimitReached {
+        /// The maximum number of iterations
+        max: usize,
+
+        #[label("in this node")]
+        /// Which node gave up
+        span: miette::SourceSpan,
+    },
+}
+
+impl TestError {
+    /// The location of the node that caused this error
+    pub(crate) fn span(&self) -> miette::SourceSpan {
+        match self {
+            TestError::Error { span, .. }
+            | TestError::Panic { span, .. }
+            | TestError::ConditionFailed { span, .. }
+            | TestError::SkipRest { span }
+            | TestError::Skipped { span, .. }
+            | TestError::Timeout { span, .. }
+            | TestError::IterationLimitReached { span, .. } => *span,
+        }
+    }
+
+    /// The line and column of the node that caused this error, both starting at 1
+    ///
+    /// `input` has to be the input of the testcase that returned this error.
+    pub fn location(&self, input: &TestCaseInput) -> Option<(usize, usize)> {
+        input.line_column(self.span())
+    }
+
+    /// Whether this stops the testcase without being a failure of the verb
+    ///
+    /// This is the case for [`SkipRest`](TestError::SkipRest) and
+    /// [`Skipped`](TestError::Skipped). Verbs pass these on without wrapping them.
+    pub fn is_control_flow(&self) -> bool {
+        matches!(self, TestError::SkipRest { .. } | TestError::Skipped { .. })
+    }
+
+    /// The error of the nested verb or condition that caused this error, if any
+    ///
+    /// Verbs like `repeat` or `group` fail with the error of their failing child, which is
+    /// returned here.
+    pub fn nested(&self) -> Option<&TestError> {
+        let TestError::Error { error, .. } = self else {
+            return None;
+        };
+
+        error
+            .downcast_ref::<TestError>()
+            .or_else(|| error.downcast_ref::<GroupError>().map(GroupError::error))
+    }
 }
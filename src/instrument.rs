@@ -0,0 +1,42 @@
+//! Emitting `tracing` spans and events for running verbs and conditions
+//!
+//! This module is only available with the `tracing` feature.
+
+use crate::error::TestError;
+
+/// Enter a span for the given node, which is exited once the returned value is dropped
+pub(crate) fn enter(is_verb: bool, node: &kdl::KdlNode) -> tracing::span::EnteredSpan {
+    span(is_verb, node).entered()
+}
+
+/// Create the span for the given node
+pub(crate) fn span(is_verb: bool, node: &kdl::KdlNode) -> tracing::Span {
+    let name = node.name().value();
+    let span = node.span();
+
+    if is_verb {
+        tracing::info_span!("verb", name, offset = span.offset(), length = span.len())
+    } else {
+        tracing::info_span!(
+            "condition",
+            name,
+            offset = span.offset(),
+            length = span.len()
+        )
+    }
+}
+
+/// Log the error of the node whose span is currently entered
+pub(crate) fn record_error(result: Result<(), &TestError>) {
+    if let Err(error) = result {
+        let mut message = error.to_string();
+        let mut cause = miette::Diagnostic::diagnostic_source(error);
+        while let Some(inner) = cause {
+            message.push_str(": ");
+            message.push_str(&inner.to_string());
+            cause = inner.diagnostic_source();
+        }
+
+        tracing::error!(error = message);
+    }
+}
@@ -0,0 +1,146 @@
+//! Interpolation of variables in string arguments
+
+use std::collections::HashMap;
+
+use crate::error::TestErrorCase;
+
+/// Replace the variables in all string entries of `node` and its children
+///
+/// Returns the errors of all entries that could not be interpolated.
+pub(crate) fn interpolate_node(
+    node: &mut kdl::KdlNode,
+    variables: &HashMap<String, String>,
+    env: bool,
+) -> Vec<TestErrorCase> {
+    let mut errors = vec![];
+
+    for entry in node.entries_mut() {
+        let Some(value) = entry.value().as_string() else {
+            continue;
+        };
+
+        if !value.contains('$') {
+            continue;
+        }
+
+        match interpolate(value, variables, env) {
+            Ok(value) => entry.set_value(value),
+            Err(reason) => errors.push(TestErrorCase::InvalidInterpolation {
+                argument: entry.span(),
+                reason,
+            }),
+        }
+    }
+
+    if let Some(children) = node.children_mut() {
+        for child in children.nodes_mut() {
+            errors.extend(interpolate_node(child, variables, env));
+        }
+    }
+
+    errors
+}
+
+/// Replace `${name}` with the value of the variable `name`, and `$$` with `$`
+///
+/// If `env` is set, `$ENV{NAME}` is replaced with the value of the environment variable `NAME`.
+/// A `$` that is not followed by any of these is kept as is.
+fn interpolate(
+    input: &str,
+    variables: &HashMap<String, String>,
+    env: bool,
+) -> Result<String, String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            output.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('{') {
+            let Some(end) = after.find('}') else {
+                return Err(String::from("The `${` is missing its closing `}`."));
+            };
+
+            let name = &after[..end];
+            let value = variables
+                .get(name)
+                .ok_or_else(|| format!("The variable `{name}` is not defined."))?;
+
+            output.push_str(value);
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix("ENV{").filter(|_| env) {
+            let Some(end) = after.find('}') else {
+                return Err(String::from("The `$ENV{` is missing its closing `}`."));
+            };
+
+            let name = &after[..end];
+            let value = std::env::var(name)
+                .map_err(|_| format!("The environment variable `{name}` is not set."))?;
+
+            output.push_str(&value);
+            rest = &after[end + 1..];
+        } else {
+            output.push('$');
+        }
+    }
+
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::interpolate;
+
+    #[test]
+    fn check_interpolate() {
+        let variables = HashMap::from([
+            (String::from("base"), String::from("http://x")),
+            (String::from("port"), String::from("8080")),
+        ]);
+
+        assert_eq!(
+            interpolate("${base}:${port}/health", &variables, false).unwrap(),
+            "http://x:8080/health"
+        );
+        assert_eq!(
+            interpolate("costs $$5", &variables, false).unwrap(),
+            "costs $5"
+        );
+        assert_eq!(
+            interpolate("$$${port}", &variables, false).unwrap(),
+            "$8080"
+        );
+        assert_eq!(
+            interpolate("a $ b $", &variables, false).unwrap(),
+            "a $ b $"
+        );
+        assert!(interpolate("${missing}", &variables, false).is_err());
+        assert!(interpolate("${base", &variables, false).is_err());
+    }
+
+    #[test]
+    fn check_interpolate_env() {
+        let variables = HashMap::new();
+        let path = std::env::var("PATH").unwrap();
+
+        assert_eq!(interpolate("$ENV{PATH}", &variables, true).unwrap(), path);
+        assert_eq!(
+            interpolate("$ENV{PATH}", &variables, false).unwrap(),
+            "$ENV{PATH}"
+        );
+        assert_eq!(
+            interpolate("$$ENV{PATH}", &variables, true).unwrap(),
+            "$ENV{PATH}"
+        );
+        assert!(interpolate("$ENV{TEST_DSL_SURELY_UNSET}", &variables, true).is_err());
+        assert!(interpolate("$ENV{PATH", &variables, true).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! Conditions for validating JSON payloads
+//!
+//! This module is only available with the `serde_json` feature.
+
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::argument::VerbArgument;
+use crate::condition::Condition;
+use crate::context::TestContext;
+
+/// A string argument that is expected to contain JSON
+#[derive(Debug, Clone)]
+pub struct JsonString {
+    value: String,
+    span: miette::SourceSpan,
+}
+
+impl JsonString {
+    /// Get the raw string
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+
+    /// Parse the string as a JSON value
+    pub fn parse(&self) -> Result<serde_json::Value, InvalidJson> {
+        serde_json::from_str(&self.value).map_err(|error| InvalidJson {
+            line: error.line(),
+            column: error.column(),
+            message: error.to_string(),
+            span: self.span,
+        })
+    }
+}
+
+impl VerbArgument for JsonString {
+    fn get_error_type_name() -> &'static str {
+        "JSON string"
+    }
+
+    fn from_value(value: &kdl::KdlEntry) -> Option<Self> {
+        value.value().as_string().map(|json| JsonString {
+            value: json.to_owned(),
+            span: value.span(),
+        })
+    }
+}
+
+/// A JSON string could not be parsed
+#[derive(Debug, Error, Diagnostic)]
+#[error("The argument is not valid JSON")]
+pub struct InvalidJson {
+    /// The line inside the JSON string
+    pub line: usize,
+    /// The column inside the JSON string
+    pub column: usize,
+    /// The message reported by the JSON parser
+    pub message: String,
+
+    /// The location of the JSON string
+    #[label("{message}")]
+    pub span: miette::SourceSpan,
+}
+
+/// A condition that checks whether its argument is valid JSON
+///
+/// It does not depend on the harness, and can be used in both direct and waiting contexts.
+///
+/// ```
+/// # use test_dsl::TestDsl;
+/// # use test_dsl::json::ValidJson;
+/// let mut dsl = TestDsl::<()>::new();
+/// dsl.add_condition("valid_json", ValidJson);
+///
+/// let testcases = dsl
+///     .parse_testcase(r#"testcase { assert { valid_json "{\"status\": 200}" } }"#)
+///     .unwrap();
+///
+/// testcases[0].run(&mut ()).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ValidJson;
+
+impl<H: 'static> Condition<H> for ValidJson {
+    type Arguments = (JsonString,);
+
+    fn check_now(
+        &self,
+        _harness: &H,
+        _context: &TestContext,
+        (json,): &Self::Arguments,
+    ) -> miette::Result<bool> {
+        json.parse()?;
+
+        Ok(true)
+    }
+
+    fn wait_until(
+        &self,
+        harness: &H,
+        context: &TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<bool> {
+        self.check_now(harness, context, arguments)
+    }
+}
@@ -1,13 +1,21 @@
 #![doc = include_str!("../README.md")]
 
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::marker::PhantomData;
+use std::ops::ControlFlow;
+use std::path::Path;
 use std::sync::Arc;
+use std::sync::PoisonError;
 
 use argument::BoxedArguments;
-use argument::ConditionChildren;
+use argument::NowConditionChildren;
+use argument::SingleConditionChild;
 use argument::VerbChildren;
+use argument::WaitConditionChildren;
 use condition::ErasedCondition;
+use context::TestContext;
 use error::TestError;
 use error::TestErrorCase;
 use verb::ErasedVerb;
@@ -18,12 +26,32 @@ mod macros;
 
 pub mod argument;
 pub mod condition;
+pub mod context;
 pub mod error;
+#[cfg(feature = "tracing")]
+mod instrument;
+mod interpolation;
+#[cfg(feature = "serde_json")]
+pub mod json;
+pub mod observer;
+mod panic;
+pub mod producer;
+pub mod report;
 pub mod test_case;
+#[cfg(feature = "async")]
+mod timer;
+#[cfg(feature = "serde")]
+pub mod trace;
 pub mod verb;
 pub use kdl;
 pub use miette;
 
+/// The version of the KDL syntax testcases are written in
+///
+/// With the `kdl-v1-fallback` feature, documents that are not valid KDL v2 are parsed as KDL v1
+/// instead. See the crate documentation for how the versions differ in their arguments.
+pub const KDL_VERSION: u8 = 2;
+
 /// The main type of the crate
 ///
 /// It contains all available verbs and conditions, and is used to derive
@@ -31,6 +59,23 @@ pub use miette;
 pub struct TestDsl<H> {
     verbs: HashMap<String, ErasedVerb<H>>,
     conditions: HashMap<String, ErasedCondition<H>>,
+    producers: HashMap<String, producer::ErasedProducer<H>>,
+    deprecated_verbs: HashMap<String, String>,
+    variables: HashMap<String, String>,
+    env_interpolation: bool,
+    allow_include: bool,
+    catch_panics: bool,
+    capture_output: bool,
+    top_level_nodes: Vec<String>,
+    testcase_keyword: String,
+    strict: bool,
+    warning_handler: Box<dyn Fn(error::TestParseWarning)>,
+    before_each: Option<Box<dyn Fn(&mut H)>>,
+    after_each: Option<Box<dyn Fn(&mut H)>>,
+    observer: Option<std::rc::Rc<dyn observer::RunObserver>>,
+    output: context::OutputHandle,
+    /// The warnings of the testcase that is being parsed, `None` outside of parsing a testcase
+    parsing_warnings: RefCell<Option<Vec<error::TestWarningCase>>>,
 }
 
 impl<H> std::fmt::Debug for TestDsl<H> {
@@ -46,16 +91,59 @@ impl<H: 'static> Default for TestDsl<H> {
 }
 
 impl<H: 'static> TestDsl<H> {
-    /// Create an empty [`TestDsl`]
-    pub fn new() -> Self {
-        let mut dsl = TestDsl {
+    /// Create an empty [`TestDsl`], without any verbs or conditions
+    pub fn empty() -> Self {
+        TestDsl {
             verbs: HashMap::default(),
             conditions: HashMap::default(),
-        };
+            producers: HashMap::default(),
+            deprecated_verbs: HashMap::default(),
+            variables: HashMap::default(),
+            env_interpolation: false,
+            allow_include: false,
+            catch_panics: true,
+            capture_output: false,
+            top_level_nodes: vec![],
+            testcase_keyword: String::from("testcase"),
+            strict: false,
+            warning_handler: Box::new(|_| ()),
+            before_each: None,
+            after_each: None,
+            observer: None,
+            output: context::OutputHandle::new(std::io::stdout()),
+            parsing_warnings: RefCell::default(),
+        }
+    }
+
+    /// Create a new [`TestDsl`] with the built-in verbs and conditions
+    ///
+    /// The built-in verbs are `repeat`, `repeat_until`, `group`, `assert`, `assert_all`,
+    /// `assert_fails`, `if`, `wait_for`, `sleep`, `fail`, `measure`, `print`, `skip_rest` and
+    /// `skip_if`. The built-in conditions are `not`, `all`, `any`, `eventually` and
+    /// `consistently`. Use [`empty`](Self::empty) to start without them.
+    pub fn new() -> Self {
+        let mut dsl = Self::empty();
 
-        dsl.add_verb("repeat", Repeat);
-        dsl.add_verb("group", Group);
+        dsl.add_nesting_verb("repeat", Repeat);
+        dsl.add_verb("repeat_until", RepeatUntil);
+        dsl.add_nesting_verb("group", Group);
         dsl.add_verb("assert", AssertConditions);
+        dsl.add_verb("assert_all", AssertAllConditions);
+        dsl.add_verb("assert_fails", AssertFails);
+        dsl.add_verb("if", If);
+        dsl.add_nesting_verb("wait_for", WaitFor);
+        dsl.add_verb("sleep", Sleep);
+        dsl.add_verb("fail", Fail);
+        dsl.add_verb("measure", producer::Measure);
+        dsl.add_verb("print", Print);
+        dsl.add_verb("skip_rest", SkipRest);
+        dsl.add_verb("skip_if", SkipIf);
+
+        dsl.add_mut_condition("not", NotCondition);
+        dsl.add_mut_condition("all", AllConditions);
+        dsl.add_mut_condition("any", AnyCondition);
+        dsl.add_mut_condition("eventually", Eventually);
+        dsl.add_mut_condition("consistently", Consistently);
 
         dsl
     }
@@ -65,11 +153,217 @@ impl<H: 'static> TestDsl<H> {
     /// The name is used as-is in your testcases, the arguments are up to each individual [`Verb`] implementation.
     ///
     /// See [`FunctionVerb`](verb::FunctionVerb) for an easy to use way of defining verbs.
+    ///
+    /// # Panics
+    ///
+    /// If a verb with the same name has already been added. Use
+    /// [`try_add_verb`](Self::try_add_verb) to handle this case.
     pub fn add_verb(&mut self, name: impl AsRef<str>, verb: impl Verb<H>) {
-        let existing = self
-            .verbs
-            .insert(name.as_ref().to_string(), ErasedVerb::erase(verb));
-        assert!(existing.is_none());
+        if let Err(error) = self.try_add_verb(name, verb) {
+            panic!("{error}");
+        }
+    }
+
+    /// Add a single verb, unless a verb with the same name already exists
+    ///
+    /// Contrary to [`add_verb`](Self::add_verb), this returns an error instead of panicking if
+    /// the name is already taken. The existing verb is kept in that case.
+    pub fn try_add_verb(
+        &mut self,
+        name: impl AsRef<str>,
+        verb: impl Verb<H>,
+    ) -> Result<(), error::DuplicateNameError> {
+        insert_unique(&mut self.verbs, name.as_ref(), ErasedVerb::erase(verb))
+    }
+
+    /// Add a single asynchronous verb
+    ///
+    /// Testcases using it have to be run with
+    /// [`TestCase::run_async`](test_case::TestCase::run_async). This is only available with the
+    /// `async` feature.
+    ///
+    /// # Panics
+    ///
+    /// If a verb with the same name has already been added.
+    #[cfg(feature = "async")]
+    pub fn add_async_verb(&mut self, name: impl AsRef<str>, verb: impl verb::AsyncVerb<H>) {
+        let verb = ErasedVerb::erase_async(verb);
+
+        if let Err(error) = insert_unique(&mut self.verbs, name.as_ref(), verb) {
+            panic!("{error}");
+        }
+    }
+
+    /// Add a built-in verb whose children may be asynchronous verbs
+    #[cfg(feature = "async")]
+    fn add_nesting_verb<V>(&mut self, name: &str, verb: V)
+    where
+        V: Verb<H> + verb::AsyncVerb<H, Arguments = <V as Verb<H>>::Arguments>,
+    {
+        self.verbs
+            .insert(name.to_string(), ErasedVerb::erase_dual(verb));
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn add_nesting_verb<V: Verb<H>>(&mut self, name: &str, verb: V) {
+        self.add_verb(name, verb);
+    }
+
+    /// Add a single verb, replacing any verb with the same name
+    ///
+    /// This allows to deliberately shadow a built-in verb, or one that was added before. Returns
+    /// whether a verb was replaced.
+    pub fn override_verb(&mut self, name: impl AsRef<str>, verb: impl Verb<H>) -> bool {
+        self.verbs
+            .insert(name.as_ref().to_string(), ErasedVerb::erase(verb))
+            .is_some()
+    }
+
+    /// Remove the verb with the given name
+    ///
+    /// Testcases using the verb fail to parse afterwards. Returns whether a verb was removed.
+    pub fn remove_verb(&mut self, name: &str) -> bool {
+        self.verbs.remove(name).is_some()
+    }
+
+    /// Mark the verb with the given name as deprecated
+    ///
+    /// The verb keeps working as before, but every use of it in a testcase emits a
+    /// [`TestWarningCase::DeprecatedVerb`](error::TestWarningCase::DeprecatedVerb) warning with
+    /// the given message. Use it to point your users to its replacement.
+    pub fn deprecate_verb(&mut self, name: impl AsRef<str>, message: impl Into<String>) {
+        self.deprecated_verbs
+            .insert(name.as_ref().to_string(), message.into());
+    }
+
+    /// Set the value of a variable that can be used in string arguments
+    ///
+    /// Every `${name}` in a string argument of a testcase is replaced with the value of the
+    /// variable `name` while parsing, for example `get "${base}/health"`. Using a variable that
+    /// is not set is an error. Use `$$` to write a literal `$`.
+    ///
+    /// Strings are only interpolated once a variable is set or
+    /// [`set_env_interpolation`](Self::set_env_interpolation) is enabled, so that testcases
+    /// without variables can contain a literal `${`.
+    pub fn set_variable(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.variables.insert(name.into(), value.into());
+    }
+
+    /// Whether the string arguments of testcases are interpolated, see
+    /// [`set_variable`](Self::set_variable)
+    fn interpolates(&self) -> bool {
+        !self.variables.is_empty() || self.env_interpolation
+    }
+
+    /// Allow string arguments to read environment variables
+    ///
+    /// When enabled, every `$ENV{NAME}` in a string argument is replaced with the value of the
+    /// environment variable `NAME` while parsing. Using a variable that is not set is an error.
+    /// This is disabled by default, so that testcases do not depend on their environment.
+    pub fn set_env_interpolation(&mut self, enabled: bool) {
+        self.env_interpolation = enabled;
+    }
+
+    /// Allow top-level `include "path.kdl"` nodes
+    ///
+    /// When enabled, an `include` node adds the testcases of the file at its path in its place.
+    /// The path is resolved against the directory of the file containing the node, so only
+    /// testcases read from a file, for example with [`parse_file`](Self::parse_file), can include
+    /// other files. This is disabled by default, so that parsing a string never reads files.
+    pub fn set_allow_include(&mut self, enabled: bool) {
+        self.allow_include = enabled;
+    }
+
+    /// Set whether panics in verbs and conditions are turned into errors
+    ///
+    /// By default, a panic is caught and reported as a [`TestError::Panic`]. When disabled, panics
+    /// propagate out of the run, so that `RUST_BACKTRACE` and debuggers see them where they happen.
+    /// This only affects testcases that are parsed afterwards.
+    pub fn set_catch_panics(&mut self, enabled: bool) {
+        self.catch_panics = enabled;
+    }
+
+    /// Set whether the output of verbs is attached to their errors
+    ///
+    /// Verbs write their output to [`TestContext::stdout`] and [`TestContext::stderr`]. By
+    /// default, it is passed on to stdout and stderr after each verb. When enabled, the output
+    /// of a failing verb is shown as the help of its [`TestError`] instead, and the output of
+    /// succeeding verbs is discarded. This only affects testcases that are parsed afterwards.
+    pub fn set_capture_output(&mut self, enabled: bool) {
+        self.capture_output = enabled;
+    }
+
+    /// Recognize nodes named `name` at the top level of a document, next to `testcase`
+    ///
+    /// These nodes are skipped when parsing testcases, so that they can be handled separately
+    /// by reading the document yourself.
+    pub fn add_top_level_node(&mut self, name: impl Into<String>) {
+        let name = name.into();
+
+        if !self.top_level_nodes.contains(&name) {
+            self.top_level_nodes.push(name);
+        }
+    }
+
+    /// Set the name of the top-level nodes that contain testcases, `testcase` by default
+    ///
+    /// This allows to use a name from your domain instead, like `scenario`. Nodes named
+    /// `testcase` are then treated like any other unknown top-level node.
+    pub fn set_testcase_keyword(&mut self, keyword: impl Into<String>) {
+        self.testcase_keyword = keyword.into();
+    }
+
+    /// Set whether unknown top-level nodes are reported with the nodes that are allowed there
+    ///
+    /// By default, a top-level node that is neither a `testcase` nor added with
+    /// [`add_top_level_node`](Self::add_top_level_node) is reported as a
+    /// [`TestErrorCase::NotTestcase`](error::TestErrorCase::NotTestcase). In strict mode it is
+    /// reported as a [`TestErrorCase::UnknownTopLevelNode`](error::TestErrorCase::UnknownTopLevelNode)
+    /// instead, whose help lists all recognized top-level nodes.
+    pub fn set_strict(&mut self, enabled: bool) {
+        self.strict = enabled;
+    }
+
+    /// Set the function that is called with warnings found while parsing
+    ///
+    /// It is called once for every testcase that has warnings, even if the testcase has errors as
+    /// well. By default, warnings are ignored. The warnings of a parsed testcase are also available
+    /// through [`TestCase::warnings`](test_case::TestCase::warnings).
+    pub fn set_warning_handler(&mut self, handler: impl Fn(error::TestParseWarning) + 'static) {
+        self.warning_handler = Box::new(handler);
+    }
+
+    /// Set the function that is called on the harness before each testcase
+    ///
+    /// It is called by the runners of the [`TestDsl`], like [`run_testcase`](Self::run_testcase),
+    /// but not by [`TestCase::run`](test_case::TestCase::run).
+    pub fn set_before_each(&mut self, setup: impl Fn(&mut H) + 'static) {
+        self.before_each = Some(Box::new(setup));
+    }
+
+    /// Set the function that is called on the harness after each testcase
+    ///
+    /// It is called by the runners of the [`TestDsl`], like [`run_testcase`](Self::run_testcase),
+    /// even if the testcase failed or panicked.
+    pub fn set_after_each(&mut self, teardown: impl Fn(&mut H) + 'static) {
+        self.after_each = Some(Box::new(teardown));
+    }
+
+    /// Set the [`RunObserver`](observer::RunObserver) that is told about every verb that runs
+    ///
+    /// Like the functions set with [`set_before_each`](Self::set_before_each), it is used by the
+    /// runners of the [`TestDsl`], but not by [`TestCase::run`](test_case::TestCase::run).
+    pub fn set_observer(&mut self, observer: impl observer::RunObserver + 'static) {
+        self.observer = Some(std::rc::Rc::new(observer));
+    }
+
+    /// Set where the `print` verb writes to
+    ///
+    /// By default, it writes to stdout. Like the [`RunObserver`](observer::RunObserver), it is
+    /// used by the runners of the [`TestDsl`], while [`TestCase::run`](test_case::TestCase::run)
+    /// always prints to stdout.
+    pub fn set_output(&mut self, output: impl std::io::Write + 'static) {
+        self.output.set(output);
     }
 
     /// Add a single condition
@@ -78,501 +372,5020 @@ impl<H: 'static> TestDsl<H> {
     /// [`Condition`](condition::Condition) implementation.
     ///
     /// See [`FunctionCondition`](condition::FunctionCondition) for an easy to use way of defining conditions.
+    ///
+    /// # Panics
+    ///
+    /// If a condition with the same name has already been added. Use
+    /// [`try_add_condition`](Self::try_add_condition) to handle this case.
     pub fn add_condition(
         &mut self,
         name: impl AsRef<str>,
         condition: impl condition::Condition<H>,
     ) {
-        let existing = self
-            .conditions
-            .insert(name.as_ref().to_string(), ErasedCondition::erase(condition));
-
-        assert!(existing.is_none());
+        if let Err(error) = self.try_add_condition(name, condition) {
+            panic!("{error}");
+        }
     }
 
-    /// Parse a given document as a [`KdlDocument`](kdl::KdlDocument) and generate a
-    /// [`TestCase`](test_case::TestCase) out of it.
-    pub fn parse_testcase(
-        &self,
-        input: impl Into<TestCaseInput>,
-    ) -> Result<Vec<test_case::TestCase<H>>, error::TestParseError> {
-        let input = input.into();
-        let document = kdl::KdlDocument::parse(input.content())?;
+    /// Add a single condition, unless a condition with the same name already exists
+    ///
+    /// Contrary to [`add_condition`](Self::add_condition), this returns an error instead of
+    /// panicking if the name is already taken. The existing condition is kept in that case.
+    pub fn try_add_condition(
+        &mut self,
+        name: impl AsRef<str>,
+        condition: impl condition::Condition<H>,
+    ) -> Result<(), error::DuplicateNameError> {
+        let condition = ErasedCondition::erase(condition);
 
-        let mut cases = vec![];
+        insert_unique(&mut self.conditions, name.as_ref(), condition)
+    }
 
-        let mut errors = vec![];
+    /// Add a single condition, replacing any condition with the same name
+    ///
+    /// This allows to deliberately shadow a built-in condition, or one that was added before.
+    /// Returns whether a condition was replaced.
+    pub fn override_condition(
+        &mut self,
+        name: impl AsRef<str>,
+        condition: impl condition::Condition<H>,
+    ) -> bool {
+        self.conditions
+            .insert(name.as_ref().to_string(), ErasedCondition::erase(condition))
+            .is_some()
+    }
 
-        for testcase_node in document.nodes() {
-            if testcase_node.name().value() != "testcase" {
-                errors.push(error::TestErrorCase::NotTestcase {
-                    span: testcase_node.name().span(),
-                });
+    /// Remove the condition with the given name
+    ///
+    /// Testcases using the condition fail to parse afterwards. Returns whether a condition was
+    /// removed.
+    pub fn remove_condition(&mut self, name: &str) -> bool {
+        self.conditions.remove(name).is_some()
+    }
 
-                continue;
-            }
+    /// Add a single condition that needs mutable access to the harness
+    ///
+    /// Such conditions can be used wherever a [`Condition`](condition::Condition) can. See
+    /// [`MutCondition`](condition::MutCondition) for the contract these conditions have to
+    /// uphold.
+    ///
+    /// # Panics
+    ///
+    /// If a condition with the same name has already been added.
+    pub fn add_mut_condition(
+        &mut self,
+        name: impl AsRef<str>,
+        condition: impl condition::MutCondition<H>,
+    ) {
+        let condition = ErasedCondition::erase_mut(condition);
 
-            let mut testcase = test_case::TestCase::new(input.clone());
+        if let Err(error) = insert_unique(&mut self.conditions, name.as_ref(), condition) {
+            panic!("{error}");
+        }
+    }
 
-            for node in testcase_node.iter_children() {
-                match VerbInstance::with_test_dsl(self, node) {
-                    Ok(verb) => testcase.cases.push(verb),
-                    Err(e) => errors.push(e),
-                }
-            }
+    /// Add a single asynchronous condition
+    ///
+    /// Async conditions can only be awaited by the built-in `wait_for` verb, in testcases that are
+    /// run with [`TestCase::run_async`](test_case::TestCase::run_async). This is only available
+    /// with the `async` feature.
+    ///
+    /// # Panics
+    ///
+    /// If a condition with the same name has already been added.
+    #[cfg(feature = "async")]
+    pub fn add_async_condition(
+        &mut self,
+        name: impl AsRef<str>,
+        condition: impl condition::AsyncCondition<H>,
+    ) {
+        let condition = ErasedCondition::erase_async(condition);
 
-            cases.push(testcase);
+        if let Err(error) = insert_unique(&mut self.conditions, name.as_ref(), condition) {
+            panic!("{error}");
         }
+    }
 
-        if !errors.is_empty() {
-            return Err(error::TestParseError {
-                errors,
-                source_code: Some(input.clone()),
-            });
+    /// Add a single producer
+    ///
+    /// Producers are run with the built-in `measure` verb, which stores the produced value in the
+    /// [`TestContext`] for later verbs and conditions. See the [`producer`] module for details.
+    ///
+    /// # Panics
+    ///
+    /// If a producer with the same name has already been added.
+    pub fn add_producer<T: 'static>(
+        &mut self,
+        name: impl AsRef<str>,
+        producer: impl producer::Producer<H, T>,
+    ) {
+        let producer = producer::ErasedProducer::erase(producer);
+
+        if let Err(error) = insert_unique(&mut self.producers, name.as_ref(), producer) {
+            panic!("{error}");
         }
+    }
 
-        Ok(cases)
+    /// The names of all registered verbs, sorted alphabetically
+    pub fn list_verbs(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.verbs.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
     }
 
-    fn get_condition_for_node(
-        &self,
-        condition_node: &kdl::KdlNode,
-    ) -> Result<ErasedCondition<H>, error::TestErrorCase> {
-        let condition = self
-            .conditions
-            .get(condition_node.name().value())
-            .ok_or_else(|| error::TestErrorCase::UnknownCondition {
-                condition: condition_node.name().span(),
-            })?
-            .clone();
+    /// The names of all registered conditions, sorted alphabetically
+    pub fn list_conditions(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.conditions.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
 
-        Ok(condition)
+    /// Check whether a verb with the given name is registered
+    pub fn has_verb(&self, name: &str) -> bool {
+        self.verbs.contains_key(name)
     }
 
-    fn get_verb_for_node(
-        &self,
-        verb_node: &kdl::KdlNode,
-    ) -> Result<ErasedVerb<H>, error::TestErrorCase> {
-        let verb = self
-            .verbs
-            .get(verb_node.name().value())
-            .ok_or_else(|| error::TestErrorCase::UnknownVerb {
-                verb: verb_node.name().span(),
-            })?
-            .clone();
+    /// Check whether a condition with the given name is registered
+    pub fn has_condition(&self, name: &str) -> bool {
+        self.conditions.contains_key(name)
+    }
 
-        Ok(verb)
+    /// Describe the arguments of the verb with the given name
+    ///
+    /// Returns `None` if there is no such verb, or if it does not describe its arguments. See
+    /// [`Verb::signature`](verb::Verb::signature).
+    pub fn verb_signature(&self, name: &str) -> Option<&argument::Signature> {
+        self.verbs.get(name)?.signature()
     }
-}
 
-#[derive(Debug, Clone)]
-/// The input to a [`TestCase`](test_case::TestCase)
-pub enum TestCaseInput {
-    /// Input that is not backed by a file
-    InMemory(Arc<str>),
-    /// Input that is backed by a file
-    FromFile {
-        /// The filepath of the file the contents are read from
-        filepath: Arc<str>,
-        /// The content of the file
-        contents: Arc<str>,
-    },
-}
+    /// Move all verbs, conditions and producers of `other` into this [`TestDsl`]
+    ///
+    /// Deprecations of the moved verbs are moved along with them. If a name is registered in
+    /// both, `strategy` decides which one is kept. In both cases, the names that collided are
+    /// returned as an error, sorted alphabetically.
+    pub fn merge(&mut self, other: TestDsl<H>, strategy: MergeStrategy) -> Result<(), Vec<String>> {
+        let TestDsl {
+            verbs,
+            conditions,
+            producers,
+            mut deprecated_verbs,
+            ..
+        } = other;
 
-impl From<&str> for TestCaseInput {
-    fn from(value: &str) -> Self {
-        TestCaseInput::InMemory(Arc::from(value))
-    }
-}
+        let mut collisions = vec![];
 
-impl miette::SourceCode for TestCaseInput {
-    fn read_span<'a>(
-        &'a self,
-        span: &miette::SourceSpan,
-        context_lines_before: usize,
-        context_lines_after: usize,
-    ) -> Result<Box<dyn miette::SpanContents<'a> + 'a>, miette::MietteError> {
-        match self {
-            TestCaseInput::InMemory(content) => {
-                content.read_span(span, context_lines_before, context_lines_after)
-            }
-            TestCaseInput::FromFile {
-                filepath: filename,
-                contents,
-            } => {
-                let inner_contents =
-                    contents.read_span(span, context_lines_before, context_lines_after)?;
-                let mut contents = miette::MietteSpanContents::new_named(
-                    filename.to_string(),
-                    inner_contents.data(),
-                    *inner_contents.span(),
-                    inner_contents.line(),
-                    inner_contents.column(),
-                    inner_contents.line_count(),
-                );
-                contents = contents.with_language("kdl");
-                Ok(Box::new(contents))
+        for (name, verb) in verbs {
+            if self.verbs.contains_key(&name) {
+                collisions.push(name.clone());
+
+                if strategy == MergeStrategy::KeepExisting {
+                    continue;
+                }
             }
-        }
-    }
-}
 
-impl TestCaseInput {
-    fn content(&self) -> &str {
-        match self {
-            TestCaseInput::InMemory(content) => content,
-            TestCaseInput::FromFile { contents, .. } => contents,
+            match deprecated_verbs.remove(&name) {
+                Some(message) => self.deprecated_verbs.insert(name.clone(), message),
+                None => self.deprecated_verbs.remove(&name),
+            };
+            self.verbs.insert(name, verb);
         }
-    }
-}
 
-#[derive(Debug, Clone)]
-struct AssertConditions;
+        for (name, condition) in conditions {
+            if self.conditions.contains_key(&name) {
+                collisions.push(name.clone());
 
-impl<H: 'static> Verb<H> for AssertConditions {
-    type Arguments = ConditionChildren<H, ((),)>;
-    fn run(&self, harness: &mut H, arguments: &Self::Arguments) -> miette::Result<()> {
-        for child in arguments.children() {
-            child.run(harness)?;
+                if strategy == MergeStrategy::KeepExisting {
+                    continue;
+                }
+            }
+
+            self.conditions.insert(name, condition);
         }
 
-        Ok(())
-    }
-}
+        for (name, producer) in producers {
+            if self.producers.contains_key(&name) {
+                collisions.push(name.clone());
 
-#[derive(Debug, Clone)]
-struct Group;
+                if strategy == MergeStrategy::KeepExisting {
+                    continue;
+                }
+            }
 
-impl<H: 'static> Verb<H> for Group {
-    type Arguments = VerbChildren<H, ((),)>;
-    fn run(&self, harness: &mut H, arguments: &Self::Arguments) -> miette::Result<()> {
-        for child in arguments.children() {
-            child.run(harness)?;
+            self.producers.insert(name, producer);
+        }
+
+        if !collisions.is_empty() {
+            collisions.sort_unstable();
+            return Err(collisions);
         }
 
         Ok(())
     }
-}
 
-#[derive(Debug, Clone)]
-struct Repeat;
+    /// Generate a Markdown reference of all registered verbs and conditions
+    ///
+    /// Both are listed in alphabetical order together with their arguments, how many of them have
+    /// to be given and whether they take children, as described by their
+    /// [`Signature`](argument::Signature). Without a signature only the type of their arguments is
+    /// shown. Verbs that were marked with [`deprecate_verb`](Self::deprecate_verb) include their
+    /// deprecation message. As the reference is generated from the registered verbs and
+    /// conditions, it always reflects what testcases can use.
+    pub fn to_markdown(&self) -> String {
+        use std::fmt::Write;
 
-impl<H: 'static> Verb<H> for Repeat {
-    type Arguments = VerbChildren<H, (usize,)>;
-    fn run(&self, harness: &mut H, arguments: &Self::Arguments) -> miette::Result<()> {
-        let (times,) = *arguments.parameters();
+        let mut verbs: Vec<_> = self.verbs.iter().collect();
+        verbs.sort_by_key(|(name, _)| *name);
 
-        for _ in 0..times {
-            for child in arguments.children() {
-                child.run(harness)?;
-            }
+        let mut conditions: Vec<_> = self.conditions.iter().collect();
+        conditions.sort_by_key(|(name, _)| *name);
+
+        let mut markdown = String::from("# Verbs\n\n");
+        markdown.push_str("| Verb | Arguments | Arity | Children | Deprecated |\n");
+        markdown.push_str("| --- | --- | --- | --- | --- |\n");
+
+        for (name, verb) in verbs {
+            let deprecated = self
+                .deprecated_verbs
+                .get(name)
+                .map(String::as_str)
+                .unwrap_or_default();
+
+            writeln!(
+                markdown,
+                "| `{name}` | {} | {deprecated} |",
+                markdown_signature(verb.signature(), verb.arguments_name())
+            )
+            .unwrap();
         }
 
-        Ok(())
+        markdown.push_str("\n# Conditions\n\n");
+        markdown.push_str("| Condition | Arguments | Arity | Children |\n");
+        markdown.push_str("| --- | --- | --- | --- |\n");
+
+        for (name, condition) in conditions {
+            writeln!(
+                markdown,
+                "| `{name}` | {} |",
+                markdown_signature(condition.signature(), condition.arguments_name())
+            )
+            .unwrap();
+        }
+
+        markdown
     }
-}
 
-/// An instance of a [`Condition`](condition::Condition)
-pub struct ConditionInstance<H> {
-    _pd: PhantomData<fn(H)>,
-    condition: ErasedCondition<H>,
-    arguments: Box<dyn BoxedArguments<H>>,
-    node: kdl::KdlNode,
-}
+    /// Parse a given document as a [`KdlDocument`](kdl::KdlDocument) and generate a
+    /// [`TestCase`](test_case::TestCase) out of it.
+    ///
+    /// If enabled with [`set_allow_include`](Self::set_allow_include), a top-level
+    /// `include "path.kdl"` node adds the testcases of that file in its place.
+    pub fn parse_testcase(
+        &self,
+        input: impl Into<TestCaseInput>,
+    ) -> Result<Vec<test_case::TestCase<H>>, error::TestParseError> {
+        let input = input.into();
 
-impl<H: 'static> Clone for ConditionInstance<H> {
-    fn clone(&self) -> Self {
-        Self {
-            _pd: self._pd,
-            condition: self.condition.clone(),
-            arguments: self.arguments.clone(),
-            node: self.node.clone(),
+        let mut cases = vec![];
+
+        let mut errors = vec![];
+
+        self.parse_testcase_streaming(input.clone(), |result| {
+            match result {
+                Ok(testcase) => cases.push(testcase),
+                Err(e) => errors.push(e),
+            }
+
+            ControlFlow::Continue(())
+        })?;
+
+        if !errors.is_empty() {
+            return Err(error::TestParseError {
+                errors,
+                source_code: Some(input.clone()),
+            });
         }
-    }
-}
 
-impl<H> std::fmt::Debug for ConditionInstance<H> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("ConditionInstance")
-            .field("_pd", &self._pd)
-            .field("condition", &self.condition)
-            .field("arguments", &self.arguments)
-            .field("node", &self.node)
-            .finish()
+        Ok(cases)
     }
-}
 
-impl<H: 'static> ConditionInstance<H> {
-    /// Create a new instance with the given node and [`TestDsl`]
-    pub fn with_test_dsl(
-        test_dsl: &TestDsl<H>,
-        node: &kdl::KdlNode,
-    ) -> Result<Self, TestErrorCase> {
-        let condition = test_dsl.get_condition_for_node(node)?;
+    /// Read the file at `path` and parse the [`TestCase`](test_case::TestCase)s in it
+    ///
+    /// The testcases are parsed from a [`TestCaseInput::FromFile`], so that diagnostics and
+    /// [`TestCase::path`](test_case::TestCase::path) refer to the file.
+    pub fn parse_file(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<Vec<test_case::TestCase<H>>, error::TestParseError> {
+        let path = path.as_ref();
 
-        let arguments = condition.parse_args(test_dsl, node)?;
+        let contents =
+            std::fs::read_to_string(path).map_err(|source| error::TestErrorCase::Io {
+                path: path.display().to_string(),
+                source,
+            })?;
 
-        Ok(ConditionInstance {
-            _pd: PhantomData,
-            condition,
-            arguments,
-            node: node.clone(),
+        self.parse_testcase(TestCaseInput::FromFile {
+            filepath: Arc::from(path.to_string_lossy()),
+            contents: Arc::from(contents),
         })
     }
 
-    /// Run the condition
+    /// Parse all files in `dir` and its subdirectories whose name matches `pattern`
     ///
-    /// This returns an error if:
-    /// - The condition returns [`Ok(false)`](Ok)
-    /// - It returns an [`Err`]
-    /// - It [`panic`]s
-    pub fn run(&self, harness: &mut H) -> Result<(), TestError> {
-        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            self.condition
-                .check_now(harness, self.arguments.as_dyn_any())
-        }));
+    /// The pattern is matched against the file name only. It supports `*` for any number of
+    /// characters and `?` for a single character, for example `*.kdl`. Files are parsed in
+    /// alphabetical order with [`parse_file`](Self::parse_file), and the testcases of all of them
+    /// are returned together.
+    ///
+    /// If any file fails to parse, the errors of all failing files are returned, each pointing
+    /// into its own file. Symbolic links to directories are followed, but every directory is only
+    /// searched once, so that a link to one of its parents does not recurse forever.
+    pub fn parse_directory(
+        &self,
+        dir: impl AsRef<Path>,
+        pattern: &str,
+    ) -> Result<Vec<test_case::TestCase<H>>, error::TestDirectoryError> {
+        let mut files = vec![];
+        let mut errors = vec![];
 
-        match res {
-            Ok(Ok(true)) => Ok(()),
-            Ok(Ok(false)) => Err(TestError::ConditionFailed {
-                span: self.node.span(),
-            }),
-            Ok(Err(error)) => Err(TestError::Error {
-                error,
-                span: self.node.span(),
-            }),
-            Err(payload) => {
-                let mut message = "Something went wrong".to_string();
+        collect_files(
+            dir.as_ref(),
+            pattern,
+            &mut HashSet::new(),
+            &mut files,
+            &mut errors,
+        );
+        files.sort();
+
+        let mut cases = vec![];
+
+        for file in files {
+            match self.parse_file(file) {
+                Ok(testcases) => cases.extend(testcases),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(error::TestDirectoryError { errors });
+        }
+
+        Ok(cases)
+    }
+
+    /// Parse a given document and hand each [`TestCase`](test_case::TestCase) to `on_result` as
+    /// soon as it has been parsed
+    ///
+    /// Contrary to [`parse_testcase`](Self::parse_testcase), errors are not aggregated. The
+    /// callback is invoked once for every error that was found, and a testcase containing errors
+    /// is not handed out. Returning [`ControlFlow::Break`] from the callback stops parsing the
+    /// rest of the document.
+    ///
+    /// An error is only returned if the document is not valid KDL.
+    pub fn parse_testcase_streaming(
+        &self,
+        input: impl Into<TestCaseInput>,
+        mut on_result: impl FnMut(
+            Result<test_case::TestCase<H>, error::TestErrorCase>,
+        ) -> ControlFlow<()>,
+    ) -> Result<(), error::TestParseError> {
+        let input = input.into();
+        let document = kdl::KdlDocument::parse(input.content())?;
+
+        let mut includes = input
+            .filepath()
+            .map(|filepath| (include_key(Path::new(&**filepath)), filepath.clone()))
+            .into_iter()
+            .collect();
+
+        let _ = self.parse_document(&input, document, &mut includes, &mut on_result);
+
+        Ok(())
+    }
+
+    /// Parse the testcases of `document`, splicing in the testcases of included files
+    ///
+    /// `includes` are the keys and paths of the files that are currently being included, to
+    /// detect cycles.
+    fn parse_document(
+        &self,
+        input: &TestCaseInput,
+        mut document: kdl::KdlDocument,
+        includes: &mut Vec<(std::path::PathBuf, Arc<str>)>,
+        on_result: &mut dyn FnMut(
+            Result<test_case::TestCase<H>, error::TestErrorCase>,
+        ) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        for testcase_node in document.nodes_mut() {
+            let node_name = testcase_node.name().value();
+
+            if self.top_level_nodes.iter().any(|name| name == node_name) {
+                continue;
+            }
+
+            if self.allow_include && node_name == "include" {
+                if self
+                    .parse_include(input, testcase_node, includes, on_result)
+                    .is_break()
+                {
+                    return ControlFlow::Break(());
+                }
+
+                continue;
+            }
+
+            if node_name != self.testcase_keyword {
+                let error = self.unknown_top_level_node(testcase_node);
+
+                if on_result(Err(error)).is_break() {
+                    return ControlFlow::Break(());
+                }
+
+                continue;
+            }
 
-                if let Some(msg) = payload.downcast_ref::<&str>() {
-                    message = msg.to_string();
+            let mut testcase = test_case::TestCase::new(input.clone(), testcase_node.clone());
+            let mut has_errors = false;
+
+            if self.interpolates() {
+                for error in interpolation::interpolate_node(
+                    testcase_node,
+                    &self.variables,
+                    self.env_interpolation,
+                ) {
+                    has_errors = true;
+
+                    if on_result(Err(error)).is_break() {
+                        return ControlFlow::Break(());
+                    }
                 }
+            }
 
-                if let Some(msg) = payload.downcast_ref::<String>() {
-                    message.clone_from(msg);
+            let testcase_node = &*testcase_node;
+
+            let name =
+                <(Option<String>,) as argument::ParseArguments<H>>::parse(self, testcase_node);
+            let tags = argument::parse_named::<Option<String>>(testcase_node, "tags");
+
+            match argument::join(name, tags) {
+                Ok(((name,), tags)) => {
+                    testcase.name = name;
+                    testcase.tags = tags
+                        .iter()
+                        .flat_map(|tags| tags.split(','))
+                        .map(str::trim)
+                        .filter(|tag| !tag.is_empty())
+                        .map(String::from)
+                        .collect();
                 }
+                Err(error) => {
+                    has_errors = true;
 
-                Err(TestError::Panic {
-                    error: miette::Report::msg(message),
-                    span: self.node.span(),
-                })
+                    for error in error.flatten() {
+                        if on_result(Err(error)).is_break() {
+                            return ControlFlow::Break(());
+                        }
+                    }
+                }
+            }
+
+            let previous = self.parsing_warnings.replace(Some(vec![]));
+            let verbs = testcase_node
+                .iter_children()
+                .map(|node| VerbInstance::with_test_dsl(self, node))
+                .collect::<Vec<_>>();
+            testcase.warnings = self.parsing_warnings.replace(previous).unwrap_or_default();
+
+            if !testcase.warnings.is_empty() {
+                (self.warning_handler)(error::TestParseWarning {
+                    warnings: testcase.warnings.clone(),
+                    source_code: input.clone(),
+                });
+            }
+
+            for verb in verbs {
+                match verb {
+                    Ok(verb) => testcase.cases.push(verb),
+                    Err(e) => {
+                        has_errors = true;
+
+                        for error in e.flatten() {
+                            if on_result(Err(error)).is_break() {
+                                return ControlFlow::Break(());
+                            }
+                        }
+                    }
+                }
+            }
+
+            if !has_errors && on_result(Ok(testcase)).is_break() {
+                return ControlFlow::Break(());
             }
         }
+
+        ControlFlow::Continue(())
     }
-}
 
-/// An instance of a [`Verb`]
-pub struct VerbInstance<H> {
-    _pd: PhantomData<fn(H)>,
-    verb: ErasedVerb<H>,
-    arguments: Box<dyn BoxedArguments<H>>,
-    node: kdl::KdlNode,
-}
+    /// Parse the testcases of the file included by the `include` node `node`
+    fn parse_include(
+        &self,
+        input: &TestCaseInput,
+        node: &kdl::KdlNode,
+        includes: &mut Vec<(std::path::PathBuf, Arc<str>)>,
+        on_result: &mut dyn FnMut(
+            Result<test_case::TestCase<H>, error::TestErrorCase>,
+        ) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let path = match <(String,) as argument::ParseArguments<H>>::parse(self, node) {
+            Ok((path,)) => path,
+            Err(error) => {
+                for error in error.flatten() {
+                    if on_result(Err(error)).is_break() {
+                        return ControlFlow::Break(());
+                    }
+                }
 
-impl<H> std::fmt::Debug for VerbInstance<H> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("VerbInstance")
-            .field("_pd", &self._pd)
-            .field("verb", &self.verb)
-            .field("arguments", &self.arguments)
-            .field("node", &self.node)
-            .finish()
+                return ControlFlow::Continue(());
+            }
+        };
+
+        let Some(filepath) = input.filepath() else {
+            return on_result(Err(error::TestErrorCase::IncludeWithoutFile {
+                span: node.name().span(),
+            }));
+        };
+
+        let path = Path::new(&**filepath)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(path);
+
+        let key = include_key(&path);
+        let filepath = Arc::<str>::from(path.to_string_lossy());
+
+        if let Some(start) = includes.iter().position(|(include, _)| *include == key) {
+            let cycle = includes[start..]
+                .iter()
+                .map(|(_, filepath)| filepath)
+                .chain([&filepath])
+                .map(|filepath| format!("`{filepath}`"))
+                .collect::<Vec<_>>();
+
+            return on_result(Err(error::TestErrorCase::IncludeCycle {
+                path: node.entry(0).map_or(node.span(), |entry| entry.span()),
+                cycle: format!(
+                    "The includes form a cycle: {}",
+                    cycle.join(", which includes ")
+                ),
+            }));
+        }
+
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(source) => {
+                return on_result(Err(error::TestErrorCase::Io {
+                    path: path.display().to_string(),
+                    source,
+                }));
+            }
+        };
+
+        let included = TestCaseInput::included(filepath.clone(), contents, input);
+        let mut on_result = |result: Result<_, _>| {
+            on_result(
+                result.map_err(|error| error::TestErrorCase::InIncludedFile {
+                    source_code: included.clone(),
+                    error: miette::Error::new(error),
+                }),
+            )
+        };
+
+        let document = match kdl::KdlDocument::parse(included.content()) {
+            Ok(document) => document,
+            Err(source) => return on_result(Err(error::TestErrorCase::Kdl { source })),
+        };
+
+        includes.push((key, filepath));
+        let flow = self.parse_document(&included, document, includes, &mut on_result);
+        includes.pop();
+
+        flow
+    }
+
+    fn unknown_top_level_node(&self, node: &kdl::KdlNode) -> error::TestErrorCase {
+        let span = node.name().span();
+
+        if !self.strict {
+            return error::TestErrorCase::NotTestcase {
+                span,
+                keyword: self.testcase_keyword.clone(),
+            };
+        }
+
+        let mut names = vec![self.testcase_keyword.as_str()];
+        if self.allow_include {
+            names.push("include");
+        }
+        for name in &self.top_level_nodes {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+
+        let names = names
+            .iter()
+            .map(|name| format!("`{name}`"))
+            .collect::<Vec<_>>();
+        let allowed = match names.split_last() {
+            Some((last, others)) if !others.is_empty() => {
+                format!("{} and {last}", others.join(", "))
+            }
+            _ => names.concat(),
+        };
+
+        error::TestErrorCase::UnknownTopLevelNode {
+            span,
+            allowed: format!("Only {allowed} nodes are allowed at the top level"),
+        }
+    }
+
+    /// Run a single testcase, surrounded by the functions set with
+    /// [`set_before_each`](Self::set_before_each) and [`set_after_each`](Self::set_after_each)
+    ///
+    /// A skipped testcase counts as success, like with
+    /// [`TestCase::run`](test_case::TestCase::run).
+    pub fn run_testcase(
+        &self,
+        testcase: &test_case::TestCase<H>,
+        harness: &mut H,
+    ) -> Result<(), test_case::TestCaseError> {
+        test_case::skipped_as_passed(self.run_with_hooks(
+            testcase,
+            harness,
+            TestContext::random_seed(),
+        ))
+        .map(|_| ())
+    }
+
+    fn run_with_hooks(
+        &self,
+        testcase: &test_case::TestCase<H>,
+        harness: &mut H,
+        seed: u64,
+    ) -> (test_case::RunReport, Option<test_case::TestCaseError>) {
+        if let Some(before_each) = &self.before_each {
+            before_each(harness);
+        }
+
+        let mut context = TestContext::new(seed).with_output(self.output.clone());
+        if let Some(observer) = &self.observer {
+            context = context.with_observer(observer::ObserverHandle::new(
+                observer.clone(),
+                testcase.source_code.clone(),
+            ));
+        }
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            testcase.run_with_context(harness, context)
+        }));
+
+        if let Some(after_each) = &self.after_each {
+            after_each(harness);
+        }
+
+        result.unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+
+    /// Run only the testcases at the given indices of `cases`
+    ///
+    /// Each selected testcase runs on a fresh harness, `make_harness` is called once per testcase
+    /// right before running it. The results are returned together with the index of their
+    /// testcase, in the order of `selection`. Indices that are out of range are ignored.
+    ///
+    /// Passing the indices of the failed outcomes back in allows to rerun only those testcases.
+    ///
+    /// The functions set with [`set_before_each`](Self::set_before_each) and
+    /// [`set_after_each`](Self::set_after_each) are called around each testcase.
+    pub fn run_selected(
+        &self,
+        cases: &[test_case::TestCase<H>],
+        mut make_harness: impl FnMut() -> H,
+        selection: impl IntoIterator<Item = usize>,
+    ) -> Vec<(usize, test_case::TestOutcome)> {
+        selection
+            .into_iter()
+            .filter_map(|index| {
+                let testcase = cases.get(index)?;
+                let mut harness = make_harness();
+
+                Some((
+                    index,
+                    test_case::TestOutcome::from_run(self.run_with_hooks(
+                        testcase,
+                        &mut harness,
+                        TestContext::random_seed(),
+                    )),
+                ))
+            })
+            .collect()
+    }
+
+    /// Parse `input` and run its testcases on up to `num_threads` threads, each on a fresh harness
+    ///
+    /// Verbs, conditions and testcases are not [`Send`], so they cannot be moved to another thread.
+    /// Instead, every thread creates its own [`TestDsl`] with `make_dsl` and parses `input` itself.
+    /// The testcases are handed out to the threads one at a time, and `make_harness` is called on
+    /// the thread running a testcase, right before running it. The harness therefore never crosses
+    /// threads.
+    ///
+    /// The results are returned together with the name of their testcase, in the order the
+    /// testcases appear in `input`, no matter which of them finished first. If `input` does not
+    /// parse, the error is returned before any testcase runs. A `num_threads` of `0` is treated as
+    /// `1`.
+    ///
+    /// The functions set with [`set_before_each`](Self::set_before_each) and
+    /// [`set_after_each`](Self::set_after_each) are called around each testcase.
+    ///
+    /// If a [`TestDsl`] returned by `make_dsl` fails to parse `input` on one of the threads, or
+    /// parses a different number of testcases, that error is returned once all threads finished.
+    pub fn run_parallel(
+        make_dsl: impl Fn() -> TestDsl<H> + Sync,
+        input: impl Into<TestCaseInput>,
+        make_harness: impl Fn() -> H + Sync,
+        num_threads: usize,
+    ) -> Result<Vec<(Option<String>, test_case::TestOutcome)>, error::TestParseError> {
+        let input = input.into();
+        let count = make_dsl().parse_testcase(input.clone())?.len();
+
+        let next = std::sync::atomic::AtomicUsize::new(0);
+        let results = std::sync::Mutex::new((0..count).map(|_| None).collect::<Vec<_>>());
+
+        std::thread::scope(|scope| {
+            let threads = (0..num_threads.clamp(1, count.max(1)))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let dsl = make_dsl();
+                        let cases = dsl.parse_testcase(input.clone())?;
+
+                        if cases.len() != count {
+                            return Err(error::TestParseError {
+                                errors: vec![error::TestErrorCase::TestcaseCountChanged {
+                                    expected: count,
+                                    found: cases.len(),
+                                }],
+                                source_code: Some(input.clone()),
+                            });
+                        }
+
+                        loop {
+                            let index = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                            let Some(testcase) = cases.get(index) else {
+                                break;
+                            };
+
+                            let mut harness = make_harness();
+                            let result = test_case::TestOutcome::from_run(dsl.run_with_hooks(
+                                testcase,
+                                &mut harness,
+                                TestContext::random_seed(),
+                            ));
+
+                            results.lock().unwrap_or_else(PoisonError::into_inner)[index] =
+                                Some((testcase.name().map(ToString::to_string), result));
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            threads.into_iter().try_for_each(|thread| {
+                thread
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+        })?;
+
+        Ok(results
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .into_iter()
+            .map(|result| result.expect("Every thread parsed all testcases, so each one was run"))
+            .collect())
+    }
+
+    fn get_condition_for_node(
+        &self,
+        condition_node: &kdl::KdlNode,
+    ) -> Result<ErasedCondition<H>, error::TestErrorCase> {
+        let condition = self
+            .conditions
+            .get(condition_node.name().value())
+            .ok_or_else(|| error::TestErrorCase::UnknownCondition {
+                condition: condition_node.name().span(),
+                suggestion: did_you_mean(condition_node.name().value(), self.conditions.keys()),
+            })?
+            .clone();
+
+        Ok(condition)
+    }
+
+    fn get_producer(&self, name: &str) -> Option<producer::ErasedProducer<H>> {
+        self.producers.get(name).cloned()
+    }
+
+    fn get_verb_for_node(
+        &self,
+        verb_node: &kdl::KdlNode,
+    ) -> Result<ErasedVerb<H>, error::TestErrorCase> {
+        let verb = self
+            .verbs
+            .get(verb_node.name().value())
+            .ok_or_else(|| error::TestErrorCase::UnknownVerb {
+                verb: verb_node.name().span(),
+                suggestion: did_you_mean(verb_node.name().value(), self.verbs.keys()),
+            })?
+            .clone();
+
+        let deprecation = self.deprecated_verbs.get(verb_node.name().value());
+
+        if let (Some(message), Some(warnings)) =
+            (deprecation, &mut *self.parsing_warnings.borrow_mut())
+        {
+            warnings.push(error::TestWarningCase::DeprecatedVerb {
+                verb: verb_node.name().span(),
+                message: message.clone(),
+            });
+        }
+
+        Ok(verb)
+    }
+}
+
+/// What [`TestDsl::merge`] does with names that are registered in both [`TestDsl`]s
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Keep the verb, condition or producer that was already registered
+    KeepExisting,
+    /// Replace it with the one being merged in
+    Overwrite,
+}
+
+#[derive(Debug, Clone)]
+/// The input to a [`TestCase`](test_case::TestCase)
+pub enum TestCaseInput {
+    /// Input that is not backed by a file
+    InMemory(Arc<str>),
+    /// Input that is backed by a file
+    FromFile {
+        /// The filepath of the file the contents are read from
+        filepath: Arc<str>,
+        /// The content of the file
+        contents: Arc<str>,
+    },
+    /// Input that was included from another input
+    Included {
+        /// The filepath of the included file
+        filepath: Arc<str>,
+        /// The content of the included file
+        contents: Arc<str>,
+        /// The filepath of the input that included this file, if it has one
+        included_from: Option<Arc<str>>,
+    },
+}
+
+impl From<&str> for TestCaseInput {
+    fn from(value: &str) -> Self {
+        TestCaseInput::InMemory(Arc::from(value))
+    }
+}
+
+impl miette::SourceCode for TestCaseInput {
+    fn read_span<'a>(
+        &'a self,
+        span: &miette::SourceSpan,
+        context_lines_before: usize,
+        context_lines_after: usize,
+    ) -> Result<Box<dyn miette::SpanContents<'a> + 'a>, miette::MietteError> {
+        let name = match self {
+            TestCaseInput::InMemory(content) => {
+                let contents =
+                    content.read_span(span, context_lines_before, context_lines_after)?;
+
+                return Ok(Box::new(
+                    miette::MietteSpanContents::new(
+                        contents.data(),
+                        *contents.span(),
+                        contents.line(),
+                        contents.column(),
+                        contents.line_count(),
+                    )
+                    .with_language("kdl"),
+                ));
+            }
+            TestCaseInput::FromFile { filepath, .. } => filepath.to_string(),
+            TestCaseInput::Included {
+                filepath,
+                included_from: Some(included_from),
+                ..
+            } => format!("{filepath} (included from {included_from})"),
+            TestCaseInput::Included {
+                filepath,
+                included_from: None,
+                ..
+            } => format!("{filepath} (included)"),
+        };
+
+        let inner_contents =
+            self.content()
+                .read_span(span, context_lines_before, context_lines_after)?;
+        let mut contents = miette::MietteSpanContents::new_named(
+            name,
+            inner_contents.data(),
+            *inner_contents.span(),
+            inner_contents.line(),
+            inner_contents.column(),
+            inner_contents.line_count(),
+        );
+        contents = contents.with_language("kdl");
+        Ok(Box::new(contents))
+    }
+}
+
+impl TestCaseInput {
+    /// Create an input for `contents` that is labeled as coming from `filepath`
+    ///
+    /// The contents do not have to be read from that file, it is only used for diagnostics and
+    /// [`TestCase::path`](test_case::TestCase::path).
+    pub fn from_file_parts(filepath: impl Into<Arc<str>>, contents: impl Into<Arc<str>>) -> Self {
+        TestCaseInput::FromFile {
+            filepath: filepath.into(),
+            contents: contents.into(),
+        }
+    }
+
+    /// Create the input of a file that was included by `parent`
+    ///
+    /// Diagnostics pointing into this input are labeled with its own path, as well as the path of
+    /// the including input.
+    pub fn included(
+        filepath: impl Into<Arc<str>>,
+        contents: impl Into<Arc<str>>,
+        parent: &TestCaseInput,
+    ) -> Self {
+        TestCaseInput::Included {
+            filepath: filepath.into(),
+            contents: contents.into(),
+            included_from: parent.filepath().cloned(),
+        }
+    }
+
+    /// Resolve the start of `span` to its line and column, both starting at 1
+    ///
+    /// Returns `None` if the span lies outside of this input.
+    pub fn line_column(&self, span: miette::SourceSpan) -> Option<(usize, usize)> {
+        let contents = miette::SourceCode::read_span(self.content(), &span, 0, 0).ok()?;
+
+        Some((contents.line() + 1, contents.column() + 1))
+    }
+
+    fn content(&self) -> &str {
+        match self {
+            TestCaseInput::InMemory(content) => content,
+            TestCaseInput::FromFile { contents, .. } => contents,
+            TestCaseInput::Included { contents, .. } => contents,
+        }
+    }
+
+    pub(crate) fn filepath(&self) -> Option<&Arc<str>> {
+        match self {
+            TestCaseInput::InMemory(_) => None,
+            TestCaseInput::FromFile { filepath, .. } => Some(filepath),
+            TestCaseInput::Included { filepath, .. } => Some(filepath),
+        }
+    }
+}
+
+/// Add `value` under `name`, unless the name is already taken
+///
+/// All the ways of adding verbs, conditions and producers go through this, so that a duplicate
+/// name is always reported with a [`DuplicateNameError`](error::DuplicateNameError).
+fn insert_unique<T>(
+    map: &mut HashMap<String, T>,
+    name: &str,
+    value: T,
+) -> Result<(), error::DuplicateNameError> {
+    if map.contains_key(name) {
+        return Err(error::DuplicateNameError {
+            name: name.to_string(),
+        });
+    }
+
+    map.insert(name.to_string(), value);
+
+    Ok(())
+}
+
+/// The key of an included file, used to detect include cycles
+fn include_key(path: &Path) -> std::path::PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Collect the files in `dir` and its subdirectories whose name matches `pattern`
+///
+/// `visited` are the canonical paths of the directories that were already searched, to not
+/// follow symbolic links in a cycle.
+fn collect_files(
+    dir: &Path,
+    pattern: &str,
+    visited: &mut HashSet<std::path::PathBuf>,
+    files: &mut Vec<std::path::PathBuf>,
+    errors: &mut Vec<error::TestParseError>,
+) {
+    if !visited.insert(include_key(dir)) {
+        return;
+    }
+
+    let io_error = |source| {
+        error::TestParseError::from(error::TestErrorCase::Io {
+            path: dir.display().to_string(),
+            source,
+        })
+    };
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(source) => return errors.push(io_error(source)),
+    };
+
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(source) => {
+                errors.push(io_error(source));
+                continue;
+            }
+        };
+
+        let path = entry.path();
+
+        if path.is_dir() {
+            collect_files(&path, pattern, visited, files, errors);
+        } else if matches_pattern(pattern, &entry.file_name().to_string_lossy()) {
+            files.push(path);
+        }
+    }
+}
+
+/// Match `name` against a pattern where `*` matches any number of characters and `?` a single one
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    n = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Suggest the name out of `names` that is closest to `name`, if any is close enough
+fn did_you_mean<'a>(name: &str, names: impl IntoIterator<Item = &'a String>) -> Option<String> {
+    let threshold = (name.chars().count() / 3).max(1);
+
+    names
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min()
+        .map(|(_, candidate)| format!("Did you mean `{candidate}`?"))
+}
+
+/// The edit distance between `a` and `b`, where swapping two adjacent characters counts as one
+/// edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // distances[i][j] is the distance between the first i characters of a and j characters of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = substitution
+                .min(distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+
+            distances[i][j] = distance;
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
+
+/// Strip the module paths from a type name, `alloc::string::String` becomes `String`
+/// The arguments, arity and children cells of a row in [`TestDsl::to_markdown`]
+///
+/// Without a signature, only the type of the arguments is known.
+fn markdown_signature(signature: Option<&argument::Signature>, arguments_name: &str) -> String {
+    let Some(signature) = signature else {
+        return format!("`{}` |  | ", short_type_name(arguments_name));
+    };
+
+    let arguments = signature
+        .arguments()
+        .iter()
+        .map(|argument| format!("`{}`", short_type_name(argument)))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let total = signature.arguments().len();
+    let required = signature.required_arguments();
+    let arity = if required == total {
+        total.to_string()
+    } else {
+        format!("{required} to {total}")
+    };
+
+    let children = if signature.takes_children() {
+        "yes"
+    } else {
+        "no"
+    };
+
+    format!("{arguments} | {arity} | {children}")
+}
+
+fn short_type_name(name: &str) -> String {
+    let mut short = String::new();
+    let mut path = String::new();
+
+    for c in name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            path.push(c);
+            continue;
+        }
+
+        short.push_str(path.rsplit("::").next().unwrap_or_default());
+        path.clear();
+        short.push(c);
+    }
+
+    short.push_str(path.rsplit("::").next().unwrap_or_default());
+    short
+}
+
+#[derive(Debug, Clone)]
+struct AssertConditions;
+
+impl<H: 'static> Verb<H> for AssertConditions {
+    type Arguments = NowConditionChildren<H, ((),)>;
+    fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        for child in arguments.children() {
+            child.run(harness, context)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct AssertAllConditions;
+
+impl<H: 'static> Verb<H> for AssertAllConditions {
+    type Arguments = NowConditionChildren<H, ((),)>;
+    fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        let errors: Vec<_> = arguments
+            .children()
+            .iter()
+            .filter_map(|child| child.run(harness, context).err())
+            .collect();
+
+        if !errors.is_empty() {
+            return Err(error::ConditionsFailed::new(arguments.children().len(), errors).into());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct AssertFails;
+
+impl<H: 'static> Verb<H> for AssertFails {
+    type Arguments = VerbChildren<H, ((),)>;
+    fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        for child in arguments.children() {
+            match child.run(harness, context) {
+                Ok(()) => (),
+                Err(error) if error.is_control_flow() => return Err(error.into()),
+                Err(_) => return Ok(()),
+            }
+        }
+
+        Err(error::ExpectedFailure.into())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct If;
+
+impl<H: 'static> Verb<H> for If {
+    type Arguments = argument::IfArguments<H>;
+    fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        let verbs = if arguments.condition().check_now(harness, context)? {
+            arguments.then_verbs()
+        } else {
+            arguments.else_verbs()
+        };
+
+        for verb in verbs {
+            verb.run(harness, context)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct NotCondition;
+
+impl<H: 'static> condition::MutCondition<H> for NotCondition {
+    type Arguments = SingleConditionChild<H, ((),)>;
+    fn check_now_mut(
+        &self,
+        harness: &mut H,
+        context: &TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<bool> {
+        Ok(!arguments.child().check_now(harness, context)?)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct AllConditions;
+
+impl<H: 'static> condition::MutCondition<H> for AllConditions {
+    type Arguments = NowConditionChildren<H, ((),)>;
+    fn check_now_mut(
+        &self,
+        harness: &mut H,
+        context: &TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<bool> {
+        for child in arguments.children() {
+            if !child.check_now(harness, context)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct AnyCondition;
+
+impl<H: 'static> condition::MutCondition<H> for AnyCondition {
+    type Arguments = NowConditionChildren<H, ((),)>;
+    fn check_now_mut(
+        &self,
+        harness: &mut H,
+        context: &TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<bool> {
+        for child in arguments.children() {
+            if child.check_now(harness, context)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+named_parameters!(TemporalParameters {
+    timeout = std::time::Duration,
+    interval = std::time::Duration = WaitFor::DEFAULT_INTERVAL,
+});
+
+#[derive(Debug, Clone)]
+struct Eventually;
+
+impl<H: 'static> condition::MutCondition<H> for Eventually {
+    type Arguments = SingleConditionChild<H, TemporalParameters>;
+    fn check_now_mut(
+        &self,
+        harness: &mut H,
+        context: &TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<bool> {
+        let child = arguments.child();
+        let TemporalParameters { timeout, interval } = *arguments.parameters();
+        let deadline = std::time::Instant::now() + timeout;
+
+        loop {
+            if child.check_now(harness, context)? {
+                return Ok(true);
+            }
+
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+
+            if remaining.is_zero() {
+                return Ok(false);
+            }
+
+            std::thread::sleep(interval.min(remaining));
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Consistently;
+
+impl<H: 'static> condition::MutCondition<H> for Consistently {
+    type Arguments = SingleConditionChild<H, TemporalParameters>;
+    fn check_now_mut(
+        &self,
+        harness: &mut H,
+        context: &TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<bool> {
+        let child = arguments.child();
+        let TemporalParameters { timeout, interval } = *arguments.parameters();
+        let deadline = std::time::Instant::now() + timeout;
+
+        loop {
+            if !child.check_now(harness, context)? {
+                return Ok(false);
+            }
+
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+
+            if remaining.is_zero() {
+                return Ok(true);
+            }
+
+            std::thread::sleep(interval.min(remaining));
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Sleep;
+
+impl<H: 'static> Verb<H> for Sleep {
+    type Arguments = (std::time::Duration,);
+    fn run(
+        &self,
+        _harness: &mut H,
+        _context: &mut TestContext,
+        (duration,): &Self::Arguments,
+    ) -> miette::Result<()> {
+        std::thread::sleep(*duration);
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SkipRest;
+
+impl<H: 'static> Verb<H> for SkipRest {
+    type Arguments = ((),);
+    fn run(
+        &self,
+        _harness: &mut H,
+        _context: &mut TestContext,
+        _arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        Err(error::SkipRest.into())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SkipIf;
+
+impl<H: 'static> Verb<H> for SkipIf {
+    type Arguments = NowConditionChildren<H, (Option<String>,)>;
+    fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        for child in arguments.children() {
+            if !child.check_now(harness, context)? {
+                return Ok(());
+            }
+        }
+
+        let (reason,) = arguments.parameters();
+
+        Err(error::Skipped {
+            reason: reason.clone(),
+        }
+        .into())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Fail;
+
+impl<H: 'static> Verb<H> for Fail {
+    type Arguments = (Option<String>,);
+    fn run(
+        &self,
+        _harness: &mut H,
+        _context: &mut TestContext,
+        (message,): &Self::Arguments,
+    ) -> miette::Result<()> {
+        Err(miette::miette!(
+            "{}",
+            message.as_deref().unwrap_or("explicit fail")
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PrintArguments {
+    message: String,
+    span: miette::SourceSpan,
+}
+
+impl<H: 'static> argument::ParseArguments<H> for PrintArguments {
+    fn parse(test_dsl: &TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, TestErrorCase> {
+        let (message,) = <(String,) as argument::ParseArguments<H>>::parse(test_dsl, node)?;
+
+        Ok(PrintArguments {
+            message,
+            span: node.span(),
+        })
+    }
+
+    fn signature() -> Option<argument::Signature> {
+        <(String,) as argument::ParseArguments<H>>::signature()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Print;
+
+impl<H: 'static> Verb<H> for Print {
+    type Arguments = PrintArguments;
+    fn run(
+        &self,
+        _harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        let line = match context.location(arguments.span) {
+            Some(location) => format!("[{location}] {}", arguments.message),
+            None => arguments.message.clone(),
+        };
+
+        context
+            .print_line(&line)
+            .map_err(|error| miette::miette!("Could not write the message: {error}"))
+    }
+}
+
+named_parameters!(WaitForParameters {
+    timeout = Option<std::time::Duration>,
+    interval = Option<std::time::Duration>,
+});
+
+#[derive(Debug, Clone)]
+struct WaitFor;
+
+impl WaitFor {
+    const DEFAULT_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
+}
+
+impl<H: 'static> Verb<H> for WaitFor {
+    type Arguments = WaitConditionChildren<H, WaitForParameters>;
+    fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        let WaitForParameters { timeout, interval } = *arguments.parameters();
+        let interval = interval.unwrap_or(Self::DEFAULT_INTERVAL);
+        let deadline = timeout.map(|timeout| (std::time::Instant::now() + timeout, timeout));
+
+        for child in arguments.children() {
+            while !child.wait_until(harness, context)? {
+                let pause = match deadline {
+                    Some((deadline, timeout)) => {
+                        let remaining =
+                            deadline.saturating_duration_since(std::time::Instant::now());
+
+                        if remaining.is_zero() {
+                            return Err(TestError::Timeout {
+                                timeout,
+                                span: child.node.span(),
+                            }
+                            .into());
+                        }
+
+                        interval.min(remaining)
+                    }
+                    None => interval,
+                };
+
+                std::thread::sleep(pause);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl<H: 'static> verb::AsyncVerb<H> for WaitFor {
+    type Arguments = WaitConditionChildren<H, WaitForParameters>;
+    async fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        let WaitForParameters { timeout, interval } = *arguments.parameters();
+        let interval = interval.unwrap_or(Self::DEFAULT_INTERVAL);
+        let deadline = timeout.map(|timeout| std::time::Instant::now() + timeout);
+        let (harness, context) = (&*harness, &*context);
+
+        for child in arguments.children() {
+            let waited = timer::timeout(deadline, async {
+                while !child.wait_until_async(harness, context).await? {
+                    timer::sleep(interval).await;
+                }
+
+                Ok::<_, TestError>(())
+            })
+            .await;
+
+            match (waited, timeout) {
+                (Some(result), _) => result?,
+                (None, Some(timeout)) => {
+                    return Err(TestError::Timeout {
+                        timeout,
+                        span: child.node.span(),
+                    }
+                    .into());
+                }
+                (None, None) => unreachable!("Only a deadline can stop the wait"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Group;
+
+impl Group {
+    /// Attach the label of the group, if any, to the error of a child
+    fn label_error(label: &Option<String>, error: TestError) -> miette::Error {
+        match label {
+            Some(label) if !error.is_control_flow() => {
+                error::GroupError::new(label.clone(), error).into()
+            }
+            _ => error.into(),
+        }
+    }
+}
+
+impl<H: 'static> Verb<H> for Group {
+    type Arguments = VerbChildren<H, (Option<String>,)>;
+    fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        let (label,) = arguments.parameters();
+
+        for child in arguments.children() {
+            child
+                .run(harness, context)
+                .map_err(|error| Self::label_error(label, error))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl<H: 'static> verb::AsyncVerb<H> for Group {
+    type Arguments = VerbChildren<H, (Option<String>,)>;
+    async fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        let (label,) = arguments.parameters();
+
+        for child in arguments.children() {
+            child
+                .run_async(harness, context)
+                .await
+                .map_err(|error| Self::label_error(label, error))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Repeat;
+
+impl<H: 'static> Verb<H> for Repeat {
+    type Arguments = VerbChildren<H, (usize,)>;
+    fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        let (times,) = *arguments.parameters();
+
+        for _ in 0..times {
+            for child in arguments.children() {
+                child.run(harness, context)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl<H: 'static> verb::AsyncVerb<H> for Repeat {
+    type Arguments = VerbChildren<H, (usize,)>;
+    async fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        let (times,) = *arguments.parameters();
+
+        for _ in 0..times {
+            for child in arguments.children() {
+                child.run_async(harness, context).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RepeatUntil;
+
+impl<H: 'static> Verb<H> for RepeatUntil {
+    type Arguments = argument::RepeatUntilArguments<H>;
+    fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        for _ in 0..arguments.max() {
+            for verb in arguments.verbs() {
+                verb.run(harness, context)?;
+            }
+
+            if arguments.condition().check_now(harness, context)? {
+                return Ok(());
+            }
+        }
+
+        Err(TestError::IterationLimitReached {
+            max: arguments.max(),
+            span: arguments.span(),
+        }
+        .into())
+    }
+}
+
+/// An instance of a [`Condition`](condition::Condition)
+pub struct ConditionInstance<H> {
+    _pd: PhantomData<fn(H)>,
+    condition: ErasedCondition<H>,
+    arguments: Box<dyn BoxedArguments<H>>,
+    node: kdl::KdlNode,
+    catch_panics: bool,
+}
+
+impl<H: 'static> Clone for ConditionInstance<H> {
+    fn clone(&self) -> Self {
+        Self {
+            _pd: self._pd,
+            condition: self.condition.clone(),
+            arguments: self.arguments.clone(),
+            node: self.node.clone(),
+            catch_panics: self.catch_panics,
+        }
+    }
+}
+
+impl<H> std::fmt::Debug for ConditionInstance<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConditionInstance")
+            .field("_pd", &self._pd)
+            .field("condition", &self.condition)
+            .field("arguments", &self.arguments)
+            .field("node", &self.node)
+            .field("catch_panics", &self.catch_panics)
+            .finish()
+    }
+}
+
+impl<H: 'static> ConditionInstance<H> {
+    /// Create a new instance with the given node and [`TestDsl`]
+    pub fn with_test_dsl(
+        test_dsl: &TestDsl<H>,
+        node: &kdl::KdlNode,
+    ) -> Result<Self, TestErrorCase> {
+        let condition = test_dsl.get_condition_for_node(node)?;
+
+        let arguments = condition.parse_args(test_dsl, node)?;
+
+        Ok(ConditionInstance {
+            _pd: PhantomData,
+            condition,
+            arguments,
+            node: node.clone(),
+            catch_panics: test_dsl.catch_panics,
+        })
+    }
+
+    /// The KDL node this condition was parsed from
+    pub fn node(&self) -> &kdl::KdlNode {
+        &self.node
+    }
+
+    /// Whether this condition can be checked now, for example in an `assert`
+    ///
+    /// See [`Condition::supports_now`](condition::Condition::supports_now).
+    pub fn supports_now(&self) -> bool {
+        self.condition.supports_now()
+    }
+
+    /// Whether this condition can be waited for, for example in a `wait_for`
+    ///
+    /// See [`Condition::supports_wait`](condition::Condition::supports_wait).
+    pub fn supports_wait(&self) -> bool {
+        self.condition.supports_wait()
+    }
+
+    /// Validate the arguments of the condition and of all nodes nested in it, without running
+    /// anything
+    ///
+    /// See [`ParseArguments::validate`](argument::ParseArguments::validate).
+    pub fn validate(&self) -> Result<(), TestError> {
+        self.arguments
+            .validate_box()
+            .map_err(|error| validation_error(error, self.node.span()))
+    }
+
+    pub(crate) fn ensure_now(&self) -> Result<(), TestErrorCase> {
+        if self.supports_now() {
+            return Ok(());
+        }
+
+        Err(TestErrorCase::UnsupportedCondition {
+            condition: self.node.span(),
+            usage: "checked now",
+        })
+    }
+
+    pub(crate) fn ensure_wait(&self) -> Result<(), TestErrorCase> {
+        if self.supports_wait() {
+            return Ok(());
+        }
+
+        Err(TestErrorCase::UnsupportedCondition {
+            condition: self.node.span(),
+            usage: "waited for",
+        })
+    }
+
+    /// Run the condition
+    ///
+    /// This returns an error if:
+    /// - The condition returns [`Ok(false)`](Ok)
+    /// - It returns an [`Err`]
+    /// - It [`panic`]s
+    pub fn run(&self, harness: &mut H, context: &TestContext) -> Result<(), TestError> {
+        self.evaluate(context, || {
+            self.condition
+                .check_now(harness, context, self.arguments.as_dyn_any())
+        })
+    }
+
+    /// Wait for the condition
+    ///
+    /// This evaluates the condition through
+    /// [`Condition::wait_until`](condition::Condition::wait_until) instead of checking it now.
+    /// Like [`run`](Self::run), this returns an error if:
+    /// - The condition returns [`Ok(false)`](Ok)
+    /// - It returns an [`Err`]
+    /// - It [`panic`]s
+    pub fn wait(&self, harness: &H, context: &TestContext) -> Result<(), TestError> {
+        self.evaluate(context, || {
+            self.condition
+                .wait_until(harness, context, self.arguments.as_dyn_any())
+        })
+    }
+
+    fn evaluate(
+        &self,
+        context: &TestContext,
+        check: impl FnOnce() -> miette::Result<bool>,
+    ) -> Result<(), TestError> {
+        let trace = context.trace_enter(false, &self.node);
+        #[cfg(feature = "tracing")]
+        let _span = instrument::enter(false, &self.node);
+
+        context.take_failure_note();
+
+        let res = if self.catch_panics {
+            panic::catch(check)
+        } else {
+            Ok(check())
+        };
+
+        let note = context.take_failure_note();
+
+        let result = match res {
+            Ok(Ok(true)) => Ok(()),
+            Ok(Ok(false)) => Err(TestError::ConditionFailed {
+                span: self.node.span(),
+                note,
+            }),
+            Ok(Err(error)) => Err(TestError::Error {
+                error,
+                span: self.node.span(),
+                output: None,
+            }),
+            Err(error) => Err(TestError::Panic {
+                error: error.into(),
+                span: self.node.span(),
+                output: None,
+            }),
+        };
+
+        #[cfg(feature = "tracing")]
+        instrument::record_error(result.as_ref().copied());
+        context.trace_exit(trace, result.as_ref().map(|()| true));
+
+        result
+    }
+
+    pub(crate) fn check_now(
+        &self,
+        harness: &mut H,
+        context: &TestContext,
+    ) -> Result<bool, TestError> {
+        let trace = context.trace_enter(false, &self.node);
+        #[cfg(feature = "tracing")]
+        let _span = instrument::enter(false, &self.node);
+
+        let result = self
+            .condition
+            .check_now(harness, context, self.arguments.as_dyn_any())
+            .map_err(|error| TestError::Error {
+                error,
+                span: self.node.span(),
+                output: None,
+            });
+
+        #[cfg(feature = "tracing")]
+        instrument::record_error(result.as_ref().map(|_| ()));
+        context.trace_exit(trace, result.as_ref().copied());
+
+        result
+    }
+
+    /// Await the condition, like [`wait_until`](Self::wait_until) does synchronously
+    #[cfg(feature = "async")]
+    pub(crate) async fn wait_until_async(
+        &self,
+        harness: &H,
+        context: &TestContext,
+    ) -> Result<bool, TestError> {
+        let wait = async {
+            let trace = context.trace_enter(false, &self.node);
+
+            let result = self
+                .condition
+                .wait_until_async(harness, context, self.arguments.as_dyn_any())
+                .await
+                .map_err(|error| TestError::Error {
+                    error,
+                    span: self.node.span(),
+                    output: None,
+                });
+
+            #[cfg(feature = "tracing")]
+            instrument::record_error(result.as_ref().map(|_| ()));
+            context.trace_exit(trace, result.as_ref().copied());
+
+            result
+        };
+
+        #[cfg(feature = "tracing")]
+        let wait = tracing::Instrument::instrument(wait, instrument::span(false, &self.node));
+
+        wait.await
+    }
+
+    pub(crate) fn wait_until(&self, harness: &H, context: &TestContext) -> Result<bool, TestError> {
+        let trace = context.trace_enter(false, &self.node);
+        #[cfg(feature = "tracing")]
+        let _span = instrument::enter(false, &self.node);
+
+        let result = self
+            .condition
+            .wait_until(harness, context, self.arguments.as_dyn_any())
+            .map_err(|error| TestError::Error {
+                error,
+                span: self.node.span(),
+                output: None,
+            });
+
+        #[cfg(feature = "tracing")]
+        instrument::record_error(result.as_ref().map(|_| ()));
+        context.trace_exit(trace, result.as_ref().copied());
+
+        result
+    }
+}
+
+/// An instance of a [`Verb`]
+pub struct VerbInstance<H> {
+    _pd: PhantomData<fn(H)>,
+    verb: ErasedVerb<H>,
+    arguments: Box<dyn BoxedArguments<H>>,
+    node: kdl::KdlNode,
+    catch_panics: bool,
+    capture_output: bool,
+}
+
+impl<H> std::fmt::Debug for VerbInstance<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VerbInstance")
+            .field("_pd", &self._pd)
+            .field("verb", &self.verb)
+            .field("arguments", &self.arguments)
+            .field("node", &self.node)
+            .field("catch_panics", &self.catch_panics)
+            .field("capture_output", &self.capture_output)
+            .finish()
+    }
+}
+
+impl<H: 'static> Clone for VerbInstance<H> {
+    fn clone(&self) -> Self {
+        Self {
+            _pd: self._pd,
+            verb: self.verb.clone(),
+            arguments: self.arguments.clone(),
+            node: self.node.clone(),
+            catch_panics: self.catch_panics,
+            capture_output: self.capture_output,
+        }
+    }
+}
+
+impl<H: 'static> VerbInstance<H> {
+    /// Create a new instance with the given node and [`TestDsl`]
+    pub fn with_test_dsl(
+        test_dsl: &TestDsl<H>,
+        node: &kdl::KdlNode,
+    ) -> Result<Self, TestErrorCase> {
+        let verb = test_dsl.get_verb_for_node(node)?;
+
+        let arguments = verb.parse_args(test_dsl, node)?;
+
+        Ok(VerbInstance {
+            _pd: PhantomData,
+            verb,
+            arguments,
+            node: node.clone(),
+            catch_panics: test_dsl.catch_panics,
+            capture_output: test_dsl.capture_output,
+        })
+    }
+
+    /// The KDL node this verb was parsed from
+    ///
+    /// Variables in its string arguments are already interpolated.
+    pub fn node(&self) -> &kdl::KdlNode {
+        &self.node
+    }
+
+    /// Validate the arguments of the verb and of all nodes nested in it, without running
+    /// anything
+    ///
+    /// See [`ParseArguments::validate`](argument::ParseArguments::validate).
+    pub fn validate(&self) -> Result<(), TestError> {
+        self.arguments
+            .validate_box()
+            .map_err(|error| validation_error(error, self.node.span()))
+    }
+
+    /// Run the verb
+    ///
+    /// This returns an error if:
+    /// - It returns an [`Err`]
+    /// - It [`panic`]s
+    pub fn run(&self, harness: &mut H, context: &mut TestContext) -> Result<(), TestError> {
+        #[cfg(feature = "tracing")]
+        let _span = instrument::enter(true, &self.node);
+        let started = self.start(context);
+
+        let mut run = || self.verb.run(harness, context, self.arguments.as_dyn_any());
+
+        let res = if self.catch_panics {
+            panic::catch(run)
+        } else {
+            Ok(run())
+        };
+
+        self.finish(context, started, res)
+    }
+
+    /// Run the verb asynchronously
+    ///
+    /// Contrary to [`run`](Self::run), [`AsyncVerb`](verb::AsyncVerb)s are awaited. Other verbs
+    /// run synchronously as usual. This is only available with the `async` feature.
+    #[cfg(feature = "async")]
+    pub fn run_async<'a>(
+        &'a self,
+        harness: &'a mut H,
+        context: &'a mut TestContext,
+    ) -> std::pin::Pin<Box<dyn Future<Output = Result<(), TestError>> + 'a>> {
+        let run = async move {
+            let started = self.start(context);
+
+            let run = self
+                .verb
+                .run_async(harness, context, self.arguments.as_dyn_any());
+
+            let res = if self.catch_panics {
+                panic::catch_future(run).await
+            } else {
+                Ok(run.await)
+            };
+
+            self.finish(context, started, res)
+        };
+
+        #[cfg(feature = "tracing")]
+        let run = tracing::Instrument::instrument(run, instrument::span(true, &self.node));
+
+        Box::pin(run)
+    }
+
+    /// Tell the context that this verb starts, returning what has to be passed to
+    /// [`finish`](Self::finish)
+    fn start(&self, context: &mut TestContext) -> (usize, (usize, usize)) {
+        let trace = context.trace_enter(true, &self.node);
+        context.enter_verb();
+        context.observe_start(&self.node);
+
+        (trace, context.output_start())
+    }
+
+    /// Turn the result of running the verb into a [`TestError`], and tell the context about it
+    fn finish(
+        &self,
+        context: &mut TestContext,
+        (trace, output_start): (usize, (usize, usize)),
+        res: Result<miette::Result<()>, error::PanicError>,
+    ) -> Result<(), TestError> {
+        context.exit_verb();
+        let output = context.take_output(output_start, self.capture_output);
+
+        let result = match res {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(error)) => Err(
+                match control_flow(&error, self.node.span()).or_else(|| limit_reached(&error)) {
+                    Some(control_flow) => control_flow,
+                    None => TestError::Error {
+                        error,
+                        span: self.node.span(),
+                        output,
+                    },
+                },
+            ),
+            Err(error) => Err(TestError::Panic {
+                error: error.into(),
+                span: self.node.span(),
+                output,
+            }),
+        };
+
+        #[cfg(feature = "tracing")]
+        instrument::record_error(result.as_ref().copied());
+        context.observe_end(&self.node, result.as_ref().copied());
+        context.trace_exit(trace, result.as_ref().map(|()| true));
+
+        result
+    }
+}
+
+/// The control flow signal in the error returned by the verb at `span`, if any
+///
+/// This is either a [`SkipRest`](error::SkipRest) or [`Skipped`](error::Skipped) returned by the
+/// verb itself, or the signal passed on from one of its children.
+fn control_flow(error: &miette::Error, span: miette::SourceSpan) -> Option<TestError> {
+    if error.is::<error::SkipRest>() {
+        return Some(TestError::SkipRest { span });
+    }
+
+    if let Some(error::Skipped { reason }) = error.downcast_ref() {
+        return Some(TestError::Skipped {
+            span,
+            reason: reason.clone(),
+        });
+    }
+
+    match error.downcast_ref()? {
+        TestError::SkipRest { span } => Some(TestError::SkipRest { span: *span }),
+        TestError::Skipped { span, reason } => Some(TestError::Skipped {
+            span: *span,
+            reason: reason.clone(),
+        }),
+        _ => None,
+    }
+}
+
+/// The [`Timeout`](TestError::Timeout) or
+/// [`IterationLimitReached`](TestError::IterationLimitReached) in the error returned by a verb,
+/// if any
+///
+/// They already point at the node that gave up, so they are passed on as they are instead of
+/// being wrapped in a [`TestError::Error`].
+fn limit_reached(error: &miette::Error) -> Option<TestError> {
+    match error.downcast_ref()? {
+        TestError::Timeout { timeout, span } => Some(TestError::Timeout {
+            timeout: *timeout,
+            span: *span,
+        }),
+        TestError::IterationLimitReached { max, span } => Some(TestError::IterationLimitReached {
+            max: *max,
+            span: *span,
+        }),
+        _ => None,
+    }
+}
+
+/// Turn an error of [`ParseArguments::validate`](argument::ParseArguments::validate) into a
+/// [`TestError`], keeping the errors of nested nodes as they are
+fn validation_error(error: miette::Error, span: miette::SourceSpan) -> TestError {
+    match error.downcast::<TestError>() {
+        Ok(error) => error,
+        Err(error) => TestError::Error {
+            error,
+            span,
+            output: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::cell::RefCell;
+    use std::ops::ControlFlow;
+    use std::rc::Rc;
+    use std::sync::atomic::AtomicUsize;
+
+    use crate::MergeStrategy;
+    use crate::TestCaseInput;
+    use crate::TestDsl;
+    use crate::argument::ConditionThenVerbs;
+    use crate::argument::RawChildren;
+    use crate::argument::Rest;
+    use crate::argument::TypeAnnotation;
+    use crate::argument::Typed;
+    use crate::argument::TypedChildren;
+    use crate::argument::ValueChildren;
+    use crate::condition::Condition;
+    use crate::condition::FunctionCondition;
+    use crate::condition::FunctionMutCondition;
+    use crate::condition::MutCondition;
+    use crate::context::TestContext;
+    use crate::error::PanicError;
+    use crate::error::TestError;
+    use crate::error::TestErrorCase;
+    use crate::matches_pattern;
+    use crate::observer::RunObserver;
+    use crate::observer::VerbEvent;
+    use crate::test_case::run_all;
+    use crate::test_case::run_all_with;
+    use crate::test_case::select;
+    use crate::verb::FunctionVerb;
+    use crate::verb::Verb;
+
+    struct ArithmeticHarness {
+        value: AtomicUsize,
+    }
+
+    #[test]
+    fn simple_test() {
+        let mut ts = TestDsl::<ArithmeticHarness>::new();
+        ts.add_verb(
+            "add_one",
+            FunctionVerb::new(|ah: &mut ArithmeticHarness| {
+                ah.value.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+
+                Ok(())
+            }),
+        );
+
+        ts.add_verb(
+            "mul_two",
+            FunctionVerb::new(|ah: &mut ArithmeticHarness| {
+                let value = ah.value.load(std::sync::atomic::Ordering::SeqCst);
+                ah.value
+                    .store(value * 2, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            }),
+        );
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                add_one
+                add_one
+                mul_two
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut ah = ArithmeticHarness {
+            value: AtomicUsize::new(0),
+        };
+
+        tc[0].run(&mut ah).unwrap();
+
+        assert_eq!(ah.value.load(std::sync::atomic::Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn repeat_test() {
+        let mut ts = TestDsl::<ArithmeticHarness>::new();
+        ts.add_verb(
+            "add_one",
+            FunctionVerb::new(|ah: &mut ArithmeticHarness| {
+                ah.value.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+
+                Ok(())
+            }),
+        );
+
+        ts.add_verb(
+            "mul_two",
+            FunctionVerb::new(|ah: &mut ArithmeticHarness| {
+                let value = ah.value.load(std::sync::atomic::Ordering::SeqCst);
+                ah.value
+                    .store(value * 2, std::sync::atomic::Ordering::SeqCst);
+
+                Ok(())
+            }),
+        );
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                repeat 2 {
+                    repeat 2 {
+                        add_one
+                        mul_two
+                    }
+                }
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut ah = ArithmeticHarness {
+            value: AtomicUsize::new(0),
+        };
+
+        tc[0].run(&mut ah).unwrap();
+
+        assert_eq!(ah.value.load(std::sync::atomic::Ordering::SeqCst), 30);
+    }
+
+    #[test]
+    fn check_arguments_work() {
+        let mut ts = TestDsl::<ArithmeticHarness>::new();
+        ts.add_verb(
+            "add_one",
+            FunctionVerb::new(|ah: &mut ArithmeticHarness| {
+                ah.value.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+
+                Ok(())
+            }),
+        );
+
+        ts.add_verb(
+            "add",
+            FunctionVerb::new(|ah: &mut ArithmeticHarness, num: usize| {
+                ah.value.fetch_add(num, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            }),
+        );
+
+        ts.add_verb(
+            "mul_two",
+            FunctionVerb::new(|ah: &mut ArithmeticHarness| {
+                let value = ah.value.load(std::sync::atomic::Ordering::SeqCst);
+                ah.value
+                    .store(value * 2, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            }),
+        );
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                repeat 2 {
+                    repeat 2 {
+                        group {
+                            add 2
+                            mul_two
+                        }
+                    }
+                }
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut ah = ArithmeticHarness {
+            value: AtomicUsize::new(0),
+        };
+
+        tc[0].run(&mut ah).unwrap();
+
+        assert_eq!(ah.value.load(std::sync::atomic::Ordering::SeqCst), 60);
+    }
+
+    #[derive(Debug, Clone)]
+    struct Roll;
+
+    impl Verb<Vec<u64>> for Roll {
+        type Arguments = ((),);
+        fn run(
+            &self,
+            harness: &mut Vec<u64>,
+            context: &mut TestContext,
+            _arguments: &Self::Arguments,
+        ) -> miette::Result<()> {
+            harness.push(context.next_u64());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+        let mut ts = TestDsl::<Vec<u64>>::new();
+        ts.add_verb("roll", Roll);
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                repeat 3 {
+                    roll
+                }
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut first = vec![];
+        let report = tc[0].run_with_seed(&mut first, 42).unwrap();
+        assert_eq!(report.seed(), 42);
+
+        let mut second = vec![];
+        tc[0].run_with_seed(&mut second, 42).unwrap();
+        assert_eq!(first, second);
+
+        let mut other = vec![];
+        tc[0].run_with_seed(&mut other, 43).unwrap();
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn check_run_report_timing() {
+        let ts = TestDsl::<()>::new();
+
+        let tc = ts
+            .parse_testcase(r#"testcase { sleep "20ms"; group; skip_rest; group }"#)
+            .unwrap();
+
+        let report = tc[0].run_with_seed(&mut (), 42).unwrap();
+        let names = report
+            .per_verb()
+            .iter()
+            .map(|verb| verb.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["sleep", "group", "skip_rest"]);
+
+        let sleep = &report.per_verb()[0];
+        assert!(sleep.elapsed() >= std::time::Duration::from_millis(20));
+        assert_eq!(sleep.span().offset(), 11);
+
+        let sum = report
+            .per_verb()
+            .iter()
+            .map(|verb| verb.elapsed())
+            .sum::<std::time::Duration>();
+        assert!(report.total() >= sum);
+
+        let tc = ts
+            .parse_testcase(r#"testcase { sleep "20ms"; fail; group }"#)
+            .unwrap();
+
+        let outcome = tc[0].run_outcome(&mut ());
+        assert!(outcome.is_failed());
+
+        let report = outcome.report();
+        assert_eq!(report.per_verb().len(), 2);
+        assert_eq!(report.per_verb()[1].name(), "fail");
+        assert!(report.total() >= std::time::Duration::from_millis(20));
+    }
+
+    #[test]
+    fn check_verb_seeds() {
+        #[derive(Debug, Clone)]
+        struct VerbSeed;
+
+        impl Verb<Vec<u64>> for VerbSeed {
+            type Arguments = ((),);
+            fn run(
+                &self,
+                harness: &mut Vec<u64>,
+                context: &mut TestContext,
+                _arguments: &Self::Arguments,
+            ) -> miette::Result<()> {
+                harness.push(context.verb_seed());
+                Ok(())
+            }
+        }
+
+        let mut ts = TestDsl::<Vec<u64>>::new();
+        ts.add_verb("roll", Roll);
+        ts.add_verb("verb_seed", VerbSeed);
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                verb_seed
+                verb_seed
+            }
+            testcase {
+                roll
+                verb_seed
+            }
+            testcase {
+                verb_seed
+                fail
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut first = vec![];
+        tc[0].run_with_seed(&mut first, 42).unwrap();
+        assert_ne!(first[0], first[1]);
+
+        let mut rolled = vec![];
+        tc[1].run_with_seed(&mut rolled, 42).unwrap();
+        assert_eq!(rolled[1], first[1]);
+
+        let error = tc[2].run_with_seed(&mut vec![], 7).unwrap_err();
+        assert_eq!(error.seed(), 7);
+    }
+
+    #[test]
+    fn check_signed_arguments() {
+        let mut ts = TestDsl::<i32>::new();
+        ts.add_verb(
+            "add",
+            FunctionVerb::new(|h: &mut i32, num: i32| {
+                *h += num;
+                Ok(())
+            }),
+        );
+        ts.add_verb("add_small", FunctionVerb::new(|_: &mut i32, _: i8| Ok(())));
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                add 10
+                add -5
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut value = 0;
+        tc[0].run(&mut value).unwrap();
+        assert_eq!(value, 5);
+
+        let tc = ts.parse_testcase(
+            r#"
+            testcase {
+                add_small 5000000000
+            }
+            "#,
+        );
+
+        assert!(tc.is_err());
+    }
+
+    #[derive(Debug, Clone)]
+    struct When;
+
+    impl Verb<i32> for When {
+        type Arguments = ConditionThenVerbs<i32, ((),)>;
+        fn run(
+            &self,
+            harness: &mut i32,
+            context: &mut TestContext,
+            arguments: &Self::Arguments,
+        ) -> miette::Result<()> {
+            if arguments.condition().run(harness, context).is_ok() {
+                for verb in arguments.verbs() {
+                    verb.run(harness, context)?;
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn check_condition_then_verbs() {
+        let mut ts = TestDsl::<i32>::new();
+        ts.add_verb("when", When);
+        ts.add_verb(
+            "add",
+            FunctionVerb::new(|h: &mut i32, num: i32| {
+                *h += num;
+                Ok(())
+            }),
+        );
+        ts.add_condition("is_zero", FunctionCondition::new_now(|h: &i32| Ok(*h == 0)));
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                when {
+                    is_zero
+                    add 2
+                    add 3
+                }
+                when {
+                    is_zero
+                    add 100
+                }
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut value = 0;
+        tc[0].run(&mut value).unwrap();
+        assert_eq!(value, 5);
+
+        let error = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                when {
+                    add 2
+                }
+            }
+            "#,
+            )
+            .unwrap_err();
+
+        assert!(matches!(
+            error.errors[..],
+            [TestErrorCase::ExpectedCondition { .. }]
+        ));
+    }
+
+    #[test]
+    fn check_f32_arguments() {
+        let mut ts = TestDsl::<f32>::new();
+        ts.add_verb(
+            "scale",
+            FunctionVerb::new(|h: &mut f32, x: f32| {
+                *h *= x;
+                Ok(())
+            }),
+        );
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                scale 1.5
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut value = 2.0;
+        tc[0].run(&mut value).unwrap();
+        assert_eq!(value, 3.0);
+
+        let tc = ts.parse_testcase(
+            r#"
+            testcase {
+                scale 1.0e300
+            }
+            "#,
+        );
+
+        assert!(tc.is_err());
+    }
+
+    #[test]
+    fn check_streaming_parse() {
+        let ts = TestDsl::<()>::new();
+
+        let input = r#"
+            testcase {
+                group {}
+            }
+            testcase {
+                not_found
+                not_found_either
+            }
+            testcase {
+                group {}
+            }
+        "#;
+
+        let mut cases = 0;
+        let mut errors = 0;
+        ts.parse_testcase_streaming(input, |result| {
+            match result {
+                Ok(_) => cases += 1,
+                Err(_) => errors += 1,
+            }
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+
+        assert_eq!((cases, errors), (2, 2));
+
+        let mut seen = 0;
+        ts.parse_testcase_streaming(input, |result| {
+            seen += 1;
+            if result.is_err() {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .unwrap();
+
+        assert_eq!(seen, 2);
+    }
+
+    #[test]
+    fn check_char_arguments() {
+        let mut ts = TestDsl::<String>::new();
+        ts.add_verb(
+            "press",
+            FunctionVerb::new(|h: &mut String, c: char| {
+                h.push(c);
+                Ok(())
+            }),
+        );
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                press x
+                press "ü"
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut typed = String::new();
+        tc[0].run(&mut typed).unwrap();
+        assert_eq!(typed, "xü");
+
+        assert!(ts.parse_testcase(r#"testcase { press "" }"#).is_err());
+        assert!(ts.parse_testcase(r#"testcase { press "xy" }"#).is_err());
+    }
+
+    #[test]
+    fn check_bool_arguments() {
+        let mut ts = TestDsl::<Vec<bool>>::new();
+        ts.add_verb(
+            "enabled",
+            FunctionVerb::new(|h: &mut Vec<bool>, enabled: bool| {
+                h.push(enabled);
+                Ok(())
+            }),
+        );
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                enabled #true
+                enabled #false
+                enabled "yes"
+                enabled NO
+                enabled on
+                enabled "Off"
+                enabled true
+                enabled "FALSE"
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut values = vec![];
+        tc[0].run(&mut values).unwrap();
+        assert_eq!(values, [true, false, true, false, true, false, true, false]);
+
+        assert!(
+            ts.parse_testcase(r#"testcase { enabled "maybe" }"#)
+                .is_err()
+        );
+        assert!(ts.parse_testcase("testcase { enabled 1 }").is_err());
+    }
+
+    #[derive(Debug, Clone)]
+    struct CanPush;
+
+    impl MutCondition<Vec<usize>> for CanPush {
+        type Arguments = ((),);
+        fn check_now_mut(
+            &self,
+            harness: &mut Vec<usize>,
+            _context: &TestContext,
+            _arguments: &Self::Arguments,
+        ) -> miette::Result<bool> {
+            harness.push(0);
+            let pushed = harness.pop().is_some();
+            Ok(pushed)
+        }
+    }
+
+    #[test]
+    fn check_mut_conditions() {
+        let mut ts = TestDsl::<Vec<usize>>::new();
+        ts.add_mut_condition("can_push", CanPush);
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                assert {
+                    can_push
+                }
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut stack = vec![1, 2];
+        tc[0].run(&mut stack).unwrap();
+        assert_eq!(stack, [1, 2]);
+    }
+
+    #[test]
+    fn check_optional_arguments() {
+        let mut ts = TestDsl::<Vec<(usize, Option<String>)>>::new();
+        ts.add_verb(
+            "click",
+            FunctionVerb::new(
+                |h: &mut Vec<(usize, Option<String>)>, x: usize, button: Option<String>| {
+                    h.push((x, button));
+                    Ok(())
+                },
+            ),
+        );
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                click 3
+                click 3 left
+                click 3 #null
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut clicks = vec![];
+        tc[0].run(&mut clicks).unwrap();
+        assert_eq!(
+            clicks,
+            [(3, None), (3, Some(String::from("left"))), (3, None)]
+        );
+
+        assert!(ts.parse_testcase("testcase { click }").is_err());
+
+        let error = ts.parse_testcase("testcase { click #null }").unwrap_err();
+        assert!(matches!(
+            error.errors[..],
+            [TestErrorCase::NullArgument { .. }]
+        ));
+    }
+
+    #[test]
+    fn check_rest_arguments() {
+        let mut ts = TestDsl::<Vec<(String, Vec<usize>)>>::new();
+        ts.add_verb(
+            "sum",
+            FunctionVerb::new(
+                |h: &mut Vec<(String, Vec<usize>)>, name: String, Rest(rest): Rest<usize>| {
+                    h.push((name, rest));
+                    Ok(())
+                },
+            ),
+        );
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                sum first 1 2 3
+                sum second
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut sums = vec![];
+        tc[0].run(&mut sums).unwrap();
+        assert_eq!(
+            sums,
+            [
+                (String::from("first"), vec![1, 2, 3]),
+                (String::from("second"), vec![])
+            ]
+        );
+
+        assert!(ts.parse_testcase("testcase { sum first 1 two 3 }").is_err());
+    }
+
+    #[derive(Debug, Clone)]
+    struct LessThan;
+
+    impl Condition<u64> for LessThan {
+        type Arguments = (String, u64);
+
+        fn check_now(
+            &self,
+            _harness: &u64,
+            context: &TestContext,
+            (name, limit): &Self::Arguments,
+        ) -> miette::Result<bool> {
+            let value = context
+                .get::<u64>(name)
+                .ok_or_else(|| miette::miette!("No value named {name} was captured"))?;
+
+            Ok(value < limit)
+        }
+
+        fn wait_until(
+            &self,
+            harness: &u64,
+            context: &TestContext,
+            arguments: &Self::Arguments,
+        ) -> miette::Result<bool> {
+            self.check_now(harness, context, arguments)
+        }
+    }
+
+    #[test]
+    fn check_producers() {
+        let mut ts = TestDsl::<u64>::new();
+        ts.add_producer("latency", |h: &u64| Ok(*h));
+        ts.add_condition("lt", LessThan);
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                measure latency capture=l
+                assert {
+                    lt l 100
+                }
+            }
+            "#,
+            )
+            .unwrap();
+
+        tc[0].run(&mut 50).unwrap();
+        assert!(tc[0].run(&mut 150).is_err());
+
+        assert!(ts.parse_testcase("testcase { measure latency }").is_err());
+        assert!(
+            ts.parse_testcase("testcase { measure unknown capture=l }")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn check_run_selected() {
+        let mut ts = TestDsl::<usize>::new();
+        ts.add_verb(
+            "add",
+            FunctionVerb::new(|h: &mut usize, n: usize| {
+                *h += n;
+                Ok(())
+            }),
+        );
+        ts.add_condition(
+            "is",
+            FunctionCondition::new_now(|h: &usize, n: usize| Ok(*h == n)),
+        );
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                add 1
+                assert { is 1 }
+            }
+            testcase {
+                add 2
+                assert { is 3 }
+            }
+            testcase {
+                add 3
+                assert { is 3 }
+            }
+            "#,
+            )
+            .unwrap();
+
+        let results = ts.run_selected(&tc, || 0, [1, 2, 5]);
+        let indices = results.iter().map(|(index, _)| *index).collect::<Vec<_>>();
+        assert_eq!(indices, [1, 2]);
+
+        let failed = results
+            .iter()
+            .filter(|(_, outcome)| outcome.is_failed())
+            .map(|(index, _)| *index)
+            .collect::<Vec<_>>();
+        assert_eq!(failed, [1]);
+
+        let rerun = ts.run_selected(&tc, || 1, failed);
+        assert!(rerun[0].1.is_passed());
+    }
+
+    #[test]
+    fn check_run_parallel() {
+        let make_dsl = || {
+            let mut ts = TestDsl::<usize>::new();
+            ts.add_verb(
+                "add",
+                FunctionVerb::new(|h: &mut usize, n: usize| {
+                    *h += n;
+                    Ok(())
+                }),
+            );
+            ts.add_condition(
+                "is",
+                FunctionCondition::new_now(|h: &usize, n: usize| Ok(*h == n)),
+            );
+            ts
+        };
+
+        let input = r#"
+            testcase "slow" {
+                sleep "50ms"
+                add 1
+                assert { is 1 }
+            }
+            testcase "wrong" {
+                add 2
+                assert { is 3 }
+            }
+            testcase "fast" {
+                add 3
+                assert { is 3 }
+            }
+            testcase
+            "#;
+
+        let harnesses = AtomicUsize::new(0);
+        let results = TestDsl::run_parallel(
+            make_dsl,
+            input,
+            || {
+                harnesses.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                0
+            },
+            3,
+        )
+        .unwrap();
+
+        let names = results
+            .iter()
+            .map(|(name, _)| name.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(names, [Some("slow"), Some("wrong"), Some("fast"), None]);
+
+        let passed = results
+            .iter()
+            .map(|(_, outcome)| outcome.is_passed())
+            .collect::<Vec<_>>();
+        assert_eq!(passed, [true, false, true, true]);
+        assert_eq!(harnesses.into_inner(), 4);
+
+        assert!(TestDsl::run_parallel(make_dsl, "testcase { unknown }", || 0, 0).is_err());
+        assert!(
+            TestDsl::run_parallel(make_dsl, "", || 0, 0)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn check_run_parallel_inconsistent_dsl() {
+        // Only the first call returns a `TestDsl` that parses both testcases
+        let calls = AtomicUsize::new(0);
+        let make_dsl = || {
+            let mut ts = TestDsl::<()>::new();
+            if calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) > 0 {
+                ts.add_top_level_node("testcase");
+            }
+            ts
+        };
+
+        let error =
+            TestDsl::run_parallel(make_dsl, "testcase {}\ntestcase {}", || (), 2).unwrap_err();
+        assert!(matches!(
+            error.errors(),
+            [
+                TestErrorCase::TestcaseCountChanged {
+                    expected: 2,
+                    found: 0
+                },
+                ..
+            ]
+        ));
+
+        let calls = AtomicUsize::new(0);
+        let make_dsl = || {
+            let mut ts = TestDsl::<()>::new();
+            if calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                ts.add_verb("noop", FunctionVerb::new(|_: &mut ()| Ok(())));
+            }
+            ts
+        };
+
+        let error = TestDsl::run_parallel(make_dsl, "testcase { noop }", || (), 1).unwrap_err();
+        assert!(matches!(
+            error.errors(),
+            [TestErrorCase::UnknownVerb { .. }]
+        ));
+    }
+
+    #[test]
+    fn check_run_all() {
+        let mut ts = TestDsl::<usize>::new();
+        ts.add_verb(
+            "add",
+            FunctionVerb::new(|h: &mut usize, n: usize| {
+                *h += n;
+                Ok(())
+            }),
+        );
+        ts.add_condition(
+            "is",
+            FunctionCondition::new_now(|h: &usize, n: usize| Ok(*h == n)),
+        );
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                add 1
+                assert { is 2 }
+            }
+            testcase {
+                add 2
+                assert { is 3 }
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut harness = 0;
+        let results = run_all(&tc, &mut harness);
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_failed());
+        assert!(results[1].is_passed());
+        assert_eq!(harness, 3);
+
+        let results = run_all_with(&tc, || 1);
+
+        assert!(results[0].is_passed());
+        assert!(results[1].is_passed());
+    }
+
+    #[test]
+    fn check_before_and_after_each() {
+        let mut ts = TestDsl::<Vec<&'static str>>::new();
+        ts.add_verb(
+            "push",
+            FunctionVerb::new(|h: &mut Vec<&'static str>| {
+                h.push("body");
+                Ok(())
+            }),
+        );
+        ts.set_before_each(|h| h.push("setup"));
+        ts.set_after_each(|h| h.push("teardown"));
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                push
+            }
+            testcase {
+                push
+                fail
+                push
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut harness = vec![];
+        ts.run_testcase(&tc[0], &mut harness).unwrap();
+        assert_eq!(harness, ["setup", "body", "teardown"]);
+
+        let mut harness = vec![];
+        assert!(ts.run_testcase(&tc[1], &mut harness).is_err());
+        assert_eq!(harness, ["setup", "body", "teardown"]);
+
+        let results = ts.run_selected(&tc, Vec::new, [0, 1]);
+        assert!(results[0].1.is_passed());
+        assert!(results[1].1.is_failed());
+
+        // Running the testcase directly does not call the hooks
+        let mut harness = vec![];
+        tc[0].run(&mut harness).unwrap();
+        assert_eq!(harness, ["body"]);
+    }
+
+    #[test]
+    fn check_observer() {
+        struct Recorder(Rc<RefCell<Vec<String>>>);
+
+        impl RunObserver for Recorder {
+            fn on_verb_start(&self, verb: &VerbEvent<'_>) {
+                self.0.borrow_mut().push(format!(
+                    "start {} at {}:{}",
+                    verb.name(),
+                    verb.line(),
+                    verb.column()
+                ));
+            }
+
+            fn on_verb_end(&self, verb: &VerbEvent<'_>, result: Result<(), &TestError>) {
+                self.0
+                    .borrow_mut()
+                    .push(format!("end {} {}", verb.name(), result.is_ok()));
+            }
+        }
+
+        let events = Rc::new(RefCell::new(vec![]));
+
+        let mut ts = TestDsl::<()>::new();
+        ts.set_observer(Recorder(events.clone()));
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                group {
+                    fail
+                }
+            }
+            "#,
+            )
+            .unwrap();
+
+        assert!(ts.run_testcase(&tc[0], &mut ()).is_err());
+
+        assert_eq!(
+            *events.borrow(),
+            [
+                "start group at 3:17",
+                "start fail at 4:21",
+                "end fail false",
+                "end group false",
+            ]
+        );
+
+        // Running the testcase directly is not observed
+        events.borrow_mut().clear();
+        assert!(tc[0].run(&mut ()).is_err());
+        assert!(events.borrow().is_empty());
+    }
+
+    #[test]
+    fn check_panic_location() {
+        let mut ts = TestDsl::<()>::new();
+        ts.add_verb("explode", FunctionVerb::new(|_h: &mut ()| panic!("boom")));
+
+        let tc = ts.parse_testcase("testcase { explode }").unwrap();
+        let error = tc[0].run(&mut ()).unwrap_err();
+
+        let TestError::Panic { error, .. } = &error.error else {
+            panic!("Expected a panic, got {:?}", error.error);
+        };
+
+        let panic = error.downcast_ref::<PanicError>().unwrap();
+        assert_eq!(panic.message(), "boom");
+        assert!(panic.location().unwrap().starts_with("src/lib.rs:"));
+    }
+
+    #[test]
+    fn check_catch_panics() {
+        let mut ts = TestDsl::<()>::new();
+        ts.add_verb("explode", FunctionVerb::new(|_h: &mut ()| panic!("boom")));
+
+        let caught = ts.parse_testcase("testcase { explode }").unwrap();
+
+        ts.set_catch_panics(false);
+        let uncaught = ts.parse_testcase("testcase { explode }").unwrap();
+
+        assert!(caught[0].run(&mut ()).is_err());
+
+        let payload =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| uncaught[0].run(&mut ())))
+                .unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"boom"));
+    }
+
+    #[test]
+    fn check_typed_argument() {
+        #[derive(Debug, Clone)]
+        struct Port;
+
+        impl TypeAnnotation for Port {
+            const NAME: &'static str = "port";
+        }
+
+        let mut ts = TestDsl::<u16>::new();
+        ts.add_verb(
+            "listen",
+            FunctionVerb::new(|h: &mut u16, Typed(port, _): Typed<u16, Port>| {
+                *h = port;
+                Ok(())
+            }),
+        );
+
+        let tc = ts.parse_testcase("testcase { listen (port)8080 }").unwrap();
+        let mut port = 0;
+        tc[0].run(&mut port).unwrap();
+        assert_eq!(port, 8080);
+
+        for input in [
+            "testcase { listen 8080 }",
+            "testcase { listen (ip)8080 }",
+            "testcase { listen (port)\"http\" }",
+        ] {
+            let error = ts.parse_testcase(input).unwrap_err();
+            let [TestErrorCase::WrongArgumentType { expected, .. }] = &error.errors[..] else {
+                panic!("Expected a wrong argument type for {input}, got {error:?}");
+            };
+            assert!(expected.contains("`(port)`"), "{expected}");
+        }
+    }
+
+    #[test]
+    fn check_raw_and_typed_children() {
+        #[derive(Debug, Clone)]
+        struct Names;
+
+        impl Verb<Vec<String>> for Names {
+            type Arguments = RawChildren<((),)>;
+
+            fn run(
+                &self,
+                h: &mut Vec<String>,
+                _context: &mut TestContext,
+                children: &Self::Arguments,
+            ) -> miette::Result<()> {
+                h.extend(
+                    children
+                        .children()
+                        .iter()
+                        .map(|child| child.name().value().to_string()),
+                );
+                Ok(())
+            }
+        }
+
+        #[derive(Debug, Clone)]
+        struct Rows;
+
+        impl Verb<Vec<String>> for Rows {
+            type Arguments = TypedChildren<(String,), (usize, usize)>;
+
+            fn run(
+                &self,
+                h: &mut Vec<String>,
+                _context: &mut TestContext,
+                rows: &Self::Arguments,
+            ) -> miette::Result<()> {
+                for (a, b) in rows.children() {
+                    h.push(format!("{} {}", rows.parameters().0, a + b));
+                }
+                Ok(())
+            }
+        }
+
+        let mut ts = TestDsl::<Vec<String>>::new();
+        ts.add_verb("names", Names);
+        ts.add_verb("rows", Rows);
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                names {
+                    unknown 1
+                    whatever { nested }
+                }
+                rows "sum" {
+                    row 1 2
+                    row 3 4
+                }
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut output = vec![];
+        tc[0].run(&mut output).unwrap();
+        assert_eq!(output, ["unknown", "whatever", "sum 3", "sum 7"]);
+
+        let error = ts
+            .parse_testcase(r#"testcase { rows "sum" { row 1; row "a" 2; } }"#)
+            .unwrap_err();
+        assert_eq!(error.errors.len(), 2);
+    }
+
+    #[test]
+    fn check_value_children() {
+        #[derive(Debug, Clone)]
+        struct Sum;
+
+        impl Verb<usize> for Sum {
+            type Arguments = ValueChildren<((),), usize>;
+
+            fn run(
+                &self,
+                h: &mut usize,
+                _context: &mut TestContext,
+                values: &Self::Arguments,
+            ) -> miette::Result<()> {
+                *h = values.children().iter().sum();
+                Ok(())
+            }
+        }
+
+        let mut ts = TestDsl::<usize>::new();
+        ts.add_verb("sum", Sum);
+
+        let tc = ts
+            .parse_testcase("testcase { sum { - 1; - 2; value 3 } }")
+            .unwrap();
+
+        let mut total = 0;
+        tc[0].run(&mut total).unwrap();
+        assert_eq!(total, 6);
+
+        let error = ts
+            .parse_testcase(r#"testcase { sum { - 1; many 2 3; - "a"; "b"; - x=1; n { - 1 } } }"#)
+            .unwrap_err();
+
+        let spans = error
+            .errors
+            .iter()
+            .map(|error| match error {
+                TestErrorCase::UnexpectedArgument { argument, .. } => {
+                    ("unexpected", argument.offset())
+                }
+                TestErrorCase::WrongArgumentType { argument, .. } => ("wrong", argument.offset()),
+                error => panic!("Unexpected error {error:?}"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            spans,
+            [
+                ("unexpected", 29),
+                ("wrong", 34),
+                ("wrong", 39),
+                ("unexpected", 46),
+                ("unexpected", 55),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_condition_combinators() {
+        let mut ts = TestDsl::<()>::new();
+        ts.add_condition("yes", FunctionCondition::new(|_h: &()| Ok(true)));
+        ts.add_condition("no", FunctionCondition::new(|_h: &()| Ok(false)));
+
+        let passes = |input: &str| {
+            ts.parse_testcase(&*format!("testcase {{ assert {{ {input} }} }}"))
+                .unwrap()[0]
+                .run(&mut ())
+                .is_ok()
+        };
+
+        assert!(passes("not { no }"));
+        assert!(!passes("not { yes }"));
+
+        for input in ["not { yes; no }", r#"eventually timeout="1ms" { yes; no }"#] {
+            let error = ts
+                .parse_testcase(&*format!("testcase {{ assert {{ {input} }} }}"))
+                .unwrap_err();
+            assert!(matches!(
+                error.errors[..],
+                [TestErrorCase::UnexpectedArgument { .. }]
+            ));
+        }
+
+        let error = ts
+            .parse_testcase(r#"testcase { assert { consistently timeout="1ms" } }"#)
+            .unwrap_err();
+        assert!(matches!(
+            error.errors[..],
+            [TestErrorCase::ExpectedCondition { .. }]
+        ));
+
+        assert!(passes("all { yes; yes }"));
+        assert!(!passes("all { yes; no }"));
+        assert!(passes("all"));
+
+        assert!(passes("any { no; yes }"));
+        assert!(!passes("any { no; no }"));
+        assert!(!passes("any"));
+
+        assert!(passes("all { yes; any { no; not { no } } }"));
+    }
+
+    #[test]
+    fn check_skip_rest() {
+        let mut ts = TestDsl::<usize>::new();
+        ts.add_verb(
+            "count",
+            FunctionVerb::new(|h: &mut usize| {
+                *h += 1;
+                Ok(())
+            }),
+        );
+
+        let run = |input: &str| {
+            let mut count = 0;
+            let result = ts
+                .parse_testcase(&*format!("testcase {{ {input} }}"))
+                .unwrap()[0]
+                .run(&mut count);
+
+            (result.is_ok(), count)
+        };
+
+        assert_eq!(run("count; skip_rest; count"), (true, 1));
+        assert_eq!(
+            run(r#"repeat 3 { group "setup" { count; skip_rest } }; count"#),
+            (true, 1)
+        );
+        assert_eq!(run("assert_fails { skip_rest }; count"), (true, 0));
+        assert_eq!(run("count; fail; skip_rest"), (false, 1));
+
+        let mut count = 0;
+        let tc = ts
+            .parse_testcase("testcase { fail; skip_rest; count }")
+            .unwrap();
+        let errors = tc[0].run_collect(&mut count).unwrap_err();
+        assert_eq!((errors.errors().len(), count), (1, 0));
+    }
+
+    #[test]
+    fn check_skip_if() {
+        let mut ts = TestDsl::<usize>::new();
+        ts.add_verb(
+            "count",
+            FunctionVerb::new(|h: &mut usize| {
+                *h += 1;
+                Ok(())
+            }),
+        );
+        ts.add_condition("yes", FunctionCondition::new(|_h: &usize| Ok(true)));
+        ts.add_condition("no", FunctionCondition::new(|_h: &usize| Ok(false)));
+
+        let run = |input: &str| {
+            let mut count = 0;
+            let outcome = ts
+                .parse_testcase(&*format!("testcase {{ {input} }}"))
+                .unwrap()[0]
+                .run_outcome(&mut count);
+
+            (outcome, count)
+        };
+
+        let (outcome, count) = run("count; skip_if { no }; count");
+        assert!(outcome.is_passed());
+        assert_eq!(count, 2);
+
+        let (outcome, count) = run(r#"count; group { skip_if "Not today" { yes } }; count"#);
+        assert!(outcome.is_skipped());
+        assert_eq!(outcome.skip_reason(), Some("Not today"));
+        assert_eq!(count, 1);
+
+        let (outcome, _) = run("skip_if { yes; no }");
+        assert!(outcome.is_passed());
+
+        let (outcome, _) = run("fail");
+        assert!(outcome.is_failed());
+        assert!(!outcome.error().unwrap().is_skipped());
+        assert_eq!(outcome.error().unwrap().skip_reason(), None);
+
+        let tc = ts.parse_testcase("testcase { skip_if { yes } }").unwrap();
+        assert!(tc[0].run(&mut 0).is_ok());
+        assert!(ts.run_testcase(&tc[0], &mut 0).is_ok());
+        assert!(tc[0].run_collect(&mut 0).is_ok());
+        assert!(run_all(&tc, &mut 0)[0].is_skipped());
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn check_matches_does_not_hold() {
+        let mut ts = TestDsl::<Cell<usize>>::new();
+        ts.add_condition(
+            "log_matches",
+            crate::condition::Matches::new(|h: &Cell<usize>| {
+                h.set(h.get() + 1);
+                ["starting", "starting", "server is ready"][h.get().min(3) - 1]
+            }),
+        );
+
+        let run = |input: &str| {
+            let tc = ts
+                .parse_testcase(&*format!("testcase {{ {input} }}"))
+                .unwrap();
+            let mut polls = Cell::new(0);
+            tc[0].run(&mut polls).map(|()| polls.get())
+        };
+
+        assert_eq!(run(r#"wait_for { log_matches "ready" }"#).unwrap(), 3);
+        assert_eq!(run(r#"assert { not { log_matches "ready" } }"#).unwrap(), 1);
+        assert_eq!(
+            run(r#"assert { eventually timeout="1s" { log_matches "ready$" } }"#).unwrap(),
+            3
+        );
+
+        let error = run(r#"assert { log_matches "ready" }"#).unwrap_err();
+        assert!(matches!(
+            error.stack().last(),
+            Some(TestError::ConditionFailed { note: Some(note), .. })
+                if note == r#"The text was "starting", which does not match `ready`"#
+        ));
+    }
+
+    #[test]
+    fn check_temporal_conditions() {
+        let mut ts = TestDsl::<Cell<usize>>::new();
+        ts.add_condition(
+            "counted_to",
+            FunctionCondition::new(|h: &Cell<usize>, count: usize| {
+                h.set(h.get() + 1);
+                Ok(h.get() >= count)
+            }),
+        );
+        ts.add_condition(
+            "below",
+            FunctionCondition::new(|h: &Cell<usize>, limit: usize| {
+                h.set(h.get() + 1);
+                Ok(h.get() < limit)
+            }),
+        );
+
+        let run = |input: &str| {
+            let mut checks = Cell::new(0);
+            let result = ts
+                .parse_testcase(&*format!("testcase {{ assert {{ {input} }} }}"))
+                .unwrap()[0]
+                .run(&mut checks);
+
+            (result.is_ok(), checks.get())
+        };
+
+        assert_eq!(
+            run(r#"eventually timeout="1s" interval="1ms" { counted_to 3 }"#),
+            (true, 3)
+        );
+        assert!(!run(r#"eventually timeout="5ms" interval="1ms" { counted_to 1000 }"#).0);
+
+        let (passed, checks) = run(r#"consistently timeout="5ms" interval="1ms" { below 1000 }"#);
+        assert!(passed);
+        assert!(checks > 1);
+
+        assert_eq!(
+            run(r#"consistently timeout="1s" interval="1ms" { below 3 }"#),
+            (false, 3)
+        );
+
+        assert!(run(r#"not { eventually timeout="5ms" { counted_to 1000 } }"#).0);
+        assert!(
+            ts.parse_testcase(
+                r#"testcase { assert { eventually timeout="5ms" { counted_to 1; counted_to 2 } } }"#
+            )
+            .is_err()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn check_trace() {
+        use crate::trace::TraceCollector;
+        use crate::trace::TraceKind;
+        use crate::trace::TraceResult;
+
+        let mut ts = TestDsl::<()>::new();
+        ts.add_condition("yes", FunctionCondition::new(|_h: &()| Ok(true)));
+        ts.add_condition("no", FunctionCondition::new(|_h: &()| Ok(false)));
+
+        let tc = ts
+            .parse_testcase("testcase { group { assert { yes; no } } }")
+            .unwrap();
+
+        let trace = TraceCollector::new();
+        assert!(tc[0].run_traced(&mut (), &trace).is_err());
+
+        let events = trace.events();
+        assert_eq!(events.len(), 1);
+
+        let assert = &events[0].children[0];
+        assert_eq!(assert.name, "assert");
+        assert!(matches!(assert.result, TraceResult::Error { .. }));
+
+        let results = assert
+            .children
+            .iter()
+            .map(|child| (child.kind, child.result.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            [
+                (TraceKind::Condition, TraceResult::Passed),
+                (TraceKind::Condition, TraceResult::Failed)
+            ]
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&trace.to_json()).unwrap();
+        assert_eq!(
+            json[0]["children"][0]["children"][1]["result"]["status"],
+            "failed"
+        );
+
+        let tc = ts
+            .parse_testcase("testcase { repeat_until max=2 { group; no } }")
+            .unwrap();
+
+        let trace = TraceCollector::new();
+        let error = tc[0].run_traced(&mut (), &trace).unwrap_err();
+        assert!(matches!(
+            error.error,
+            TestError::IterationLimitReached { max: 2, .. }
+        ));
+
+        let events = trace.events();
+        assert_eq!(
+            events[0].result,
+            TraceResult::Error {
+                message: String::from("The loop did not finish after 2 iterations")
+            }
+        );
+        assert_eq!(events[0].children.len(), 4);
+    }
+
+    #[test]
+    fn check_list_verbs_and_conditions() {
+        let mut ts = TestDsl::<()>::new();
+        ts.add_verb("roll", FunctionVerb::new(|_: &mut (), _: usize| Ok(())));
+        ts.add_condition("yes", FunctionCondition::new(|_h: &()| Ok(true)));
+
+        let verbs = ts.list_verbs();
+        assert!(verbs.contains(&"roll"));
+        assert!(verbs.contains(&"repeat"));
+        assert!(verbs.is_sorted());
+
+        let conditions = ts.list_conditions();
+        assert!(conditions.contains(&"yes"));
+        assert!(conditions.contains(&"not"));
+        assert!(conditions.is_sorted());
+
+        assert!(ts.has_verb("roll"));
+        assert!(!ts.has_verb("yes"));
+        assert!(ts.has_condition("yes"));
+        assert!(!ts.has_condition("roll"));
+    }
+
+    #[test]
+    fn check_verb_signature() {
+        let mut ts = TestDsl::<()>::new();
+        ts.add_verb(
+            "roll",
+            FunctionVerb::new(|_: &mut (), _: usize, _: Option<usize>| Ok(())),
+        );
+
+        let roll = ts.verb_signature("roll").unwrap();
+        assert_eq!(roll.arguments(), ["usize", "usize"]);
+        assert_eq!(roll.required_arguments(), 1);
+        assert!(!roll.takes_children());
+
+        let repeat = ts.verb_signature("repeat").unwrap();
+        assert_eq!(repeat.arguments(), ["usize"]);
+        assert!(repeat.takes_children());
+
+        let wait_for = ts.verb_signature("wait_for").unwrap();
+        assert!(wait_for.arguments().is_empty());
+        assert!(wait_for.takes_children());
+
+        assert_eq!(ts.verb_signature("nope"), None);
+    }
+
+    #[test]
+    fn check_try_add() {
+        let mut ts = TestDsl::<()>::new();
+
+        ts.try_add_verb("roll", FunctionVerb::new(|_: &mut (), _: usize| Ok(())))
+            .unwrap();
+        let error = ts
+            .try_add_verb("roll", FunctionVerb::new(|_: &mut ()| Ok(())))
+            .unwrap_err();
+        assert_eq!(error.name, "roll");
+        assert!(
+            ts.try_add_verb("repeat", FunctionVerb::new(|_: &mut ()| Ok(())))
+                .is_err()
+        );
+
+        ts.try_add_condition("yes", FunctionCondition::new(|_h: &()| Ok(true)))
+            .unwrap();
+        assert!(
+            ts.try_add_condition("yes", FunctionCondition::new(|_h: &()| Ok(false)))
+                .is_err()
+        );
+
+        // The first registration is kept
+        let tc = ts
+            .parse_testcase("testcase { roll 2; assert { yes; }; }")
+            .unwrap();
+        tc[0].run(&mut ()).unwrap();
+    }
+
+    #[test]
+    fn check_override() {
+        let mut ts = TestDsl::<Vec<&'static str>>::new();
+
+        let replaced = ts.override_verb(
+            "group",
+            FunctionVerb::new(|h: &mut Vec<&'static str>| {
+                h.push("group");
+                Ok(())
+            }),
+        );
+        assert!(replaced);
+
+        let replaced = ts.override_verb(
+            "push",
+            FunctionVerb::new(|h: &mut Vec<&'static str>| {
+                h.push("push");
+                Ok(())
+            }),
+        );
+        assert!(!replaced);
+
+        ts.add_condition(
+            "is_empty",
+            FunctionCondition::new(|h: &Vec<&'static str>| Ok(h.is_empty())),
+        );
+        let replaced = ts.override_condition(
+            "is_empty",
+            FunctionCondition::new(|h: &Vec<&'static str>| Ok(h.len() == 2)),
+        );
+        assert!(replaced);
+
+        let tc = ts
+            .parse_testcase("testcase { group; push; assert { is_empty; }; }")
+            .unwrap();
+
+        let mut harness = vec![];
+        tc[0].run(&mut harness).unwrap();
+        assert_eq!(harness, ["group", "push"]);
+    }
+
+    #[test]
+    fn check_empty() {
+        let mut ts = TestDsl::<()>::empty();
+
+        assert!(ts.list_verbs().is_empty());
+        assert!(ts.list_conditions().is_empty());
+        assert!(ts.parse_testcase("testcase { group; }").is_err());
+
+        ts.add_verb(
+            "group",
+            FunctionVerb::new(|_: &mut (), _name: String| Ok(())),
+        );
+
+        let tc = ts
+            .parse_testcase(r#"testcase { group "custom"; }"#)
+            .unwrap();
+        tc[0].run(&mut ()).unwrap();
+    }
+
+    #[test]
+    fn check_merge() {
+        fn module(value: &'static str) -> TestDsl<Vec<&'static str>> {
+            let mut ts = TestDsl::empty();
+            ts.add_verb(
+                value,
+                FunctionVerb::new(move |h: &mut Vec<&'static str>| {
+                    h.push(value);
+                    Ok(())
+                }),
+            );
+            ts.add_verb(
+                "shared",
+                FunctionVerb::new(move |h: &mut Vec<&'static str>| {
+                    h.push(value);
+                    Ok(())
+                }),
+            );
+            ts
+        }
+
+        let mut ts = TestDsl::new();
+        assert_eq!(
+            ts.merge(module("first"), MergeStrategy::KeepExisting),
+            Ok(())
+        );
+        assert_eq!(
+            ts.merge(module("second"), MergeStrategy::KeepExisting),
+            Err(vec![String::from("shared")])
+        );
+
+        let tc = ts
+            .parse_testcase("testcase { first; second; shared; }")
+            .unwrap();
+        let mut harness = vec![];
+        tc[0].run(&mut harness).unwrap();
+        assert_eq!(harness, ["first", "second", "first"]);
+
+        assert_eq!(
+            ts.merge(module("third"), MergeStrategy::Overwrite),
+            Err(vec![String::from("shared")])
+        );
+
+        let tc = ts.parse_testcase("testcase { shared; }").unwrap();
+        let mut harness = vec![];
+        tc[0].run(&mut harness).unwrap();
+        assert_eq!(harness, ["third"]);
+    }
+
+    #[test]
+    fn check_warnings_stay_with_their_testcase() {
+        let mut ts = TestDsl::<()>::new();
+        ts.add_verb("old", FunctionVerb::new(|_: &mut ()| Ok(())));
+        ts.deprecate_verb("old", "Use `group` instead");
+
+        let handled = Rc::new(Cell::new(0));
+        ts.set_warning_handler({
+            let handled = handled.clone();
+            move |warning| handled.set(handled.get() + warning.warnings().len())
+        });
+
+        let tc = ts
+            .parse_testcase("testcase { repeat 2 { old; }; }; testcase { group; }")
+            .unwrap();
+        assert_eq!(tc[0].warnings().len(), 1);
+        assert!(tc[1].warnings().is_empty());
+        assert_eq!(handled.get(), 1);
+
+        let node = kdl::KdlNode::parse("old").unwrap();
+        crate::VerbInstance::with_test_dsl(&ts, &node).unwrap();
+
+        let _ =
+            ts.parse_testcase_streaming("testcase { old; unknown; }", |_| ControlFlow::Break(()));
+        assert_eq!(handled.get(), 2);
+
+        let tc = ts.parse_testcase("testcase { group; }").unwrap();
+        assert!(tc[0].warnings().is_empty());
+        assert_eq!(handled.get(), 2);
+    }
+
+    #[test]
+    fn check_remove() {
+        let mut ts = TestDsl::<()>::new();
+
+        assert!(ts.remove_verb("repeat"));
+        assert!(!ts.remove_verb("repeat"));
+        assert!(ts.remove_condition("not"));
+        assert!(!ts.remove_condition("not"));
+
+        assert!(
+            ts.parse_testcase("testcase { repeat 2 { group; }; }")
+                .is_err()
+        );
+        assert!(ts.parse_testcase("testcase { group; }").is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "`roll` is already registered")]
+    fn check_add_verb_duplicate() {
+        let mut ts = TestDsl::<()>::new();
+
+        ts.add_verb("roll", FunctionVerb::new(|_: &mut ()| Ok(())));
+        ts.add_verb("roll", FunctionVerb::new(|_: &mut ()| Ok(())));
+    }
+
+    #[test]
+    #[should_panic(expected = "`yes` is already registered")]
+    fn check_add_mut_condition_duplicate() {
+        let mut ts = TestDsl::<()>::new();
+
+        ts.add_condition("yes", FunctionCondition::new(|_h: &()| Ok(true)));
+        ts.add_mut_condition("yes", FunctionMutCondition::new(|_h: &mut ()| Ok(true)));
+    }
+
+    #[test]
+    fn check_to_markdown() {
+        #[derive(Debug, Clone)]
+        struct Opaque;
+
+        impl crate::argument::ParseArguments<()> for Opaque {
+            fn parse(_test_dsl: &TestDsl<()>, _node: &kdl::KdlNode) -> Result<Self, TestErrorCase> {
+                Ok(Opaque)
+            }
+        }
+
+        #[derive(Debug, Clone)]
+        struct OpaqueVerb;
+
+        impl Verb<()> for OpaqueVerb {
+            type Arguments = Opaque;
+
+            fn run(
+                &self,
+                _harness: &mut (),
+                _context: &mut TestContext,
+                _arguments: &Self::Arguments,
+            ) -> miette::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut ts = TestDsl::<()>::new();
+        ts.add_verb(
+            "roll",
+            FunctionVerb::new(|_: &mut (), _: usize, _: Option<usize>| Ok(())),
+        );
+        ts.add_verb("old_roll", FunctionVerb::new(|_: &mut (), _: usize| Ok(())));
+        ts.deprecate_verb("old_roll", "Use `roll` instead");
+        ts.add_verb("opaque", OpaqueVerb);
+        ts.add_condition("yes", FunctionCondition::new(|_h: &()| Ok(true)));
+
+        let markdown = ts.to_markdown();
+
+        assert!(markdown.contains("| `roll` | `usize`, `usize` | 1 to 2 | no |  |\n"));
+        assert!(markdown.contains("| `old_roll` | `usize` | 1 | no | Use `roll` instead |\n"));
+        assert!(markdown.contains("| `repeat` | `usize` | 1 | yes |  |\n"));
+        assert!(markdown.contains("| `group` | `String` | 0 to 1 | yes |  |\n"));
+        assert!(markdown.contains("| `yes` |  | 0 | no |\n"));
+        assert!(markdown.contains("| `not` |  | 0 | yes |\n"));
+
+        // Without a signature, only the shortened type name of the arguments is known
+        let opaque = crate::short_type_name(std::any::type_name::<Opaque>());
+        assert!(markdown.contains(&format!("| `opaque` | `{opaque}` |  |  |  |\n")));
+
+        assert!(markdown.find("`fail`").unwrap() < markdown.find("`group`").unwrap());
+        assert!(markdown.find("# Verbs").unwrap() < markdown.find("# Conditions").unwrap());
+    }
+
+    #[test]
+    fn check_short_type_name() {
+        assert_eq!(
+            crate::short_type_name("alloc::vec::Vec<alloc::string::String>"),
+            "Vec<String>"
+        );
+        assert_eq!(
+            crate::short_type_name(
+                "test_dsl::argument::VerbChildren<(), (usize, core::time::Duration)>"
+            ),
+            "VerbChildren<(), (usize, Duration)>"
+        );
+    }
+
+    #[test]
+    fn check_if() {
+        let mut ts = TestDsl::<i32>::new();
+        ts.add_verb(
+            "add",
+            FunctionVerb::new(|h: &mut i32, num: i32| {
+                *h += num;
+                Ok(())
+            }),
+        );
+        ts.add_condition("is_zero", FunctionCondition::new_now(|h: &i32| Ok(*h == 0)));
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                if {
+                    is_zero
+                    add 2
+                    add 3
+                }
+                if {
+                    is_zero
+                    add 100
+                    else {
+                        add 10
+                    }
+                }
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut harness = 0;
+        tc[0].run(&mut harness).unwrap();
+        assert_eq!(harness, 15);
+
+        assert!(ts.parse_testcase("testcase { if { add 1 } }").is_err());
+        assert!(
+            ts.parse_testcase("testcase { if { is_zero; else; add 1 } }")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn check_repeat_until() {
+        let mut ts = TestDsl::<i32>::new();
+        ts.add_verb(
+            "add",
+            FunctionVerb::new(|h: &mut i32, num: i32| {
+                *h += num;
+                Ok(())
+            }),
+        );
+        ts.add_condition("is_zero", FunctionCondition::new_now(|h: &i32| Ok(*h == 0)));
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                repeat_until {
+                    add -1
+                    is_zero
+                }
+            }
+            testcase {
+                repeat_until max=3 {
+                    add 1
+                    is_zero
+                }
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut harness = 4;
+        tc[0].run(&mut harness).unwrap();
+        assert_eq!(harness, 0);
+
+        let mut harness = 0;
+        let error = tc[1].run(&mut harness).unwrap_err();
+        assert_eq!(harness, 3);
+        assert!(format!("{:?}", miette::Report::new(error)).contains("after 3 iterations"));
+
+        assert!(
+            ts.parse_testcase("testcase { repeat_until { is_zero; add 1 } }")
+                .is_err()
+        );
+        assert!(ts.parse_testcase("testcase { repeat_until }").is_err());
+    }
+
+    #[test]
+    fn check_matches_pattern() {
+        assert!(matches_pattern("*.kdl", "basic.kdl"));
+        assert!(matches_pattern("*.kdl", ".kdl"));
+        assert!(matches_pattern("test_?.kdl", "test_1.kdl"));
+        assert!(matches_pattern("*_*.kdl", "a_b_c.kdl"));
+        assert!(matches_pattern("*", "anything"));
+        assert!(!matches_pattern("*.kdl", "basic.kdl.bak"));
+        assert!(!matches_pattern("test_?.kdl", "test_10.kdl"));
+        assert!(!matches_pattern("basic", "basic.kdl"));
+    }
+
+    #[test]
+    fn check_testcase_name() {
+        let ts = TestDsl::<()>::new();
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase "login works" {
+                group
+            }
+            testcase {
+                group
+            }
+            "#,
+            )
+            .unwrap();
+
+        assert_eq!(tc[0].name(), Some("login works"));
+        assert_eq!(tc[1].name(), None);
+
+        assert!(ts.parse_testcase("testcase 42 { group }").is_err());
+        assert!(ts.parse_testcase(r#"testcase "a" "b" { group }"#).is_err());
+    }
+
+    #[test]
+    fn check_testcase_tags() {
+        let ts = TestDsl::<()>::new();
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase "login" tags="smoke, auth" {
+                group
+            }
+            testcase "logout" tags=auth {
+                group
+            }
+            testcase {
+                group
+            }
+            "#,
+            )
+            .unwrap();
+
+        assert_eq!(tc[0].tags(), ["smoke", "auth"]);
+        assert_eq!(tc[1].tags(), ["auth"]);
+        assert!(tc[2].tags().is_empty());
+
+        assert_eq!(select(&tc, |case| case.has_tag("smoke")), [0]);
+        assert_eq!(select(&tc, |case| case.has_tag("auth")), [0, 1]);
+        assert_eq!(select(&tc, |case| case.name() == Some("logout")), [1]);
+
+        assert!(ts.parse_testcase("testcase tags=#true { group }").is_err());
+    }
+
+    #[test]
+    fn check_sleep() {
+        let ts = TestDsl::<()>::new();
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                sleep 20
+                sleep "10ms"
+            }
+            "#,
+            )
+            .unwrap();
+
+        let start = std::time::Instant::now();
+        tc[0].run(&mut ()).unwrap();
+        assert!(start.elapsed() >= std::time::Duration::from_millis(30));
+
+        assert!(
+            ts.parse_testcase(r#"testcase { sleep "2 fortnights" }"#)
+                .is_err()
+        );
+        assert!(ts.parse_testcase("testcase { sleep -5 }").is_err());
+    }
+
+    #[test]
+    fn check_wait_for_timeout() {
+        let mut ts = TestDsl::<Cell<usize>>::new();
+        ts.add_condition(
+            "polled",
+            FunctionCondition::new_wait(|h: &Cell<usize>, times: usize| {
+                h.set(h.get() + 1);
+                Ok(h.get() >= times)
+            }),
+        );
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                wait_for timeout="1s" interval=1 {
+                    polled 3
+                }
+            }
+            testcase {
+                wait_for timeout=30 interval=5 {
+                    polled 1000
+                }
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut polls = Cell::new(0);
+        tc[0].run(&mut polls).unwrap();
+        assert_eq!(polls.get(), 3);
+
+        let start = std::time::Instant::now();
+        let error = tc[1].run(&mut Cell::new(0)).unwrap_err();
+        assert!(start.elapsed() >= std::time::Duration::from_millis(30));
+
+        assert!(matches!(error.error, crate::TestError::Timeout { .. }));
+
+        assert!(
+            ts.parse_testcase(r#"testcase { wait_for timeout="soon" { polled 1; }; }"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn check_function_mut_condition() {
+        let mut ts = TestDsl::<Vec<i32>>::new();
+        ts.add_mut_condition(
+            "next_is",
+            FunctionMutCondition::new(|h: &mut Vec<i32>, expected: i32| {
+                let next = h.pop();
+                h.extend(next);
+                Ok(next == Some(expected))
+            }),
+        );
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                assert {
+                    next_is 2
+                    not { next_is 1; }
+                }
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut harness = vec![1, 2];
+        tc[0].run(&mut harness).unwrap();
+        assert_eq!(harness, [1, 2]);
+
+        assert!(tc[0].run(&mut vec![2, 1]).is_err());
+    }
+
+    #[test]
+    fn check_context_value() {
+        let mut context = TestContext::new(0);
+        context.set("id", 42usize);
+
+        assert_eq!(context.value::<usize>("id").unwrap(), &42);
+        assert!(matches!(
+            context.value::<String>("id"),
+            Err(crate::error::ContextValueError::WrongType { .. })
+        ));
+        assert!(matches!(
+            context.value::<usize>("name"),
+            Err(crate::error::ContextValueError::Missing { .. })
+        ));
+    }
+
+    #[test]
+    fn check_variables() {
+        let mut ts = TestDsl::<Vec<String>>::new();
+        ts.add_verb(
+            "get",
+            FunctionVerb::new(|h: &mut Vec<String>, url: String| {
+                h.push(url);
+                Ok(())
+            }),
+        );
+
+        let tc = ts
+            .parse_testcase(r#"testcase { get "${base} costs $$5"; }"#)
+            .unwrap();
+        let mut harness = vec![];
+        tc[0].run(&mut harness).unwrap();
+        assert_eq!(harness, ["${base} costs $$5"]);
+
+        ts.set_variable("base", "http://x");
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase "${base}" {
+                group {
+                    get "${base}/health"
+                }
+                get "$$base"
+            }
+            "#,
+            )
+            .unwrap();
+
+        assert_eq!(tc[0].name(), Some("http://x"));
+
+        let mut harness = vec![];
+        tc[0].run(&mut harness).unwrap();
+        assert_eq!(harness, ["http://x/health", "$base"]);
+
+        let error = ts
+            .parse_testcase(r#"testcase { get "${port}"; get "${base"; }"#)
+            .unwrap_err();
+        assert_eq!(error.errors.len(), 2);
+        assert!(matches!(
+            error.errors[0],
+            TestErrorCase::InvalidInterpolation { .. }
+        ));
+
+        let tc = ts
+            .parse_testcase(r#"testcase { get "$ENV{PATH}"; }"#)
+            .unwrap();
+        let mut harness = vec![];
+        tc[0].run(&mut harness).unwrap();
+        assert_eq!(harness, ["$ENV{PATH}"]);
+
+        ts.set_env_interpolation(true);
+        let tc = ts
+            .parse_testcase(r#"testcase { get "$ENV{PATH}"; }"#)
+            .unwrap();
+        let mut harness = vec![];
+        tc[0].run(&mut harness).unwrap();
+        assert_eq!(harness, [std::env::var("PATH").unwrap()]);
+    }
+
+    #[test]
+    fn check_condition_wait() {
+        let mut ts = TestDsl::<i32>::new();
+        ts.add_condition(
+            "is_zero",
+            FunctionCondition::new_wait(|h: &i32| {
+                assert!(*h >= 0, "Negative harness");
+                Ok(*h == 0)
+            }),
+        );
+        ts.add_condition("is_zero_now", FunctionCondition::new(|h: &i32| Ok(*h == 0)));
+
+        let node = kdl::KdlNode::parse("is_zero").unwrap();
+        let condition = crate::ConditionInstance::with_test_dsl(&ts, &node).unwrap();
+        let context = TestContext::new(0);
+
+        condition.wait(&0, &context).unwrap();
+        assert!(matches!(
+            condition.wait(&1, &context),
+            Err(crate::TestError::ConditionFailed { .. })
+        ));
+        assert!(matches!(
+            condition.wait(&-1, &context),
+            Err(crate::TestError::Panic { .. })
+        ));
+        assert!(matches!(
+            condition.run(&mut 0, &context),
+            Err(crate::TestError::Error { .. })
+        ));
+
+        let node = kdl::KdlNode::parse("is_zero_now").unwrap();
+        let condition = crate::ConditionInstance::with_test_dsl(&ts, &node).unwrap();
+
+        assert!(matches!(
+            condition.wait(&0, &context),
+            Err(crate::TestError::Error { .. })
+        ));
+    }
+
+    #[test]
+    fn check_wait_for() {
+        let mut ts = TestDsl::<Cell<usize>>::new();
+        ts.add_condition(
+            "polled",
+            FunctionCondition::new_wait(|h: &Cell<usize>, times: usize| {
+                h.set(h.get() + 1);
+                Ok(h.get() >= times)
+            }),
+        );
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                wait_for {
+                    polled 3
+                }
+            }
+            "#,
+            )
+            .unwrap();
+
+        let mut polls = Cell::new(0);
+        tc[0].run(&mut polls).unwrap();
+        assert_eq!(polls.get(), 3);
+
+        let error = ts
+            .parse_testcase("testcase { assert { polled 3 } }")
+            .unwrap_err();
+        assert!(matches!(
+            error.errors(),
+            [TestErrorCase::UnsupportedCondition {
+                usage: "checked now",
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn check_approx_eq_does_not_hold() {
+        let mut ts = TestDsl::<Cell<f64>>::new();
+        ts.add_condition(
+            "approx_eq",
+            crate::condition::ApproxEq::new(|h: &Cell<f64>| h.get()),
+        );
+        ts.add_condition(
+            "counts_to",
+            crate::condition::ApproxEq::new(|h: &Cell<f64>| {
+                h.set(h.get() + 1.0);
+                h.get()
+            }),
+        );
+
+        let run = |input: &str, value: f64| {
+            let tc = ts
+                .parse_testcase(&*format!("testcase {{ {input} }}"))
+                .unwrap();
+            let mut harness = Cell::new(value);
+            tc[0].run(&mut harness).map(|()| harness.get())
+        };
+
+        assert!(run("assert { not { approx_eq 1.5 0.1 } }", 2.0).is_ok());
+        assert!(run("assert { not { approx_eq 1.5 0.1 } }", 1.5).is_err());
+        assert!(run("assert { approx_eq 1.5 0.1 }", f64::NAN).is_err());
+        assert!(run("assert { not { approx_eq 1.5 0.1 } }", f64::NAN).is_ok());
+        assert_eq!(run("wait_for { counts_to 3.0 0.1 }", 0.0).unwrap(), 3.0);
+
+        let error = run("assert { approx_eq 1.5 0.1 }", 2.0).unwrap_err();
+        assert!(matches!(
+            error.stack().last(),
+            Some(TestError::ConditionFailed { note: Some(note), .. })
+                if note == "It was 2, but expected 1.5 ± 0.1"
+        ));
+    }
+
+    #[test]
+    fn check_error_locations() {
+        let ts = TestDsl::<()>::new();
+
+        let input = TestCaseInput::from("testcase {\n    group\n    nope\n}");
+        let error = ts.parse_testcase(input.clone()).unwrap_err();
+        assert_eq!(error.errors()[0].location(&input), Some((3, 5)));
+
+        let input = TestCaseInput::from("testcase {\n  assert { not { all } }\n}");
+        let tc = ts.parse_testcase(input.clone()).unwrap();
+        let error = tc[0].run(&mut ()).unwrap_err();
+        assert_eq!(error.location(), Some((2, 3)));
+        assert_eq!(error.error().location(&input), Some((2, 3)));
+    }
+
+    #[test]
+    fn check_error_stack() {
+        let ts = TestDsl::<()>::new();
+
+        let input = "testcase {\n  repeat 2 {\n    group \"inner\" {\n      fail\n    }\n  }\n}";
+        let tc = ts.parse_testcase(input).unwrap();
+        let error = tc[0].run(&mut ()).unwrap_err();
+
+        let names = error
+            .stack()
+            .iter()
+            .map(|error| {
+                let span = error.span();
+                input[span.offset()..span.offset() + span.len()]
+                    .split_whitespace()
+                    .next()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["repeat", "group", "fail"]);
+
+        let locations = error
+            .stack()
+            .iter()
+            .map(|error| error.location(&input.into()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(locations, [(2, 3), (3, 5), (4, 7)]);
+        assert!(error.stack()[2].nested().is_none());
+    }
+
+    #[test]
+    fn check_input_from_file_parts() {
+        let ts = TestDsl::<()>::new();
+
+        let input = TestCaseInput::from_file_parts("generated/smoke.kdl", "testcase { group }");
+        let tc = ts.parse_testcase(input).unwrap();
+        assert_eq!(tc[0].path(), Some("generated/smoke.kdl"));
+
+        let input = TestCaseInput::from_file_parts("generated/broken.kdl", "testcase { nope }");
+        let error = ts.parse_testcase(input).unwrap_err();
+        let rendered = format!("{:?}", miette::Error::new(error));
+        assert!(rendered.contains("generated/broken.kdl:1:12"));
     }
-}
 
-impl<H: 'static> Clone for VerbInstance<H> {
-    fn clone(&self) -> Self {
-        Self {
-            _pd: self._pd,
-            verb: self.verb.clone(),
-            arguments: self.arguments.clone(),
-            node: self.node.clone(),
-        }
+    #[test]
+    fn check_strict_testcase_only() {
+        let mut ts = TestDsl::<()>::new();
+        ts.set_strict(true);
+
+        let error = ts.parse_testcase("fixture database").unwrap_err();
+        let [TestErrorCase::UnknownTopLevelNode { allowed, .. }] = &error.errors[..] else {
+            panic!("Expected an unknown top-level node: {error:?}");
+        };
+        assert_eq!(
+            allowed,
+            "Only `testcase` nodes are allowed at the top level"
+        );
     }
-}
 
-impl<H: 'static> VerbInstance<H> {
-    /// Create a new instance with the given node and [`TestDsl`]
-    pub fn with_test_dsl(
-        test_dsl: &TestDsl<H>,
-        node: &kdl::KdlNode,
-    ) -> Result<Self, TestErrorCase> {
-        let verb = test_dsl.get_verb_for_node(node)?;
+    #[test]
+    fn check_top_level_nodes() {
+        let mut ts = TestDsl::<()>::new();
 
-        let arguments = verb.parse_args(test_dsl, node)?;
+        let input = "fixture database\ntestcase { group }";
+        let error = ts.parse_testcase(input).unwrap_err();
+        assert!(matches!(
+            error.errors[..],
+            [TestErrorCase::NotTestcase { .. }]
+        ));
 
-        Ok(VerbInstance {
-            _pd: PhantomData,
-            verb,
-            arguments,
-            node: node.clone(),
-        })
+        ts.add_top_level_node("fixture");
+        assert_eq!(ts.parse_testcase(input).unwrap().len(), 1);
+
+        ts.set_strict(true);
+        assert_eq!(ts.parse_testcase(input).unwrap().len(), 1);
+
+        let error = ts.parse_testcase("fixtures database").unwrap_err();
+        let [TestErrorCase::UnknownTopLevelNode { allowed, .. }] = &error.errors[..] else {
+            panic!("Expected an unknown top-level node: {error:?}");
+        };
+        assert_eq!(
+            allowed,
+            "Only `testcase` and `fixture` nodes are allowed at the top level"
+        );
+
+        ts.set_allow_include(true);
+        let error = ts.parse_testcase("fixtures database").unwrap_err();
+        let [TestErrorCase::UnknownTopLevelNode { allowed, .. }] = &error.errors[..] else {
+            panic!("Expected an unknown top-level node: {error:?}");
+        };
+        assert_eq!(
+            allowed,
+            "Only `testcase`, `include` and `fixture` nodes are allowed at the top level"
+        );
     }
 
-    /// Run the verb
-    ///
-    /// This returns an error if:
-    /// - It returns an [`Err`]
-    /// - It [`panic`]s
-    pub fn run(&self, harness: &mut H) -> Result<(), TestError> {
-        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            self.verb.run(harness, self.arguments.as_dyn_any())
-        }));
+    #[test]
+    fn check_include_is_opt_in() {
+        let mut ts = TestDsl::<()>::new();
 
-        match res {
-            Ok(Ok(())) => Ok(()),
-            Ok(Err(error)) => Err(TestError::Error {
-                error,
-                span: self.node.span(),
-            }),
-            Err(payload) => {
-                let mut message = "Something went wrong".to_string();
+        let input = TestCaseInput::from_file_parts(
+            "tests/include_cases/main.kdl",
+            r#"include "common.kdl"; testcase { group }"#,
+        );
 
-                if let Some(msg) = payload.downcast_ref::<&str>() {
-                    message = msg.to_string();
-                }
+        let error = ts.parse_testcase(input.clone()).unwrap_err();
+        assert!(matches!(
+            error.errors[..],
+            [TestErrorCase::NotTestcase { .. }]
+        ));
 
-                if let Some(msg) = payload.downcast_ref::<String>() {
-                    message.clone_from(msg);
-                }
+        ts.set_allow_include(true);
+        assert_eq!(ts.parse_testcase(input).unwrap().len(), 2);
 
-                Err(TestError::Panic {
-                    error: miette::Report::msg(message),
-                    span: self.node.span(),
-                })
-            }
-        }
+        let error = ts
+            .parse_testcase(r#"include "tests/include_cases/common.kdl""#)
+            .unwrap_err();
+        assert!(matches!(
+            error.errors[..],
+            [TestErrorCase::IncludeWithoutFile { .. }]
+        ));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::sync::atomic::AtomicUsize;
+    #[test]
+    fn check_testcase_keyword() {
+        let mut ts = TestDsl::<()>::new();
+        ts.set_testcase_keyword("scenario");
 
-    use crate::TestDsl;
-    use crate::verb::FunctionVerb;
+        let tc = ts.parse_testcase(r#"scenario "login" { group }"#).unwrap();
+        assert_eq!(tc[0].name(), Some("login"));
 
-    struct ArithmeticHarness {
-        value: AtomicUsize,
+        let document = tc[0].to_kdl_document();
+        assert_eq!(document.nodes()[0].name().value(), "scenario");
+        assert_eq!(ts.parse_testcase(&*document.to_string()).unwrap().len(), 1);
+
+        let error = ts.parse_testcase("testcase { group }").unwrap_err();
+        let [TestErrorCase::NotTestcase { keyword, .. }] = &error.errors[..] else {
+            panic!("Expected a node that is not a testcase: {error:?}");
+        };
+        assert_eq!(keyword, "scenario");
+        assert_eq!(
+            miette::Diagnostic::help(&error.errors[0])
+                .unwrap()
+                .to_string(),
+            "The outer items must all be `scenario`s"
+        );
     }
 
     #[test]
-    fn simple_test() {
-        let mut ts = TestDsl::<ArithmeticHarness>::new();
-        ts.add_verb(
-            "add_one",
-            FunctionVerb::new(|ah: &mut ArithmeticHarness| {
-                ah.value.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    fn check_print() {
+        struct Shared(Rc<RefCell<Vec<u8>>>);
 
-                Ok(())
-            }),
-        );
+        impl std::io::Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
 
-        ts.add_verb(
-            "mul_two",
-            FunctionVerb::new(|ah: &mut ArithmeticHarness| {
-                let value = ah.value.load(std::sync::atomic::Ordering::SeqCst);
-                ah.value
-                    .store(value * 2, std::sync::atomic::Ordering::SeqCst);
+            fn flush(&mut self) -> std::io::Result<()> {
                 Ok(())
-            }),
+            }
+        }
+
+        let mut ts = TestDsl::<()>::new();
+        let output = Rc::new(RefCell::new(vec![]));
+
+        let tc = ts
+            .parse_testcase(TestCaseInput::from_file_parts(
+                "debug.kdl",
+                "testcase {\n    print \"first\"\n    repeat 2 { print second; }\n}",
+            ))
+            .unwrap();
+
+        ts.set_output(Shared(output.clone()));
+        ts.run_testcase(&tc[0], &mut ()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output.take()).unwrap(),
+            "[debug.kdl:2:5] first\n[debug.kdl:3:16] second\n[debug.kdl:3:16] second\n"
         );
+    }
+
+    #[test]
+    fn check_to_kdl_document() {
+        let ts = TestDsl::<Vec<usize>>::new();
 
         let tc = ts
             .parse_testcase(
                 r#"
+            testcase "nested" tags="slow, smoke" {
+                repeat 2 {
+                    group
+                }
+                assert {
+                    not { any }
+                }
+            }
             testcase {
-                add_one
-                add_one
-                mul_two
+                group
             }
             "#,
             )
             .unwrap();
 
-        let mut ah = ArithmeticHarness {
-            value: AtomicUsize::new(0),
-        };
-
-        tc[0].run(&mut ah).unwrap();
+        for testcase in &tc {
+            let document = testcase.to_kdl_document().to_string();
+            let reparsed = ts.parse_testcase(&*document).unwrap();
 
-        assert_eq!(ah.value.load(std::sync::atomic::Ordering::SeqCst), 4);
+            assert_eq!(reparsed.len(), 1);
+            assert_eq!(reparsed[0].name(), testcase.name());
+            assert_eq!(reparsed[0].tags(), testcase.tags());
+            assert_eq!(reparsed[0].to_kdl_document().to_string(), document);
+            reparsed[0].run(&mut vec![]).unwrap();
+        }
     }
 
     #[test]
-    fn repeat_test() {
-        let mut ts = TestDsl::<ArithmeticHarness>::new();
+    fn check_to_kdl_document_keeps_escapes() {
+        let mut ts = TestDsl::<Vec<String>>::new();
+        ts.set_variable("price", "5");
         ts.add_verb(
-            "add_one",
-            FunctionVerb::new(|ah: &mut ArithmeticHarness| {
-                ah.value.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-
+            "push",
+            FunctionVerb::new(|h: &mut Vec<String>, value: String| {
+                h.push(value);
                 Ok(())
             }),
         );
 
-        ts.add_verb(
-            "mul_two",
-            FunctionVerb::new(|ah: &mut ArithmeticHarness| {
-                let value = ah.value.load(std::sync::atomic::Ordering::SeqCst);
-                ah.value
-                    .store(value * 2, std::sync::atomic::Ordering::SeqCst);
+        let tc = ts
+            .parse_testcase(r#"testcase "costs $$${price}" { push "$${price} is $$${price}"; }"#)
+            .unwrap();
+
+        let document = tc[0].to_kdl_document().to_string();
+        assert!(document.contains(r#""$${price} is $$${price}""#));
+
+        let reparsed = ts.parse_testcase(&*document).unwrap();
+        assert_eq!(reparsed[0].name(), Some("costs $5"));
+        assert_eq!(reparsed[0].to_kdl_document().to_string(), document);
+
+        let mut values = vec![];
+        reparsed[0].run(&mut values).unwrap();
+        assert_eq!(values, ["${price} is $5"]);
+    }
+
+    #[test]
+    fn check_instance_nodes() {
+        let ts = TestDsl::<()>::new();
+
+        let node = kdl::KdlNode::parse("repeat 2 { group; }").unwrap();
+        let verb = crate::VerbInstance::with_test_dsl(&ts, &node).unwrap();
+        assert_eq!(verb.node().name().value(), "repeat");
+        assert_eq!(verb.node().entry(0).unwrap().value().as_integer(), Some(2));
+
+        let node = kdl::KdlNode::parse("not { all; }").unwrap();
+        let condition = crate::ConditionInstance::with_test_dsl(&ts, &node).unwrap();
+        assert_eq!(condition.node().name().value(), "not");
+        assert_eq!(condition.node().iter_children().count(), 1);
+    }
+
+    #[test]
+    fn check_testcase_verbs() {
+        let ts = TestDsl::<()>::new();
 
+        let tc = ts
+            .parse_testcase("testcase { group; repeat 2 { group; }; sleep \"1ms\"; }")
+            .unwrap();
+
+        let names = tc[0]
+            .verbs()
+            .iter()
+            .map(|verb| verb.node().name().value())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["group", "repeat", "sleep"]);
+    }
+
+    #[test]
+    fn check_parsing_validates_nested_nodes() {
+        let mut ts = TestDsl::<usize>::new();
+        ts.add_verb(
+            "add",
+            FunctionVerb::new(|h: &mut usize, num: usize| {
+                *h += num;
                 Ok(())
             }),
         );
 
-        let tc = ts
+        let errors = ts
             .parse_testcase(
                 r#"
             testcase {
-                repeat 2 {
+                add 1
+                group {
                     repeat 2 {
-                        add_one
-                        mul_two
+                        add many
+                    }
+                    assert {
+                        not { is_done }
                     }
                 }
             }
             "#,
             )
-            .unwrap();
+            .unwrap_err()
+            .errors;
 
-        let mut ah = ArithmeticHarness {
-            value: AtomicUsize::new(0),
+        assert!(matches!(
+            errors[..],
+            [
+                TestErrorCase::WrongArgumentType { .. },
+                TestErrorCase::UnknownCondition { .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn check_validate_nested_nodes() {
+        #[derive(Debug, Clone)]
+        struct Range(usize, usize);
+
+        impl crate::argument::ParseArguments<usize> for Range {
+            fn parse(
+                test_dsl: &TestDsl<usize>,
+                node: &kdl::KdlNode,
+            ) -> Result<Self, TestErrorCase> {
+                let (min, max) = <(usize, usize)>::parse(test_dsl, node)?;
+                Ok(Range(min, max))
+            }
+
+            fn validate(&self) -> miette::Result<()> {
+                if self.0 > self.1 {
+                    miette::bail!("The range {}..{} is empty", self.0, self.1);
+                }
+
+                Ok(())
+            }
+        }
+
+        #[derive(Debug, Clone)]
+        struct AddRange;
+
+        impl Verb<usize> for AddRange {
+            type Arguments = Range;
+
+            fn run(
+                &self,
+                harness: &mut usize,
+                _context: &mut TestContext,
+                range: &Self::Arguments,
+            ) -> miette::Result<()> {
+                *harness += range.1 - range.0;
+                Ok(())
+            }
+        }
+
+        let mut ts = TestDsl::<usize>::new();
+        ts.add_verb("add_range", AddRange);
+        ts.add_condition(
+            "is_zero",
+            FunctionCondition::new_now(|h: &usize| Ok(*h == 0)),
+        );
+
+        let input = r#"
+            testcase {
+                add_range 1 2
+                group {
+                    repeat 2 {
+                        if {
+                            not { is_zero }
+                            add_range 3 1
+                        }
+                    }
+                }
+            }
+            testcase {
+                group {
+                    add_range 1 3
+                }
+            }
+            "#;
+        let testcases = ts.parse_testcase(input).unwrap();
+
+        let Err(TestError::Error { error, span, .. }) = testcases[0].validate() else {
+            panic!("validating an empty range has to fail");
         };
+        assert_eq!(error.to_string(), "The range 3..1 is empty");
+        assert_eq!(span.offset(), input.find("add_range 3 1").unwrap());
 
-        tc[0].run(&mut ah).unwrap();
+        testcases[1].validate().unwrap();
+    }
 
-        assert_eq!(ah.value.load(std::sync::atomic::Ordering::SeqCst), 30);
+    #[cfg(feature = "async")]
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+
+        loop {
+            if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
     }
 
+    #[cfg(feature = "async")]
     #[test]
-    fn check_arguments_work() {
-        let mut ts = TestDsl::<ArithmeticHarness>::new();
-        ts.add_verb(
-            "add_one",
-            FunctionVerb::new(|ah: &mut ArithmeticHarness| {
-                ah.value.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    fn check_async_verbs() {
+        use crate::verb::AsyncFunctionVerb;
 
-                Ok(())
-            }),
-        );
+        /// Returns pending once before finishing
+        async fn yield_now() {
+            let mut yielded = false;
+            std::future::poll_fn(|cx| {
+                if yielded {
+                    return std::task::Poll::Ready(());
+                }
 
-        ts.add_verb(
-            "add",
-            FunctionVerb::new(|ah: &mut ArithmeticHarness, num: usize| {
-                ah.value.fetch_add(num, std::sync::atomic::Ordering::SeqCst);
-                Ok(())
+                yielded = true;
+                cx.waker().wake_by_ref();
+                std::task::Poll::Pending
+            })
+            .await
+        }
+
+        let mut ts = TestDsl::<Vec<usize>>::new();
+        ts.add_async_verb(
+            "push",
+            AsyncFunctionVerb::new(|h: &mut Vec<usize>, (value,): (usize,)| {
+                Box::pin(async move {
+                    yield_now().await;
+                    h.push(value);
+                    Ok(())
+                })
             }),
         );
-
-        ts.add_verb(
-            "mul_two",
-            FunctionVerb::new(|ah: &mut ArithmeticHarness| {
-                let value = ah.value.load(std::sync::atomic::Ordering::SeqCst);
-                ah.value
-                    .store(value * 2, std::sync::atomic::Ordering::SeqCst);
-                Ok(())
+        ts.add_async_verb(
+            "explode",
+            AsyncFunctionVerb::new(|_: &mut Vec<usize>, ((),): ((),)| {
+                Box::pin(async move {
+                    yield_now().await;
+                    panic!("boom")
+                })
             }),
         );
 
@@ -580,25 +5393,102 @@ mod tests {
             .parse_testcase(
                 r#"
             testcase {
+                push 1
                 repeat 2 {
-                    repeat 2 {
-                        group {
-                            add 2
-                            mul_two
-                        }
-                    }
+                    group { push 2; }
                 }
             }
+            testcase {
+                explode
+            }
             "#,
             )
             .unwrap();
 
-        let mut ah = ArithmeticHarness {
-            value: AtomicUsize::new(0),
+        let mut pushed = vec![];
+        block_on(tc[0].run_async(&mut pushed)).unwrap();
+        assert_eq!(pushed, [1, 2, 2]);
+
+        let error = tc[0].run(&mut vec![]).unwrap_err();
+        let TestError::Error { error, .. } = error.error else {
+            panic!("Expected an error: {error:?}");
         };
+        assert!(error.downcast_ref::<crate::error::AsyncOnly>().is_some());
 
-        tc[0].run(&mut ah).unwrap();
+        let error = block_on(tc[1].run_async(&mut vec![])).unwrap_err();
+        assert!(matches!(error.error, TestError::Panic { .. }));
+    }
 
-        assert_eq!(ah.value.load(std::sync::atomic::Ordering::SeqCst), 60);
+    #[cfg(feature = "async")]
+    #[test]
+    fn check_async_conditions() {
+        use std::sync::mpsc;
+
+        #[derive(Debug, Clone)]
+        struct Received;
+
+        impl crate::condition::AsyncCondition<mpsc::Receiver<usize>> for Received {
+            type Arguments = (usize,);
+
+            async fn wait_until(
+                &self,
+                messages: &mpsc::Receiver<usize>,
+                _context: &TestContext,
+                (expected,): &Self::Arguments,
+            ) -> miette::Result<bool> {
+                std::future::poll_fn(|cx| match messages.try_recv() {
+                    Ok(message) => std::task::Poll::Ready(Ok(message == *expected)),
+                    Err(_) => {
+                        cx.waker().wake_by_ref();
+                        std::task::Poll::Pending
+                    }
+                })
+                .await
+            }
+        }
+
+        let mut ts = TestDsl::<mpsc::Receiver<usize>>::new();
+        ts.add_async_condition("received", Received);
+
+        let tc = ts
+            .parse_testcase(
+                r#"
+            testcase {
+                wait_for timeout="5s" interval="1ms" {
+                    received 2
+                }
+            }
+            testcase {
+                wait_for timeout="20ms" {
+                    received 1
+                }
+            }
+            "#,
+            )
+            .unwrap();
+
+        let (sender, mut receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for message in 1..=2 {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                sender.send(message).unwrap();
+            }
+        });
+        block_on(tc[0].run_async(&mut receiver)).unwrap();
+
+        let (_sender, mut receiver) = mpsc::channel();
+        let error = block_on(tc[1].run_async(&mut receiver)).unwrap_err();
+        assert!(matches!(error.error, TestError::Timeout { .. }));
+
+        let error = tc[1].run(&mut receiver).unwrap_err();
+        assert!(matches!(error.error, TestError::Error { .. }));
+
+        let error = ts
+            .parse_testcase("testcase { assert { received 1 } }")
+            .unwrap_err();
+        assert!(matches!(
+            error.errors(),
+            [TestErrorCase::UnsupportedCondition { .. }]
+        ));
     }
 }
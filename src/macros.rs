@@ -25,7 +25,6 @@ macro_rules! all_the_tuples {
 /// This can then be used in your custom [`Verb`](crate::Verb) or [`Condition`](crate::condition::Condition) implementations.
 ///
 /// **Note:** The definition uses `=` instead of the usual `:` to delimit fields and their types.
-/// This is on purpose, as this may later be expanded to allow for positional arguments as well.
 ///
 /// ```
 /// use test_dsl::named_parameters;
@@ -37,28 +36,210 @@ macro_rules! all_the_tuples {
 ///     }
 /// }
 /// ```
+///
+/// Fields marked with `#[child]` are not read from a property, but from the child node with the
+/// same name. Their type has to implement [`ParseArguments`](crate::argument::ParseArguments)
+/// itself, for example by being defined with this macro as well:
+///
+/// ```
+/// use test_dsl::named_parameters;
+///
+/// named_parameters! {
+///     Headers {
+///         accept = String
+///     }
+/// }
+///
+/// // Parses `request url="/" { headers accept="text/html" }`
+/// named_parameters! {
+///     Request {
+///         url = String,
+///         #[child] headers = Headers,
+///     }
+/// }
+/// ```
+///
+/// A field can be given a default with a second `=`, which is used if the key is missing. The
+/// default is an expression of the type of the field:
+///
+/// ```
+/// use test_dsl::named_parameters;
+///
+/// // Parses both `connect host="db"` and `connect host="db" port=5433`
+/// named_parameters! {
+///     Connect {
+///         host = String,
+///         port = u16 = 5432,
+///         user = String = String::from("postgres"),
+///     }
+/// }
+/// ```
+///
+/// The key defaults to the name of the field. A different key, for example one that is not a
+/// valid Rust identifier, can be given with `as`:
+///
+/// ```
+/// use test_dsl::named_parameters;
+///
+/// // Parses `schedule set-timeout=5`
+/// named_parameters! {
+///     Schedule {
+///         set_timeout as "set-timeout" = usize,
+///     }
+/// }
+/// ```
+///
+/// Fields marked with `#[positional]` are read from the arguments of the node instead, in the
+/// order they are declared. They can be mixed freely with named fields:
+///
+/// ```
+/// use test_dsl::named_parameters;
+///
+/// // Parses `move 3 4 speed=2` as well as `move 3 4`
+/// named_parameters! {
+///     Move {
+///         #[positional] x = i64,
+///         #[positional] y = i64,
+///         speed = usize = 1,
+///     }
+/// }
+/// ```
 #[macro_export]
 macro_rules! named_parameters {
-    ( $vis:vis $param_name:ident { $($key:ident = $value:ty),* $(,)? }) => {
+    ( $vis:vis $param_name:ident { $($(#[$kind:ident])? $key:ident $(as $rename:literal)? = $value:ty $(= $default:expr)?),* $(,)? }) => {
         #[derive(Debug, Clone)]
         $vis struct $param_name {
             $($key: $value),*
         }
 
         impl<H> $crate::argument::ParseArguments<H> for $param_name {
-            fn parse(_: &$crate::TestDsl<H>, node: &$crate::kdl::KdlNode) -> Result<Self, $crate::error::TestErrorCase> {
+            fn parse(_test_dsl: &$crate::TestDsl<H>, node: &$crate::kdl::KdlNode) -> Result<Self, $crate::error::TestErrorCase> {
+                let positional_count: usize = 0 $(+ $crate::__positional_count!($($kind)?))*;
+                #[allow(unused_mut, unused_variables)]
+                let mut position = 0;
+                let mut args = $crate::argument::positional_entries(node).peekable();
+
                 $(
-                    let $key: $value = $crate::argument::VerbArgument::from_value(node.entry(stringify!($key)).unwrap()).unwrap();
+                    let $key: $value = $crate::__named_parameter!(
+                        _test_dsl,
+                        node,
+                        args,
+                        positional_count,
+                        position,
+                        $($kind)? ($crate::__parameter_key!($key $(as $rename)?)) $(= $default)?
+                    );
                 )*
 
+                $crate::argument::parse_end(&mut args, positional_count)?;
+
                 Ok($param_name {
                     $(
                         $key
                     ),*
                 })
             }
+
+            fn signature() -> Option<$crate::argument::Signature> {
+                let positional: Vec<Option<(&'static str, bool)>> = vec![
+                    $($crate::__positional_signature!($($kind)? $value $(= $default)?)),*
+                ];
+                let positional: Vec<_> = positional.into_iter().flatten().collect();
+                let required = positional.iter().filter(|(_, required)| *required).count();
+
+                Some(
+                    $crate::argument::Signature::new(positional.into_iter().map(|(name, _)| name).collect())
+                        .with_required_arguments(required),
+                )
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __parameter_key {
+    ($key:ident as $rename:literal) => {
+        $rename
+    };
+    ($key:ident) => {
+        stringify!($key)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __positional_count {
+    (positional) => {
+        1
+    };
+    ($($kind:ident)?) => {
+        0
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __positional_signature {
+    (positional $value:ty = $default:expr) => {
+        Some((
+            <$value as $crate::argument::VerbArgument>::get_error_type_name(),
+            false,
+        ))
+    };
+    (positional $value:ty) => {
+        Some((
+            <$value as $crate::argument::VerbArgument>::get_error_type_name(),
+            <$value as $crate::argument::VerbArgument>::from_missing().is_none(),
+        ))
+    };
+    (child $value:ty $(= $default:expr)?) => {
+        None
+    };
+    ($value:ty $(= $default:expr)?) => {
+        None
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __named_parameter {
+    ($test_dsl:ident, $node:ident, $args:ident, $count:ident, $position:ident, positional $name:tt = $default:expr) => {{
+        $position += 1;
+
+        if $args.peek().is_some() {
+            $crate::argument::VerbArgument::parse_positional($node, &mut $args, $count, $position)?
+        } else {
+            $default
+        }
+    }};
+    ($test_dsl:ident, $node:ident, $args:ident, $count:ident, $position:ident, positional $name:tt) => {{
+        $position += 1;
+
+        $crate::argument::VerbArgument::parse_positional($node, &mut $args, $count, $position)?
+    }};
+    ($test_dsl:ident, $node:ident, $args:ident, $count:ident, $position:ident, child $name:tt = $default:expr) => {
+        if $node
+            .iter_children()
+            .any(|child| child.name().value() == $name)
+        {
+            $crate::argument::parse_named_child($test_dsl, $node, $name)?
+        } else {
+            $default
+        }
+    };
+    ($test_dsl:ident, $node:ident, $args:ident, $count:ident, $position:ident, $name:tt = $default:expr) => {
+        if $node.entry($name).is_some() {
+            $crate::argument::parse_named($node, $name)?
+        } else {
+            $default
         }
     };
+    ($test_dsl:ident, $node:ident, $args:ident, $count:ident, $position:ident, child $name:tt) => {
+        $crate::argument::parse_named_child($test_dsl, $node, $name)?
+    };
+    ($test_dsl:ident, $node:ident, $args:ident, $count:ident, $position:ident, $name:tt) => {
+        $crate::argument::parse_named($node, $name)?
+    };
 }
 
 #[macro_export]
@@ -73,7 +254,7 @@ macro_rules! named_parameters_verb {
 #[doc(hidden)]
 #[macro_export]
 macro_rules! __inner_named_parameters_verb {
-    (@impl { |$name:ident : &mut $ty:ty $(, $param_name:ident : $param_type:ty)* $(,)?| $rest:block }) => {{
+    (@impl { |$name:ident : &mut $ty:ty $(, $param_name:ident $(as $rename:literal)? : $param_type:ty)* $(,)?| $rest:block }) => {{
         #[derive(Debug, Clone)]
         struct __NamedVerb {
             $($param_name : $param_type),*
@@ -104,7 +285,10 @@ macro_rules! __inner_named_parameters_verb {
         impl<H> $crate::argument::ParseArguments<H> for __NamedVerb {
             fn parse(_: &$crate::TestDsl<H>, node: &$crate::kdl::KdlNode) -> Result<Self, $crate::error::TestErrorCase> {
                 $(
-                    let $param_name: $param_type = $crate::argument::VerbArgument::from_value(node.entry(stringify!($param_name)).unwrap()).unwrap();
+                    let $param_name: $param_type = $crate::argument::parse_named(
+                        node,
+                        $crate::__parameter_key!($param_name $(as $rename)?),
+                    )?;
                 )*
 
                 Ok({
@@ -121,6 +305,8 @@ macro_rules! __inner_named_parameters_verb {
 
 /// Define a verb using a closure, where the argument names are used as the key names
 ///
+/// Like with [`named_parameters!`], an argument can use a different key with `as`.
+///
 /// ```
 /// # use test_dsl::{TestDsl, named_parameters_verb};
 /// let mut dsl = TestDsl::<()>::new();
@@ -132,6 +318,15 @@ macro_rules! __inner_named_parameters_verb {
 ///         Ok(())
 ///     }),
 /// );
+///
+/// // Used as `say text="Hello"`
+/// dsl.add_verb(
+///     "say",
+///     named_parameters_verb!(|_harness: &mut (), content as "text": String| {
+///         println!("{content}");
+///         Ok(())
+///     }),
+/// );
 /// ```
 #[cfg(doc)]
 #[macro_export]
@@ -143,6 +338,7 @@ macro_rules! named_parameters_verb {
 mod tests {
     use crate::TestDsl;
     use crate::argument::ParseArguments;
+    use crate::error::TestErrorCase;
 
     #[test]
     fn simple_kv() {
@@ -159,6 +355,169 @@ mod tests {
 
         assert_eq!(ints.pi, 4);
         assert_eq!(ints.name, "PI");
+
+        let node = kdl::KdlNode::parse("foo pi=notanumber name=PI").unwrap();
+        assert!(matches!(
+            CoolIntegers::parse(&dsl, &node),
+            Err(TestErrorCase::WrongArgumentType { .. })
+        ));
+
+        let node = kdl::KdlNode::parse("foo name=PI").unwrap();
+        assert!(matches!(
+            CoolIntegers::parse(&dsl, &node),
+            Err(TestErrorCase::MissingArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn nested_kv() {
+        named_parameters!(Headers { accept = String });
+
+        named_parameters!(Request {
+            url = String,
+            #[child]
+            headers = Headers,
+        });
+
+        let dsl = TestDsl::<()>::new();
+
+        let node =
+            kdl::KdlNode::parse(r#"request url="/" { headers accept="text/html" }"#).unwrap();
+
+        let request = Request::parse(&dsl, &node).unwrap();
+
+        assert_eq!(request.url, "/");
+        assert_eq!(request.headers.accept, "text/html");
+
+        let node = kdl::KdlNode::parse(r#"request url="/""#).unwrap();
+        assert!(matches!(
+            Request::parse(&dsl, &node),
+            Err(TestErrorCase::MissingArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn default_kv() {
+        named_parameters!(Connect {
+            host = String,
+            port = u16 = 5432,
+            user = String = String::from("postgres"),
+        });
+
+        let dsl = TestDsl::<()>::new();
+
+        let node = kdl::KdlNode::parse(r#"connect host="db""#).unwrap();
+        let connect = Connect::parse(&dsl, &node).unwrap();
+
+        assert_eq!(connect.host, "db");
+        assert_eq!(connect.port, 5432);
+        assert_eq!(connect.user, "postgres");
+
+        let node = kdl::KdlNode::parse(r#"connect host="db" port=5433 user=admin"#).unwrap();
+        let connect = Connect::parse(&dsl, &node).unwrap();
+
+        assert_eq!(connect.port, 5433);
+        assert_eq!(connect.user, "admin");
+
+        let node = kdl::KdlNode::parse(r#"connect host="db" port=notanumber"#).unwrap();
+        assert!(matches!(
+            Connect::parse(&dsl, &node),
+            Err(TestErrorCase::WrongArgumentType { .. })
+        ));
+
+        let node = kdl::KdlNode::parse("connect port=5433").unwrap();
+        assert!(matches!(
+            Connect::parse(&dsl, &node),
+            Err(TestErrorCase::MissingArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn positional_kv() {
+        named_parameters!(Move {
+            #[positional]
+            x = i64,
+            speed = usize = 1,
+            #[positional]
+            y = i64 = 0,
+        });
+
+        let dsl = TestDsl::<()>::new();
+
+        let node = kdl::KdlNode::parse("move 3 speed=2 -4").unwrap();
+        let step = Move::parse(&dsl, &node).unwrap();
+
+        assert_eq!((step.x, step.y, step.speed), (3, -4, 2));
+
+        let node = kdl::KdlNode::parse("move 3").unwrap();
+        let step = Move::parse(&dsl, &node).unwrap();
+
+        assert_eq!((step.x, step.y, step.speed), (3, 0, 1));
+
+        let node = kdl::KdlNode::parse("move speed=2").unwrap();
+        assert!(matches!(
+            Move::parse(&dsl, &node),
+            Err(TestErrorCase::MissingArgument { .. })
+        ));
+
+        let node = kdl::KdlNode::parse("move 1 2 3").unwrap();
+        assert!(matches!(
+            Move::parse(&dsl, &node),
+            Err(TestErrorCase::UnexpectedArgument { .. })
+        ));
+
+        let signature = <Move as ParseArguments<()>>::signature().unwrap();
+        assert_eq!(signature.arguments(), ["i64", "i64"]);
+        assert_eq!(signature.required_arguments(), 1);
+    }
+
+    #[test]
+    fn renamed_kv() {
+        named_parameters!(Schedule {
+            set_timeout as "set-timeout" = usize,
+            #[child]
+            on_fail as "on-fail" = Action,
+        });
+
+        named_parameters!(Action { content as "text" = String = String::new() });
+
+        let dsl = TestDsl::<()>::new();
+
+        let node =
+            kdl::KdlNode::parse(r#"schedule set-timeout=5 { on-fail text="retry" }"#).unwrap();
+        let schedule = Schedule::parse(&dsl, &node).unwrap();
+
+        assert_eq!(schedule.set_timeout, 5);
+        assert_eq!(schedule.on_fail.content, "retry");
+
+        let node = kdl::KdlNode::parse("schedule set_timeout=5 { on-fail }").unwrap();
+        assert!(matches!(
+            Schedule::parse(&dsl, &node),
+            Err(TestErrorCase::MissingArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn renamed_named_closure() {
+        let mut dsl = TestDsl::<Vec<String>>::new();
+
+        dsl.add_verb(
+            "say",
+            named_parameters_verb!(|harness: &mut Vec<String>, content as "text": String| {
+                harness.push(content);
+                Ok(())
+            }),
+        );
+
+        let testcases = dsl.parse_testcase(r#"testcase { say text="Hi" }"#).unwrap();
+        let mut said = vec![];
+        testcases[0].run(&mut said).unwrap();
+        assert_eq!(said, ["Hi"]);
+
+        assert!(
+            dsl.parse_testcase(r#"testcase { say content="Hi" }"#)
+                .is_err()
+        );
     }
 
     #[test]
@@ -204,4 +563,21 @@ mod tests {
                                     _pi24: usize| { Ok(()) }),
         );
     }
+
+    #[test]
+    fn named_closure_missing_key() {
+        let mut dsl = TestDsl::<()>::new();
+
+        dsl.add_verb(
+            "greet",
+            named_parameters_verb!(|_harness: &mut (), name: String| {
+                println!("Hello {name}");
+                Ok(())
+            }),
+        );
+
+        assert!(dsl.parse_testcase("testcase { greet name=World }").is_ok());
+        assert!(dsl.parse_testcase("testcase { greet }").is_err());
+        assert!(dsl.parse_testcase("testcase { greet name=#true }").is_err());
+    }
 }
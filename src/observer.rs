@@ -0,0 +1,127 @@
+//! Observing the verbs of a testcase as they run
+//!
+//! A [`RunObserver`] set with [`TestDsl::set_observer`](crate::TestDsl::set_observer) is told
+//! about every verb that is run by the runners of the [`TestDsl`](crate::TestDsl), including the
+//! verbs nested in other verbs.
+//!
+//! ```
+//! # use test_dsl::error::TestError;
+//! # use test_dsl::observer::RunObserver;
+//! # use test_dsl::observer::VerbEvent;
+//! struct Logger;
+//!
+//! impl RunObserver for Logger {
+//!     fn on_verb_start(&self, verb: &VerbEvent<'_>) {
+//!         println!("running `{}` at line {}", verb.name(), verb.line());
+//!     }
+//!
+//!     fn on_verb_end(&self, verb: &VerbEvent<'_>, result: Result<(), &TestError>) {
+//!         println!("`{}` finished: {}", verb.name(), result.is_ok());
+//!     }
+//! }
+//!
+//! let mut dsl = test_dsl::TestDsl::<()>::new();
+//! dsl.set_observer(Logger);
+//!
+//! let testcases = dsl.parse_testcase("testcase { repeat 2 { group } }").unwrap();
+//! dsl.run_testcase(&testcases[0], &mut ()).unwrap();
+//! ```
+
+use std::rc::Rc;
+
+use crate::TestCaseInput;
+use crate::error::TestError;
+
+/// Gets notified about each verb that is run
+///
+/// Both methods do nothing by default.
+pub trait RunObserver {
+    /// Called right before the verb runs
+    fn on_verb_start(&self, verb: &VerbEvent<'_>) {
+        let _ = verb;
+    }
+
+    /// Called after the verb ran, with its result
+    fn on_verb_end(&self, verb: &VerbEvent<'_>, result: Result<(), &TestError>) {
+        let _ = (verb, result);
+    }
+}
+
+/// A verb that is being run
+#[derive(Debug, Clone, Copy)]
+pub struct VerbEvent<'a> {
+    node: &'a kdl::KdlNode,
+    source_code: &'a TestCaseInput,
+}
+
+impl VerbEvent<'_> {
+    /// The name of the verb, as written in the testcase
+    pub fn name(&self) -> &str {
+        self.node.name().value()
+    }
+
+    /// The location of the verb node
+    pub fn span(&self) -> miette::SourceSpan {
+        self.node.span()
+    }
+
+    /// The path of the file the testcase was read from, if any
+    pub fn path(&self) -> Option<&str> {
+        self.source_code.filepath().map(|filepath| &**filepath)
+    }
+
+    /// The line of the verb node, starting at 1
+    pub fn line(&self) -> usize {
+        self.location().0
+    }
+
+    /// The column of the verb node, starting at 1
+    pub fn column(&self) -> usize {
+        self.location().1
+    }
+
+    fn location(&self) -> (usize, usize) {
+        self.source_code
+            .line_column(self.node.span())
+            .unwrap_or((0, 0))
+    }
+}
+
+/// A [`RunObserver`] together with the source of the testcase it observes
+#[derive(Clone)]
+pub(crate) struct ObserverHandle {
+    observer: Rc<dyn RunObserver>,
+    source_code: TestCaseInput,
+}
+
+impl std::fmt::Debug for ObserverHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObserverHandle").finish_non_exhaustive()
+    }
+}
+
+impl ObserverHandle {
+    pub(crate) fn new(observer: Rc<dyn RunObserver>, source_code: TestCaseInput) -> Self {
+        ObserverHandle {
+            observer,
+            source_code,
+        }
+    }
+
+    pub(crate) fn start(&self, node: &kdl::KdlNode) {
+        self.observer.on_verb_start(&VerbEvent {
+            node,
+            source_code: &self.source_code,
+        });
+    }
+
+    pub(crate) fn end(&self, node: &kdl::KdlNode, result: Result<(), &TestError>) {
+        self.observer.on_verb_end(
+            &VerbEvent {
+                node,
+                source_code: &self.source_code,
+            },
+            result,
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Turning panics of verbs and conditions into errors
+
+use std::backtrace::Backtrace;
+use std::backtrace::BacktraceStatus;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use crate::error::PanicError;
+
+thread_local! {
+    /// How many calls to [`catch`] are active on this thread
+    static CATCHING: Cell<usize> = const { Cell::new(0) };
+
+    /// The location and backtrace of the last panic caught on this thread
+    static LAST_PANIC: RefCell<Option<(Option<String>, Option<String>)>> = const { RefCell::new(None) };
+}
+
+type Hook = Box<dyn Fn(&std::panic::PanicHookInfo<'_>) + Sync + Send + 'static>;
+
+/// How many calls to [`catch`] are active on all threads, and the panic hook that was installed
+/// before the first of them started
+static ACTIVE: Mutex<(usize, Option<Arc<Hook>>)> = Mutex::new((0, None));
+
+/// Install a panic hook that records panics happening inside of [`catch`], unless it is already
+/// installed
+///
+/// All other panics are passed on to the hook that was installed before, which
+/// [`uninstall_hook`] puts back once no call to [`catch`] is active anymore.
+fn install_hook() {
+    let mut active = ACTIVE.lock().unwrap_or_else(PoisonError::into_inner);
+
+    active.0 += 1;
+    if active.0 > 1 {
+        return;
+    }
+
+    let previous = Arc::new(std::panic::take_hook());
+    active.1 = Some(previous.clone());
+
+    std::panic::set_hook(Box::new(move |info| {
+        if CATCHING.get() == 0 {
+            return previous(info);
+        }
+
+        let location = info.location().map(ToString::to_string);
+        let backtrace = Backtrace::capture();
+        let backtrace =
+            (backtrace.status() == BacktraceStatus::Captured).then(|| backtrace.to_string());
+
+        LAST_PANIC.set(Some((location, backtrace)));
+    }));
+}
+
+/// Put back the panic hook that was installed before [`install_hook`], if this was the last
+/// active call to [`catch`]
+fn uninstall_hook() {
+    let mut active = ACTIVE.lock().unwrap_or_else(PoisonError::into_inner);
+
+    active.0 -= 1;
+    if active.0 > 0 {
+        return;
+    }
+
+    // Dropping our hook releases its reference to the previous one
+    drop(std::panic::take_hook());
+
+    if let Some(previous) = active.1.take() {
+        match Arc::try_unwrap(previous) {
+            Ok(previous) => std::panic::set_hook(previous),
+            Err(previous) => std::panic::set_hook(Box::new(move |info| previous(info))),
+        }
+    }
+}
+
+/// Run `f`, catching a panic as a [`PanicError`]
+///
+/// The error carries the panic message, the location of the panic and, if enabled through
+/// `RUST_BACKTRACE`, its backtrace. The panic hook is only replaced while `f` runs.
+pub(crate) fn catch<T>(f: impl FnOnce() -> T) -> Result<T, PanicError> {
+    install_hook();
+
+    CATCHING.set(CATCHING.get() + 1);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    CATCHING.set(CATCHING.get() - 1);
+
+    uninstall_hook();
+
+    result.map_err(|payload| {
+        let mut message = "Something went wrong".to_string();
+
+        if let Some(msg) = payload.downcast_ref::<&str>() {
+            message = msg.to_string();
+        }
+
+        if let Some(msg) = payload.downcast_ref::<String>() {
+            message.clone_from(msg);
+        }
+
+        let (location, backtrace) = LAST_PANIC.take().unwrap_or_default();
+
+        PanicError::new(message, location, backtrace)
+    })
+}
+
+/// Await `future`, catching a panic in any of its polls as a [`PanicError`]
+#[cfg(feature = "async")]
+pub(crate) async fn catch_future<F: Future>(future: F) -> Result<F::Output, PanicError> {
+    let mut future = std::pin::pin!(future);
+
+    std::future::poll_fn(|cx| match catch(|| future.as_mut().poll(cx)) {
+        Ok(std::task::Poll::Ready(output)) => std::task::Poll::Ready(Ok(output)),
+        Ok(std::task::Poll::Pending) => std::task::Poll::Pending,
+        Err(error) => std::task::Poll::Ready(Err(error)),
+    })
+    .await
+}
@@ -0,0 +1,172 @@
+//! Producers observe a value, so that it can be asserted on later
+//!
+//! Contrary to a [`Condition`](crate::condition::Condition), which only answers 'yes' or 'no', a
+//! [`Producer`] returns what it has observed. The built-in `measure` verb runs a producer and
+//! stores its value in the [`TestContext`] under the name given as its `capture` property:
+//!
+//! ```kdl
+//! testcase {
+//!     measure latency capture=l
+//!     assert {
+//!         lt l 100
+//!     }
+//! }
+//! ```
+
+use std::any::Any;
+
+use crate::TestDsl;
+use crate::argument::ParseArguments;
+use crate::argument::VerbArgument;
+use crate::context::TestContext;
+use crate::error::TestErrorCase;
+
+/// Something that observes a value of type `T` of the harness
+///
+/// It is implemented for closures of the form `Fn(&H) -> miette::Result<T>`.
+pub trait Producer<H, T>: Clone + 'static {
+    /// Observe the value
+    fn produce(&self, harness: &H) -> miette::Result<T>;
+}
+
+impl<H, T, F> Producer<H, T> for F
+where
+    F: Fn(&H) -> miette::Result<T>,
+    F: Clone + 'static,
+{
+    fn produce(&self, harness: &H) -> miette::Result<T> {
+        self(harness)
+    }
+}
+
+pub(crate) struct ErasedProducer<H> {
+    producer: Box<dyn Any>,
+    fn_capture: fn(&dyn Any, &H, &mut TestContext, &str) -> miette::Result<()>,
+    fn_clone: fn(&dyn Any) -> Box<dyn Any>,
+}
+
+impl<H> std::fmt::Debug for ErasedProducer<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ErasedProducer")
+            .field("producer", &self.producer)
+            .field("fn_capture", &self.fn_capture)
+            .field("fn_clone", &self.fn_clone)
+            .finish()
+    }
+}
+
+impl<H> Clone for ErasedProducer<H> {
+    fn clone(&self) -> Self {
+        Self {
+            producer: (self.fn_clone)(&*self.producer),
+            fn_capture: self.fn_capture,
+            fn_clone: self.fn_clone,
+        }
+    }
+}
+
+impl<H> ErasedProducer<H> {
+    pub(crate) fn erase<P, T>(producer: P) -> Self
+    where
+        P: Producer<H, T>,
+        T: 'static,
+    {
+        ErasedProducer {
+            producer: Box::new(producer),
+            fn_capture: |this, harness, context, name| {
+                let this: &P = this.downcast_ref().unwrap();
+
+                let value = this.produce(harness)?;
+                context.set(name, value);
+
+                Ok(())
+            },
+            fn_clone: |this| {
+                let this: &P = this.downcast_ref().unwrap();
+
+                Box::new(this.clone())
+            },
+        }
+    }
+
+    fn capture(&self, harness: &H, context: &mut TestContext, name: &str) -> miette::Result<()> {
+        (self.fn_capture)(&*self.producer, harness, context, name)
+    }
+}
+
+/// The arguments of the built-in `measure` verb
+pub(crate) struct MeasureArguments<H> {
+    producer: ErasedProducer<H>,
+    capture: String,
+}
+
+impl<H> std::fmt::Debug for MeasureArguments<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MeasureArguments")
+            .field("producer", &self.producer)
+            .field("capture", &self.capture)
+            .finish()
+    }
+}
+
+impl<H> Clone for MeasureArguments<H> {
+    fn clone(&self) -> Self {
+        Self {
+            producer: self.producer.clone(),
+            capture: self.capture.clone(),
+        }
+    }
+}
+
+impl<H: 'static> ParseArguments<H> for MeasureArguments<H> {
+    fn parse(test_dsl: &TestDsl<H>, node: &kdl::KdlNode) -> Result<Self, TestErrorCase> {
+        let mut args = crate::argument::positional_entries(node);
+
+        let name_entry = args.clone().next();
+        let name = <String as VerbArgument>::parse_positional(node, &mut args, 1, 1)?;
+        crate::argument::parse_end(&mut args, 1)?;
+
+        let producer =
+            test_dsl
+                .get_producer(&name)
+                .ok_or_else(|| TestErrorCase::UnknownProducer {
+                    producer: name_entry.map_or(node.span(), |entry| entry.span()),
+                })?;
+
+        let capture = node
+            .entry("capture")
+            .ok_or_else(|| TestErrorCase::MissingArgument {
+                parent: node.span(),
+                missing: String::from(
+                    "This verb needs a `capture` property naming the measured value.",
+                ),
+            })?;
+        let capture = crate::argument::parse_entry(node, capture)?;
+
+        Ok(MeasureArguments { producer, capture })
+    }
+
+    fn signature() -> Option<crate::argument::Signature> {
+        Some(crate::argument::Signature::new(vec![
+            <String as VerbArgument>::get_error_type_name(),
+        ]))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Measure;
+
+impl<H: 'static> crate::verb::Verb<H> for Measure {
+    type Arguments = MeasureArguments<H>;
+
+    fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        arguments
+            .producer
+            .capture(harness, context, &arguments.capture)
+    }
+}
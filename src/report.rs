@@ -0,0 +1,266 @@
+//! Reports of testcase runs in formats understood by other tools
+//!
+//! The functions in this module take the testcases together with the
+//! [outcomes](crate::test_case::TestOutcome) of running them, for example by zipping them:
+//!
+//! ```
+//! let dsl = test_dsl::TestDsl::<()>::new();
+//!
+//! let testcases = dsl
+//!     .parse_testcase(r#"testcase "works" { group }"#)
+//!     .unwrap();
+//!
+//! let results = test_dsl::test_case::run_all(&testcases, &mut ());
+//!
+//! let xml = test_dsl::report::to_junit_xml(testcases.iter().zip(&results));
+//! assert!(xml.contains(r#"<testcase name="works" />"#));
+//!
+//! let tap = test_dsl::report::to_tap(testcases.iter().zip(&results));
+//! assert!(tap.contains("ok 1 - works"));
+//! ```
+//!
+//! Testcases without a [`name`](TestCase::name) are named after their position, starting at 1.
+
+use std::fmt::Write;
+
+use crate::TestCaseInput;
+use crate::error::TestError;
+use crate::test_case::TestCase;
+use crate::test_case::TestCaseError;
+use crate::test_case::TestOutcome;
+
+/// Render the results as a JUnit XML `<testsuite>`
+///
+/// Every testcase becomes a `<testcase>` element. Failed testcases contain a `<failure>` with the
+/// rendered error, including the location in the source where it occurred, and the seed of the
+/// failed run as a `seed` property. Skipped testcases contain a `<skipped>` element instead.
+pub fn to_junit_xml<'a, H: 'static>(
+    results: impl IntoIterator<Item = (&'a TestCase<H>, &'a TestOutcome)>,
+) -> String {
+    let mut tests = 0;
+    let mut failures = 0;
+    let mut skipped = 0;
+    let mut body = String::new();
+
+    for (index, (testcase, result)) in results.into_iter().enumerate() {
+        tests += 1;
+
+        write!(
+            body,
+            r#"  <testcase name="{}""#,
+            escape_xml(&display_name(testcase, index))
+        )
+        .unwrap();
+
+        if let Some(path) = testcase.path() {
+            write!(body, r#" classname="{}""#, escape_xml(path)).unwrap();
+        }
+
+        match result {
+            TestOutcome::Passed(_) => body.push_str(" />\n"),
+            TestOutcome::Skipped { reason, .. } => {
+                skipped += 1;
+
+                match reason {
+                    Some(reason) => writeln!(
+                        body,
+                        ">\n    <skipped message=\"{}\" />\n  </testcase>",
+                        escape_xml(reason)
+                    ),
+                    None => writeln!(body, ">\n    <skipped />\n  </testcase>"),
+                }
+                .unwrap();
+            }
+            TestOutcome::Failed(error, _) => {
+                failures += 1;
+
+                writeln!(
+                    body,
+                    ">\n    <properties>\n      \
+                     <property name=\"seed\" value=\"{}\" />\n    </properties>",
+                    error.seed()
+                )
+                .unwrap();
+                writeln!(
+                    body,
+                    "    <failure message=\"{}\">{}</failure>\n  </testcase>",
+                    escape_xml(&error.to_string()),
+                    escape_xml(&render(error))
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <testsuite name=\"test-dsl\" tests=\"{tests}\" failures=\"{failures}\" \
+         skipped=\"{skipped}\">\n\
+         {body}</testsuite>\n"
+    )
+}
+
+/// Render the results in the [Test Anything Protocol](https://testanything.org)
+///
+/// Every testcase becomes an `ok` or `not ok` line. Failed testcases are followed by a YAML block
+/// with the error message, the location of the node that failed and the seed of the failed run.
+/// Skipped testcases are `ok` lines with a `SKIP` directive.
+pub fn to_tap<'a, H: 'static>(
+    results: impl IntoIterator<Item = (&'a TestCase<H>, &'a TestOutcome)>,
+) -> String {
+    let mut tests = 0;
+    let mut body = String::new();
+
+    for (index, (testcase, result)) in results.into_iter().enumerate() {
+        tests += 1;
+
+        let name = display_name(testcase, index).replace('#', "\\#");
+
+        let error = match result {
+            TestOutcome::Passed(_) => {
+                writeln!(body, "ok {tests} - {name}").unwrap();
+                continue;
+            }
+            TestOutcome::Skipped { reason, .. } => {
+                match reason {
+                    Some(reason) => writeln!(
+                        body,
+                        "ok {tests} - {name} # SKIP {}",
+                        reason.replace('\n', " ")
+                    ),
+                    None => writeln!(body, "ok {tests} - {name} # SKIP"),
+                }
+                .unwrap();
+                continue;
+            }
+            TestOutcome::Failed(error, _) => error,
+        };
+
+        writeln!(body, "not ok {tests} - {name}").unwrap();
+        body.push_str("  ---\n");
+
+        let mut message = error.error.to_string();
+        let mut cause = miette::Diagnostic::diagnostic_source(&error.error);
+        while let Some(inner) = cause {
+            write!(message, ": {inner}").unwrap();
+            cause = inner.diagnostic_source();
+        }
+        writeln!(body, "  message: {}", quote_yaml(&message)).unwrap();
+
+        writeln!(body, "  at:").unwrap();
+        if let Some(path) = testcase.path() {
+            writeln!(body, "    file: {}", quote_yaml(path)).unwrap();
+        }
+
+        let span = error.error.span();
+        writeln!(body, "    offset: {}", span.offset()).unwrap();
+        writeln!(body, "    length: {}", span.len()).unwrap();
+        if let Some((line, column)) = error.error.location(&error.source_code) {
+            writeln!(body, "    line: {line}").unwrap();
+            writeln!(body, "    column: {column}").unwrap();
+        }
+        writeln!(body, "  seed: {}", error.seed()).unwrap();
+
+        body.push_str("  ...\n");
+    }
+
+    format!("TAP version 13\n1..{tests}\n{body}")
+}
+
+fn display_name<H: 'static>(testcase: &TestCase<H>, index: usize) -> String {
+    match testcase.name() {
+        Some(name) => name.to_string(),
+        None => format!("testcase {}", index + 1),
+    }
+}
+
+/// Render the error as plain text, without colors or graphical elements
+fn render(error: &TestCaseError) -> String {
+    let mut rendered = String::new();
+
+    miette::NarratableReportHandler::new()
+        .render_report(
+            &mut rendered,
+            &Located {
+                error: &error.error,
+                source_code: &error.source_code,
+            },
+        )
+        .unwrap();
+
+    rendered
+}
+
+/// A [`TestError`] together with the source its spans point into
+///
+/// The plain text renderer only renders the labels of the outermost diagnostic, which for a
+/// [`TestCaseError`] has none.
+#[derive(Debug)]
+struct Located<'a> {
+    error: &'a TestError,
+    source_code: &'a TestCaseInput,
+}
+
+impl std::fmt::Display for Located<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl std::error::Error for Located<'_> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+impl miette::Diagnostic for Located<'_> {
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        self.error.help()
+    }
+
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        Some(self.source_code)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        self.error.labels()
+    }
+
+    fn diagnostic_source(&self) -> Option<&dyn miette::Diagnostic> {
+        self.error.diagnostic_source()
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn quote_yaml(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
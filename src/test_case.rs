@@ -1,21 +1,38 @@
 //! Individual testcases
 
+use std::time::Duration;
+use std::time::Instant;
+
 use miette::Diagnostic;
 use thiserror::Error;
 
 use crate::TestCaseInput;
 use crate::VerbInstance;
+use crate::context::TestContext;
 use crate::error::TestError;
+use crate::error::TestWarningCase;
 
 /// A singular test case
+///
+/// A test case is only created once all of its verbs and conditions, including the ones nested
+/// in other verbs, were found and their arguments parsed. Use [`validate`](TestCase::validate)
+/// for the checks of the arguments that go beyond parsing, without any verb having run.
 pub struct TestCase<H> {
+    pub(crate) name: Option<String>,
+    pub(crate) tags: Vec<String>,
     pub(crate) cases: Vec<VerbInstance<H>>,
+    pub(crate) warnings: Vec<TestWarningCase>,
     pub(crate) source_code: TestCaseInput,
+    /// The testcase node as it was written, before its variables were interpolated
+    pub(crate) node: kdl::KdlNode,
 }
 
 impl<H> std::fmt::Debug for TestCase<H> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("TestCase").finish_non_exhaustive()
+        f.debug_struct("TestCase")
+            .field("name", &self.name)
+            .field("tags", &self.tags)
+            .finish_non_exhaustive()
     }
 }
 
@@ -28,34 +45,559 @@ pub struct TestCaseError {
 
     #[source_code]
     pub(crate) source_code: TestCaseInput,
+
+    pub(crate) seed: u64,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[error("Testcase did not run successfully")]
+/// Errors occured while running a test with [`TestCase::run_collect`]
+pub struct TestCaseErrors {
+    #[related]
+    pub(crate) errors: Vec<TestError>,
+
+    #[source_code]
+    pub(crate) source_code: TestCaseInput,
+
+    pub(crate) seed: u64,
+}
+
+impl TestCaseError {
+    /// The error of the verb that failed
+    pub fn error(&self) -> &TestError {
+        &self.error
+    }
+
+    /// The line and column of the verb that failed, both starting at 1
+    pub fn location(&self) -> Option<(usize, usize)> {
+        self.error.location(&self.source_code)
+    }
+
+    /// The seed of the failed run
+    ///
+    /// Passing it to [`TestCase::run_with_seed`] reruns the testcase with the same randomness.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Whether the testcase was skipped instead of failing
+    ///
+    /// This is the case if a verb like `skip_if` returned [`Skipped`](crate::error::Skipped).
+    /// Runners that count a skipped testcase as success, like [`TestCase::run`], never return such
+    /// an error, [`TestCase::run_outcome`] reports it as [`TestOutcome::Skipped`].
+    pub fn is_skipped(&self) -> bool {
+        matches!(self.error, TestError::Skipped { .. })
+    }
+
+    /// Why the testcase was skipped, if it was skipped and the reason is known
+    pub fn skip_reason(&self) -> Option<&str> {
+        match &self.error {
+            TestError::Skipped { reason, .. } => reason.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The errors of the failing verb and the verbs it is nested in, outermost first
+    ///
+    /// For a testcase `repeat 2 { group { fail } }`, these are the errors of `repeat`, `group`
+    /// and `fail`. The last one is the error of the node that actually failed.
+    pub fn stack(&self) -> Vec<&TestError> {
+        std::iter::successors(Some(&self.error), |error| error.nested()).collect()
+    }
+}
+
+impl TestCaseErrors {
+    /// The errors of all verbs that failed, in the order they ran
+    pub fn errors(&self) -> &[TestError] {
+        &self.errors
+    }
+
+    /// The seed of the failed run
+    ///
+    /// Passing it to [`TestCase::run_with_seed`] reruns the testcase with the same randomness.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
 }
 
 impl<H: 'static> TestCase<H> {
-    pub(crate) fn new(source_code: TestCaseInput) -> Self {
+    pub(crate) fn new(source_code: TestCaseInput, node: kdl::KdlNode) -> Self {
         TestCase {
+            name: None,
+            tags: vec![],
             cases: vec![],
+            warnings: vec![],
             source_code,
+            node,
         }
     }
 
+    /// Get the name of this test case
+    ///
+    /// The name is given as the argument of the `testcase` node, `testcase "login" { .. }`.
+    /// Returns `None` for unnamed test cases.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Get the tags of this test case
+    ///
+    /// Tags are given as a comma-separated `tags` property of the `testcase` node,
+    /// `testcase tags="smoke,auth" { .. }`.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Check whether this test case has the given tag
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Get the warnings that were found while parsing this test case
+    ///
+    /// These are the same warnings that were passed to the function set with
+    /// [`TestDsl::set_warning_handler`](crate::TestDsl::set_warning_handler).
+    pub fn warnings(&self) -> &[TestWarningCase] {
+        &self.warnings
+    }
+
+    /// Get the top-level verbs of this test case, in the order they run
+    ///
+    /// Verbs nested in other verbs are part of the [`node`](VerbInstance::node) of their parent.
+    pub fn verbs(&self) -> &[VerbInstance<H>] {
+        &self.cases
+    }
+
     /// Get the path of the source of this test case.
     ///
     /// Returns `None` if the test case source came from in-memory.
     pub fn path(&self) -> Option<&str> {
-        match self.source_code {
-            TestCaseInput::InMemory(_) => None,
-            TestCaseInput::FromFile { ref filepath, .. } => Some(&**filepath),
+        self.source_code.filepath().map(|filepath| &**filepath)
+    }
+
+    /// Write this testcase back as KDL
+    ///
+    /// The testcase is written as it was parsed, before its variables were interpolated, so
+    /// that escapes like `$$` are kept. Parsing the document with the same
+    /// [`TestDsl`](crate::TestDsl) yields an equivalent testcase.
+    pub fn to_kdl_document(&self) -> kdl::KdlDocument {
+        let mut document = kdl::KdlDocument::new();
+        document.nodes_mut().push(self.node.clone());
+        document.autoformat();
+
+        document
+    }
+
+    /// Validate all verbs and conditions of this testcase, including nested ones, without running
+    /// any of them
+    ///
+    /// Each of them is checked with [`ParseArguments::validate`](crate::argument::ParseArguments::validate),
+    /// the first error is returned.
+    pub fn validate(&self) -> Result<(), TestError> {
+        for verb in &self.cases {
+            verb.validate()?;
         }
+
+        Ok(())
     }
 
     /// Run the given test and report on its success
+    ///
+    /// A verb returning [`SkipRest`](crate::error::SkipRest) stops the run early, which still
+    /// counts as success. So does a skipped testcase, use [`run_outcome`](Self::run_outcome) to
+    /// tell them apart. The run is seeded with a random seed, use
+    /// [`run_with_seed`](Self::run_with_seed) to control it.
     pub fn run(&self, harness: &mut H) -> Result<(), TestCaseError> {
-        self.cases
-            .iter()
-            .try_for_each(|verb| verb.run(harness))
-            .map_err(|error| TestCaseError {
-                error,
+        self.run_with_seed(harness, TestContext::random_seed())
+            .map(|_| ())
+    }
+
+    /// Run the given test and report whether it passed, failed or was skipped
+    ///
+    /// The run is seeded with a random seed, use
+    /// [`run_outcome_with_seed`](Self::run_outcome_with_seed) to control it.
+    pub fn run_outcome(&self, harness: &mut H) -> TestOutcome {
+        self.run_outcome_with_seed(harness, TestContext::random_seed())
+    }
+
+    /// Run the given test with a fixed seed and report whether it passed, failed or was skipped
+    pub fn run_outcome_with_seed(&self, harness: &mut H, seed: u64) -> TestOutcome {
+        TestOutcome::from_run(self.run_with_context(harness, TestContext::new(seed)))
+    }
+
+    /// Run the given test asynchronously and report on its success
+    ///
+    /// Each verb is awaited in turn, see [`AsyncVerb`](crate::verb::AsyncVerb). The run is
+    /// seeded with a random seed. This is only available with the `async` feature.
+    #[cfg(feature = "async")]
+    pub async fn run_async(&self, harness: &mut H) -> Result<(), TestCaseError> {
+        let mut context = TestContext::new(TestContext::random_seed());
+
+        for verb in &self.cases {
+            match verb.run_async(harness, &mut context).await {
+                Ok(()) => (),
+                Err(TestError::SkipRest { .. } | TestError::Skipped { .. }) => break,
+                Err(error) => {
+                    return Err(TestCaseError {
+                        error,
+                        source_code: self.source_code.clone(),
+                        seed: context.seed(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run all verbs of the given test, even if some of them fail
+    ///
+    /// Contrary to [`run`](Self::run), which stops at the first failing verb, every top-level
+    /// verb is run and all of their errors are returned together. Skipping the testcase stops the
+    /// run, keeping the errors of the verbs before it.
+    pub fn run_collect(&self, harness: &mut H) -> Result<(), TestCaseErrors> {
+        let mut context = TestContext::new(TestContext::random_seed());
+
+        let mut errors = vec![];
+
+        for verb in &self.cases {
+            match verb.run(harness, &mut context) {
+                Ok(()) => (),
+                Err(TestError::SkipRest { .. } | TestError::Skipped { .. }) => break,
+                Err(error) => errors.push(error),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(TestCaseErrors {
+                errors,
                 source_code: self.source_code.clone(),
-            })
+                seed: context.seed(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Run the given test with a fixed seed and report on its success
+    ///
+    /// Verbs drawing randomness from the [`TestContext`] behave the same way on every run
+    /// with the same seed.
+    pub fn run_with_seed(&self, harness: &mut H, seed: u64) -> Result<RunReport, TestCaseError> {
+        skipped_as_passed(self.run_with_context(harness, TestContext::new(seed)))
+    }
+
+    /// Run the given test and record its execution in `trace`
+    ///
+    /// This is only available with the `serde` feature.
+    #[cfg(feature = "serde")]
+    pub fn run_traced(
+        &self,
+        harness: &mut H,
+        trace: &crate::trace::TraceCollector,
+    ) -> Result<RunReport, TestCaseError> {
+        let context = TestContext::new(TestContext::random_seed()).with_trace(trace.clone());
+
+        skipped_as_passed(self.run_with_context(harness, context))
+    }
+
+    /// Run the verbs of the given test
+    ///
+    /// The report covers the verbs that ran, including the one that failed or skipped the
+    /// testcase, which is returned as the error.
+    pub(crate) fn run_with_context(
+        &self,
+        harness: &mut H,
+        mut context: TestContext,
+    ) -> (RunReport, Option<TestCaseError>) {
+        context.set_source_code(self.source_code.clone());
+        let seed = context.seed();
+        let start = Instant::now();
+        let mut verbs = vec![];
+        let mut failure = None;
+
+        for verb in &self.cases {
+            let verb_start = Instant::now();
+            let result = verb.run(harness, &mut context);
+
+            verbs.push(VerbTiming {
+                name: verb.node.name().value().to_string(),
+                span: verb.node.span(),
+                elapsed: verb_start.elapsed(),
+            });
+
+            match result {
+                Ok(()) => (),
+                Err(TestError::SkipRest { .. }) => break,
+                Err(error) => {
+                    failure = Some(TestCaseError {
+                        error,
+                        source_code: self.source_code.clone(),
+                        seed,
+                    });
+                    break;
+                }
+            }
+        }
+
+        let report = RunReport {
+            seed,
+            total: start.elapsed(),
+            verbs,
+        };
+
+        (report, failure)
+    }
+}
+
+/// Count a skipped run as a successful one
+pub(crate) fn skipped_as_passed(
+    (report, failure): (RunReport, Option<TestCaseError>),
+) -> Result<RunReport, TestCaseError> {
+    match failure {
+        Some(error) if !error.is_skipped() => Err(error),
+        _ => Ok(report),
+    }
+}
+
+/// Whether a [`TestCase`] passed, failed or was skipped
+///
+/// Returned by [`TestCase::run_outcome`] and the functions running several testcases at once.
+/// Each outcome keeps the [`RunReport`] of the verbs that ran, see [`report`](Self::report).
+#[derive(Debug)]
+pub enum TestOutcome {
+    /// All verbs ran successfully, or a verb like `skip_rest` stopped the run early
+    Passed(RunReport),
+    /// A verb failed
+    Failed(TestCaseError, RunReport),
+    /// A verb like `skip_if` returned [`Skipped`](crate::error::Skipped)
+    Skipped {
+        /// Why the testcase was skipped, if known
+        reason: Option<String>,
+        /// The timings of the verbs that ran before it was skipped
+        report: RunReport,
+    },
+}
+
+impl TestOutcome {
+    pub(crate) fn from_run((report, failure): (RunReport, Option<TestCaseError>)) -> Self {
+        match failure {
+            None => TestOutcome::Passed(report),
+            Some(TestCaseError {
+                error: TestError::Skipped { reason, .. },
+                ..
+            }) => TestOutcome::Skipped { reason, report },
+            Some(error) => TestOutcome::Failed(error, report),
+        }
+    }
+
+    /// The timings of the verbs that ran, including the one that failed or skipped the testcase
+    pub fn report(&self) -> &RunReport {
+        match self {
+            TestOutcome::Passed(report)
+            | TestOutcome::Failed(_, report)
+            | TestOutcome::Skipped { report, .. } => report,
+        }
+    }
+
+    /// Whether the testcase passed
+    pub fn is_passed(&self) -> bool {
+        matches!(self, TestOutcome::Passed(_))
+    }
+
+    /// Whether the testcase failed
+    pub fn is_failed(&self) -> bool {
+        matches!(self, TestOutcome::Failed(..))
+    }
+
+    /// Whether the testcase was skipped
+    pub fn is_skipped(&self) -> bool {
+        matches!(self, TestOutcome::Skipped { .. })
+    }
+
+    /// The error of the testcase, if it failed
+    pub fn error(&self) -> Option<&TestCaseError> {
+        match self {
+            TestOutcome::Failed(error, _) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Why the testcase was skipped, if it was skipped and the reason is known
+    pub fn skip_reason(&self) -> Option<&str> {
+        match self {
+            TestOutcome::Skipped { reason, .. } => reason.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Turn the outcome into a result, counting a skipped testcase as success
+    pub fn into_result(self) -> Result<(), TestCaseError> {
+        match self {
+            TestOutcome::Failed(error, _) => Err(error),
+            TestOutcome::Passed(_) | TestOutcome::Skipped { .. } => Ok(()),
+        }
+    }
+}
+
+/// Run all test cases in order on the same harness
+///
+/// Contrary to stopping at the first failure, every test case is run and the result of each is
+/// returned, in the order of `cases`. As the harness is shared, state left behind by one test case
+/// is visible to the ones after it.
+///
+/// ```
+/// # use test_dsl::test_case::run_all;
+/// let dsl = test_dsl::TestDsl::<()>::new();
+///
+/// let testcases = dsl
+///     .parse_testcase(
+///         r#"
+///         testcase { fail }
+///         testcase { group }
+///         "#,
+///     )
+///     .unwrap();
+///
+/// let results = run_all(&testcases, &mut ());
+/// assert!(results[0].is_failed());
+/// assert!(results[1].is_passed());
+/// ```
+pub fn run_all<H: 'static>(cases: &[TestCase<H>], harness: &mut H) -> Vec<TestOutcome> {
+    cases.iter().map(|case| case.run_outcome(harness)).collect()
+}
+
+/// Run all test cases in order, each on a fresh harness
+///
+/// `make_harness` is called once per test case, immediately before running it, so that no state
+/// is shared between test cases. The result of each test case is returned, in the order of
+/// `cases`.
+///
+/// ```
+/// # use test_dsl::test_case::run_all_with;
+/// # use test_dsl::test_case::TestOutcome;
+/// let dsl = test_dsl::TestDsl::<()>::new();
+///
+/// let testcases = dsl
+///     .parse_testcase("testcase { group }; testcase { group }")
+///     .unwrap();
+///
+/// let mut harnesses = 0;
+/// let results = run_all_with(&testcases, || harnesses += 1);
+///
+/// assert_eq!(harnesses, 2);
+/// assert!(results.iter().all(TestOutcome::is_passed));
+/// ```
+pub fn run_all_with<H: 'static>(
+    cases: &[TestCase<H>],
+    mut make_harness: impl FnMut() -> H,
+) -> Vec<TestOutcome> {
+    cases
+        .iter()
+        .map(|case| case.run_outcome(&mut make_harness()))
+        .collect()
+}
+
+/// Get the indices of the test cases for which `predicate` returns `true`
+///
+/// The indices can be passed to [`TestDsl::run_selected`](crate::TestDsl::run_selected) to run
+/// only those test cases:
+///
+/// ```
+/// # use test_dsl::test_case::select;
+/// let dsl = test_dsl::TestDsl::<()>::new();
+///
+/// let testcases = dsl
+///     .parse_testcase(
+///         r#"
+///         testcase "login" tags="smoke" { group }
+///         testcase "export" { group }
+///         "#,
+///     )
+///     .unwrap();
+///
+/// let smoke = select(&testcases, |testcase| testcase.has_tag("smoke"));
+///
+/// let results = dsl.run_selected(&testcases, || (), smoke);
+/// assert_eq!(results.len(), 1);
+/// ```
+pub fn select<H>(cases: &[TestCase<H>], predicate: impl Fn(&TestCase<H>) -> bool) -> Vec<usize> {
+    cases
+        .iter()
+        .enumerate()
+        .filter(|(_, case)| predicate(case))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Information about a successful run of a [`TestCase`]
+///
+/// The timings are always measured, they can be used to assert on the performance of a testcase:
+///
+/// ```
+/// # use std::time::Duration;
+/// let dsl = test_dsl::TestDsl::<()>::new();
+///
+/// let testcases = dsl
+///     .parse_testcase(r#"testcase { sleep "10ms"; group }"#)
+///     .unwrap();
+///
+/// let report = testcases[0].run_with_seed(&mut (), 42).unwrap();
+///
+/// assert_eq!(report.per_verb()[0].name(), "sleep");
+/// assert!(report.per_verb()[0].elapsed() >= Duration::from_millis(10));
+/// assert!(report.total() >= report.per_verb()[0].elapsed());
+/// ```
+#[derive(Debug, Clone)]
+pub struct RunReport {
+    seed: u64,
+    total: Duration,
+    verbs: Vec<VerbTiming>,
+}
+
+impl RunReport {
+    /// The seed the run was started with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// How long running the testcase took
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// How long each top-level verb took, in the order they ran
+    ///
+    /// Verbs that did not run, because a verb like `skip_rest` stopped the testcase early, are
+    /// missing.
+    pub fn per_verb(&self) -> &[VerbTiming] {
+        &self.verbs
+    }
+}
+
+/// How long a top-level verb of a [`TestCase`] took to run
+///
+/// The time of a verb includes the time of the verbs nested in it.
+#[derive(Debug, Clone)]
+pub struct VerbTiming {
+    name: String,
+    span: miette::SourceSpan,
+    elapsed: Duration,
+}
+
+impl VerbTiming {
+    /// The name of the verb, as written in the testcase
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The location of the verb node
+    pub fn span(&self) -> miette::SourceSpan {
+        self.span
+    }
+
+    /// How long the verb took
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
     }
 }
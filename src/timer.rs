@@ -0,0 +1,151 @@
+//! Waiting in async verbs without depending on a specific runtime
+//!
+//! All timers are kept by a single thread, which is started with the first timer and wakes
+//! each task once its timer elapsed. This module is only available with the `async` feature.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::Once;
+use std::sync::PoisonError;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
+use std::time::Duration;
+use std::time::Instant;
+
+/// The timers that did not elapse yet, with the wakers of their tasks
+struct Timers {
+    pending: Mutex<Vec<(Instant, Arc<Mutex<Waker>>)>>,
+    changed: Condvar,
+}
+
+static TIMERS: Timers = Timers {
+    pending: Mutex::new(Vec::new()),
+    changed: Condvar::new(),
+};
+
+static START_THREAD: Once = Once::new();
+
+impl Timers {
+    /// Wake the task of `waker` once `deadline` has passed
+    ///
+    /// A timer whose future is dropped early stays pending until its deadline, waking the task
+    /// once more does no harm.
+    fn add(&'static self, deadline: Instant, waker: Arc<Mutex<Waker>>) {
+        START_THREAD.call_once(|| {
+            std::thread::Builder::new()
+                .name(String::from("test-dsl-timer"))
+                .spawn(|| self.run())
+                .expect("Could not start the timer thread");
+        });
+
+        self.lock().push((deadline, waker));
+        self.changed.notify_one();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<(Instant, Arc<Mutex<Waker>>)>> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Wake the tasks of the elapsed timers, forever
+    fn run(&self) {
+        let mut pending = self.lock();
+
+        loop {
+            let now = Instant::now();
+            let (elapsed, waiting) = std::mem::take(&mut *pending)
+                .into_iter()
+                .partition::<Vec<_>, _>(|(deadline, _)| *deadline <= now);
+            *pending = waiting;
+
+            if !elapsed.is_empty() {
+                // Waking a task may add a new timer, so it has to happen without the lock
+                drop(pending);
+
+                for (_, waker) in elapsed {
+                    waker.lock().unwrap().wake_by_ref();
+                }
+
+                pending = self.lock();
+                continue;
+            }
+
+            pending = match pending.iter().map(|(deadline, _)| *deadline).min() {
+                Some(next) => {
+                    self.changed
+                        .wait_timeout(pending, next - now)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+                None => self
+                    .changed
+                    .wait(pending)
+                    .unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+    }
+}
+
+/// A future that completes once its deadline has passed
+#[derive(Debug)]
+pub(crate) struct Sleep {
+    deadline: Instant,
+    waker: Option<Arc<Mutex<Waker>>>,
+}
+
+impl Sleep {
+    pub(crate) fn until(deadline: Instant) -> Self {
+        Sleep {
+            deadline,
+            waker: None,
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        match &self.waker {
+            Some(waker) => waker.lock().unwrap().clone_from(cx.waker()),
+            None => {
+                let waker = Arc::new(Mutex::new(cx.waker().clone()));
+                TIMERS.add(self.deadline, Arc::clone(&waker));
+                self.waker = Some(waker);
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Wait for the given duration
+pub(crate) fn sleep(duration: Duration) -> Sleep {
+    Sleep::until(Instant::now() + duration)
+}
+
+/// Await `future`, unless the deadline passes first
+///
+/// Returns `None` if the deadline passed. Without a deadline, this simply awaits the future.
+pub(crate) async fn timeout<F: Future>(deadline: Option<Instant>, future: F) -> Option<F::Output> {
+    let mut future = std::pin::pin!(future);
+    let mut sleep = deadline.map(Sleep::until);
+
+    std::future::poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+
+        match sleep.as_mut().map(|sleep| Pin::new(sleep).poll(cx)) {
+            Some(Poll::Ready(())) => Poll::Ready(None),
+            _ => Poll::Pending,
+        }
+    })
+    .await
+}
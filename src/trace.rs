@@ -0,0 +1,198 @@
+//! Structured traces of testcase runs
+//!
+//! This module is only available with the `serde` feature.
+//!
+//! A [`TraceCollector`] records every verb and condition that is evaluated during a run as a tree
+//! of [`TraceEvent`]s. Each event is opened when its node is entered, and closed with its
+//! [`TraceResult`] and duration when it is exited. Verbs that run other verbs or conditions, like
+//! `repeat` or `assert`, contain them as their children.
+//!
+//! ```
+//! # use test_dsl::trace::TraceCollector;
+//! let dsl = test_dsl::TestDsl::<()>::new();
+//!
+//! let testcases = dsl.parse_testcase("testcase { repeat 2 { group } }").unwrap();
+//!
+//! let trace = TraceCollector::new();
+//! testcases[0].run_traced(&mut (), &trace).unwrap();
+//!
+//! let events = trace.events();
+//! assert_eq!(events[0].name, "repeat");
+//! assert_eq!(events[0].children.len(), 2);
+//!
+//! println!("{}", trace.to_json());
+//! ```
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::error::TestError;
+
+/// Collects the [`TraceEvent`]s of one or more runs
+///
+/// Clones of a collector share the same events.
+#[derive(Debug, Clone, Default)]
+pub struct TraceCollector {
+    state: Rc<RefCell<TraceState>>,
+}
+
+#[derive(Debug, Default)]
+struct TraceState {
+    events: Vec<TraceEvent>,
+    open: Vec<(TraceEvent, Instant)>,
+}
+
+impl TraceCollector {
+    /// Create a new, empty collector
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The events of all top-level nodes that were run
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.state.borrow().events.clone()
+    }
+
+    /// Serialize all recorded events as a JSON array
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.state.borrow().events)
+            .expect("Trace events are always serializable")
+    }
+
+    pub(crate) fn enter(&self, kind: TraceKind, node: &kdl::KdlNode) -> usize {
+        let event = TraceEvent {
+            kind,
+            name: node.name().value().to_string(),
+            span: TraceSpan {
+                offset: node.span().offset(),
+                length: node.span().len(),
+            },
+            arguments: node
+                .entries()
+                .iter()
+                .map(|entry| entry.to_string().trim().to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+            result: TraceResult::Passed,
+            duration_micros: 0,
+            children: vec![],
+        };
+
+        let mut state = self.state.borrow_mut();
+        state.open.push((event, Instant::now()));
+        state.open.len() - 1
+    }
+
+    pub(crate) fn exit(&self, depth: usize, result: Result<bool, &TestError>) {
+        let mut state = self.state.borrow_mut();
+
+        // Nodes below `depth` that are still open were unwound by a panic
+        while state.open.len() > depth + 1 {
+            state.close(TraceResult::Panic {
+                message: String::from("Unwound by a panic"),
+            });
+        }
+
+        state.close(match result {
+            Ok(true) => TraceResult::Passed,
+            Ok(false) | Err(TestError::ConditionFailed { .. }) => TraceResult::Failed,
+            Err(TestError::Error { error, .. }) => TraceResult::Error {
+                message: error.to_string(),
+            },
+            Err(error @ (TestError::IterationLimitReached { .. } | TestError::Timeout { .. })) => {
+                TraceResult::Error {
+                    message: error.to_string(),
+                }
+            }
+            Err(TestError::Panic { error, .. }) => TraceResult::Panic {
+                message: error.to_string(),
+            },
+            Err(TestError::SkipRest { .. } | TestError::Skipped { .. }) => TraceResult::Skipped,
+        });
+    }
+}
+
+impl TraceState {
+    fn close(&mut self, result: TraceResult) {
+        let Some((mut event, start)) = self.open.pop() else {
+            return;
+        };
+
+        event.result = result;
+        event.duration_micros = u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX);
+
+        match self.open.last_mut() {
+            Some((parent, _)) => parent.children.push(event),
+            None => self.events.push(event),
+        }
+    }
+}
+
+/// A single verb or condition that was run
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceEvent {
+    /// Whether this was a verb or a condition
+    pub kind: TraceKind,
+
+    /// The name of the node
+    pub name: String,
+
+    /// The location of the node in its source
+    pub span: TraceSpan,
+
+    /// The arguments of the node, as written in the source
+    pub arguments: String,
+
+    /// How running the node ended
+    pub result: TraceResult,
+
+    /// How long running the node took, including its children
+    pub duration_micros: u64,
+
+    /// The verbs and conditions that were run as part of this node
+    pub children: Vec<TraceEvent>,
+}
+
+/// The kind of node a [`TraceEvent`] belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceKind {
+    /// A [`Verb`](crate::verb::Verb)
+    Verb,
+    /// A [`Condition`](crate::condition::Condition)
+    Condition,
+}
+
+/// The location of a node in its source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TraceSpan {
+    /// The byte offset of the node
+    pub offset: usize,
+    /// The length of the node in bytes
+    pub length: usize,
+}
+
+/// How running a node ended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TraceResult {
+    /// The verb ran successfully, or the condition held
+    Passed,
+    /// The condition did not hold
+    Failed,
+    /// The node returned an error
+    Error {
+        /// The message of the error
+        message: String,
+    },
+    /// The node panicked
+    Panic {
+        /// The message of the panic
+        message: String,
+    },
+    /// The verb skipped the rest of the testcase, or marked it as skipped
+    Skipped,
+}
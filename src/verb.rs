@@ -9,8 +9,12 @@ use std::marker::PhantomData;
 use crate::BoxedArguments;
 use crate::TestDsl;
 use crate::argument::ParseArguments;
+use crate::argument::Signature;
 use crate::argument::VerbArgument;
+use crate::argument::VerbChildren;
+use crate::context::TestContext;
 use crate::error::TestErrorCase;
+use crate::error::ValueUnchanged;
 
 /// A verb is anything that 'does' things in a [`TestCase`](crate::test_case::TestCase)
 pub trait Verb<H>: std::fmt::Debug + Clone + 'static {
@@ -18,15 +22,110 @@ pub trait Verb<H>: std::fmt::Debug + Clone + 'static {
     type Arguments: ParseArguments<H>;
 
     /// Run the verb, and do its thing
-    fn run(&self, harness: &mut H, arguments: &Self::Arguments) -> miette::Result<()>;
+    ///
+    /// The [`TestContext`] is shared between all verbs of a single run. Verbs that need
+    /// randomness should draw it from there, so that runs are reproducible.
+    fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()>;
+
+    /// Describe the arguments this verb takes
+    ///
+    /// Defaults to the [`ParseArguments::signature`] of its arguments. Returning `None` means that
+    /// the arguments are not known in advance.
+    fn signature(&self) -> Option<Signature> {
+        <Self::Arguments as ParseArguments<H>>::signature()
+    }
+}
+
+/// The future of a running [`AsyncVerb`]
+///
+/// It is not required to be [`Send`], so that it can borrow the harness.
+#[cfg(feature = "async")]
+pub type VerbFuture<'a> = std::pin::Pin<Box<dyn Future<Output = miette::Result<()>> + 'a>>;
+
+/// A verb that does its thing asynchronously
+///
+/// Async verbs are added with [`TestDsl::add_async_verb`] and run by
+/// [`TestCase::run_async`](crate::test_case::TestCase::run_async), which awaits each verb in
+/// turn. Their futures do not have to be [`Send`], so they work on single-threaded runtimes.
+///
+/// Of the built-in verbs, `group` and `repeat` await their async children, and `wait_for` awaits
+/// [`AsyncCondition`](crate::condition::AsyncCondition)s. Running an async verb
+/// synchronously, through [`TestCase::run`](crate::test_case::TestCase::run) or inside another
+/// built-in verb, fails with an [`AsyncOnly`](crate::error::AsyncOnly) error.
+///
+/// This trait is only available with the `async` feature.
+///
+/// ```
+/// # use test_dsl::context::TestContext;
+/// # use test_dsl::verb::AsyncVerb;
+/// # fn block_on<F: Future>(future: F) -> F::Output {
+/// #     let mut future = std::pin::pin!(future);
+/// #     let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+/// #     loop {
+/// #         if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+/// #             return output;
+/// #         }
+/// #     }
+/// # }
+/// #[derive(Debug, Clone)]
+/// struct Publish;
+///
+/// impl AsyncVerb<Vec<String>> for Publish {
+///     type Arguments = (String,);
+///
+///     async fn run(
+///         &self,
+///         outbox: &mut Vec<String>,
+///         _context: &mut TestContext,
+///         (message,): &Self::Arguments,
+///     ) -> miette::Result<()> {
+///         // Await your client here
+///         outbox.push(message.clone());
+///         Ok(())
+///     }
+/// }
+///
+/// let mut dsl = test_dsl::TestDsl::<Vec<String>>::new();
+/// dsl.add_async_verb("publish", Publish);
+///
+/// let testcases = dsl
+///     .parse_testcase(r#"testcase { repeat 2 { publish "hello" } }"#)
+///     .unwrap();
+///
+/// let mut outbox = vec![];
+/// block_on(testcases[0].run_async(&mut outbox)).unwrap();
+/// assert_eq!(outbox, ["hello", "hello"]);
+/// ```
+#[cfg(feature = "async")]
+pub trait AsyncVerb<H>: std::fmt::Debug + Clone + 'static {
+    /// Arguments to this verb
+    type Arguments: ParseArguments<H>;
+
+    /// Run the verb, and do its thing
+    fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> impl Future<Output = miette::Result<()>>;
 }
 
 pub(crate) struct ErasedVerb<H> {
     verb: Box<dyn Any>,
     fn_parse_args:
         fn(&crate::TestDsl<H>, &kdl::KdlNode) -> Result<Box<dyn BoxedArguments<H>>, TestErrorCase>,
-    fn_run: fn(&dyn Any, &mut H, &dyn Any) -> miette::Result<()>,
+    fn_run: fn(&dyn Any, &mut H, &mut TestContext, &dyn Any) -> miette::Result<()>,
+    #[cfg(feature = "async")]
+    fn_run_async:
+        for<'a> fn(&'a dyn Any, &'a mut H, &'a mut TestContext, &'a dyn Any) -> VerbFuture<'a>,
     fn_clone: fn(&dyn Any) -> Box<dyn Any>,
+    arguments_name: &'static str,
+    signature: Option<Signature>,
 }
 
 impl<H> std::fmt::Debug for ErasedVerb<H> {
@@ -36,6 +135,8 @@ impl<H> std::fmt::Debug for ErasedVerb<H> {
             .field("fn_parse_args", &self.fn_parse_args)
             .field("fn_run", &self.fn_run)
             .field("fn_clone", &self.fn_clone)
+            .field("arguments_name", &self.arguments_name)
+            .field("signature", &self.signature)
             .finish()
     }
 }
@@ -46,7 +147,11 @@ impl<H> Clone for ErasedVerb<H> {
             verb: (self.fn_clone)(&*self.verb),
             fn_parse_args: self.fn_parse_args,
             fn_run: self.fn_run,
+            #[cfg(feature = "async")]
+            fn_run_async: self.fn_run_async,
             fn_clone: self.fn_clone,
+            arguments_name: self.arguments_name,
+            signature: self.signature.clone(),
         }
     }
 }
@@ -55,29 +160,96 @@ impl<H> ErasedVerb<H> {
     pub(crate) fn erase<V>(verb: V) -> Self
     where
         V: Verb<H>,
+    {
+        let signature = verb.signature();
+
+        Self::erase_with::<V, V::Arguments>(
+            verb,
+            signature,
+            run_erased::<H, V>,
+            #[cfg(feature = "async")]
+            |this, harness, context, arguments| {
+                Box::pin(std::future::ready(run_erased::<H, V>(
+                    this, harness, context, arguments,
+                )))
+            },
+        )
+    }
+
+    /// Erase a verb that can only run asynchronously
+    #[cfg(feature = "async")]
+    pub(crate) fn erase_async<V>(verb: V) -> Self
+    where
+        V: AsyncVerb<H>,
+    {
+        Self::erase_with::<V, V::Arguments>(
+            verb,
+            <V::Arguments as ParseArguments<H>>::signature(),
+            |_this, _harness, _context, _arguments| Err(crate::error::AsyncOnly.into()),
+            run_erased_async::<H, V>,
+        )
+    }
+
+    /// Erase a verb that can run both synchronously and asynchronously
+    #[cfg(feature = "async")]
+    pub(crate) fn erase_dual<V>(verb: V) -> Self
+    where
+        V: Verb<H> + AsyncVerb<H, Arguments = <V as Verb<H>>::Arguments>,
+    {
+        let signature = Verb::signature(&verb);
+
+        Self::erase_with::<V, <V as Verb<H>>::Arguments>(
+            verb,
+            signature,
+            run_erased::<H, V>,
+            run_erased_async::<H, V>,
+        )
+    }
+
+    fn erase_with<V, A>(
+        verb: V,
+        signature: Option<Signature>,
+        fn_run: fn(&dyn Any, &mut H, &mut TestContext, &dyn Any) -> miette::Result<()>,
+        #[cfg(feature = "async")] fn_run_async: for<'a> fn(
+            &'a dyn Any,
+            &'a mut H,
+            &'a mut TestContext,
+            &'a dyn Any,
+        ) -> VerbFuture<'a>,
+    ) -> Self
+    where
+        V: Clone + 'static,
+        A: ParseArguments<H>,
     {
         ErasedVerb {
             verb: Box::new(verb),
             fn_parse_args: |test_dsl, node| {
-                <V::Arguments as ParseArguments<H>>::parse(test_dsl, node).map(|a| {
+                <A as ParseArguments<H>>::parse(test_dsl, node).map(|a| {
                     let args = Box::new(a);
                     args as _
                 })
             },
-            fn_run: |this, harness, arguments| {
-                let this: &V = this.downcast_ref().unwrap();
-                let arguments: &V::Arguments = arguments.downcast_ref().unwrap();
-
-                this.run(harness, arguments)
-            },
+            fn_run,
+            #[cfg(feature = "async")]
+            fn_run_async,
             fn_clone: |this| {
                 let this: &V = this.downcast_ref().unwrap();
 
                 Box::new(this.clone())
             },
+            arguments_name: std::any::type_name::<A>(),
+            signature,
         }
     }
 
+    pub(crate) fn arguments_name(&self) -> &'static str {
+        self.arguments_name
+    }
+
+    pub(crate) fn signature(&self) -> Option<&Signature> {
+        self.signature.as_ref()
+    }
+
     pub(crate) fn parse_args(
         &self,
         test_dsl: &TestDsl<H>,
@@ -86,11 +258,51 @@ impl<H> ErasedVerb<H> {
         (self.fn_parse_args)(test_dsl, node)
     }
 
-    pub(crate) fn run(&self, harness: &mut H, arguments: &dyn Any) -> miette::Result<()> {
-        (self.fn_run)(&*self.verb, harness, arguments)
+    pub(crate) fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &dyn Any,
+    ) -> miette::Result<()> {
+        (self.fn_run)(&*self.verb, harness, context, arguments)
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn run_async<'a>(
+        &'a self,
+        harness: &'a mut H,
+        context: &'a mut TestContext,
+        arguments: &'a dyn Any,
+    ) -> VerbFuture<'a> {
+        (self.fn_run_async)(&*self.verb, harness, context, arguments)
     }
 }
 
+fn run_erased<H, V: Verb<H>>(
+    this: &dyn Any,
+    harness: &mut H,
+    context: &mut TestContext,
+    arguments: &dyn Any,
+) -> miette::Result<()> {
+    let this: &V = this.downcast_ref().unwrap();
+    let arguments: &<V as Verb<H>>::Arguments = arguments.downcast_ref().unwrap();
+
+    this.run(harness, context, arguments)
+}
+
+#[cfg(feature = "async")]
+fn run_erased_async<'a, H, V: AsyncVerb<H>>(
+    this: &'a dyn Any,
+    harness: &'a mut H,
+    context: &'a mut TestContext,
+    arguments: &'a dyn Any,
+) -> VerbFuture<'a> {
+    let this: &V = this.downcast_ref().unwrap();
+    let arguments: &<V as AsyncVerb<H>>::Arguments = arguments.downcast_ref().unwrap();
+
+    Box::pin(this.run(harness, context, arguments))
+}
+
 /// A verb defined through a closure/function
 ///
 /// See the [`CallableVerb`] trait for what can be used
@@ -235,7 +447,162 @@ where
     T: ParseArguments<H>,
 {
     type Arguments = T;
-    fn run(&self, harness: &mut H, args: &T) -> miette::Result<()> {
+    fn run(&self, harness: &mut H, _context: &mut TestContext, args: &T) -> miette::Result<()> {
         self.func.call(harness, args)
     }
 }
+
+/// An asynchronous verb defined through a closure/function
+///
+/// The closure gets the harness and the parsed arguments as a tuple, and returns a boxed
+/// future. This is only available with the `async` feature.
+///
+/// ```
+/// # use test_dsl::verb::AsyncFunctionVerb;
+/// let mut dsl = test_dsl::TestDsl::<Vec<usize>>::new();
+///
+/// dsl.add_async_verb(
+///     "push",
+///     AsyncFunctionVerb::new(|h: &mut Vec<usize>, (value,): (usize,)| {
+///         Box::pin(async move {
+///             h.push(value);
+///             Ok(())
+///         })
+///     }),
+/// );
+/// ```
+#[cfg(feature = "async")]
+pub struct AsyncFunctionVerb<H, T> {
+    func: std::rc::Rc<dyn for<'a> Fn(&'a mut H, T) -> VerbFuture<'a>>,
+}
+
+#[cfg(feature = "async")]
+impl<H, T> std::fmt::Debug for AsyncFunctionVerb<H, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncFunctionVerb").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<H, T> Clone for AsyncFunctionVerb<H, T> {
+    fn clone(&self) -> Self {
+        Self {
+            func: self.func.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<H, T> AsyncFunctionVerb<H, T> {
+    /// Create a new verb using a closure/function
+    pub fn new<F>(func: F) -> Self
+    where
+        F: for<'a> Fn(&'a mut H, T) -> VerbFuture<'a> + 'static,
+    {
+        AsyncFunctionVerb {
+            func: std::rc::Rc::new(func),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T, H: 'static> AsyncVerb<H> for AsyncFunctionVerb<H, T>
+where
+    T: ParseArguments<H>,
+{
+    type Arguments = T;
+
+    fn run(
+        &self,
+        harness: &mut H,
+        _context: &mut TestContext,
+        arguments: &T,
+    ) -> impl Future<Output = miette::Result<()>> {
+        (self.func)(harness, arguments.clone())
+    }
+}
+
+/// A verb that checks that running its children changes a value of the harness
+///
+/// The projection is evaluated before and after running the child verbs. If both values are
+/// equal, the verb fails with a [`ValueUnchanged`] error.
+///
+/// ```
+/// # use test_dsl::verb::AssertChanges;
+/// # use test_dsl::verb::FunctionVerb;
+/// let mut dsl = test_dsl::TestDsl::<Vec<usize>>::new();
+///
+/// dsl.add_verb(
+///     "push",
+///     FunctionVerb::new(|h: &mut Vec<usize>, value: usize| {
+///         h.push(value);
+///         Ok(())
+///     }),
+/// );
+/// dsl.add_verb("assert_changes", AssertChanges::new(|h: &Vec<usize>| h.len()));
+///
+/// let testcases = dsl
+///     .parse_testcase("testcase { assert_changes { push 1 } }")
+///     .unwrap();
+///
+/// testcases[0].run(&mut vec![]).unwrap();
+/// ```
+pub struct AssertChanges<H, T> {
+    projection: fn(&H) -> T,
+}
+
+impl<H, T> std::fmt::Debug for AssertChanges<H, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AssertChanges")
+            .field("projection", &self.projection)
+            .finish()
+    }
+}
+
+impl<H, T> Clone for AssertChanges<H, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<H, T> Copy for AssertChanges<H, T> {}
+
+impl<H, T> AssertChanges<H, T> {
+    /// Create a new verb comparing the value returned by `projection`
+    pub fn new(projection: fn(&H) -> T) -> Self {
+        AssertChanges { projection }
+    }
+}
+
+impl<H, T> Verb<H> for AssertChanges<H, T>
+where
+    H: 'static,
+    T: PartialEq + std::fmt::Debug + 'static,
+{
+    type Arguments = VerbChildren<H, ((),)>;
+
+    fn run(
+        &self,
+        harness: &mut H,
+        context: &mut TestContext,
+        arguments: &Self::Arguments,
+    ) -> miette::Result<()> {
+        let before = (self.projection)(harness);
+
+        for child in arguments.children() {
+            child.run(harness, context)?;
+        }
+
+        let after = (self.projection)(harness);
+
+        if before == after {
+            return Err(ValueUnchanged {
+                before: format!("{before:?}"),
+                after: format!("{after:?}"),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,307 @@
+//! Derive macros for [`test-dsl`](https://docs.rs/test-dsl)
+//!
+//! Use them through the `derive` feature of `test-dsl`, which re-exports them next to the traits
+//! they implement.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::Data;
+use syn::DeriveInput;
+use syn::Expr;
+use syn::Fields;
+use syn::LitStr;
+use syn::spanned::Spanned;
+
+/// Derive `ParseArguments` for a struct with named fields
+///
+/// See the documentation of `test_dsl::argument::ParseArguments` for the supported attributes.
+#[proc_macro_derive(ParseArguments, attributes(arg))]
+pub fn derive_parse_arguments(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+
+    parse_arguments(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `VerbArgument` for an enum without fields
+///
+/// See the documentation of `test_dsl::argument::VerbArgument` for the supported attributes.
+#[proc_macro_derive(VerbArgument, attributes(arg))]
+pub fn derive_verb_argument(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+
+    verb_argument(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Where the value of a field is read from
+enum Source {
+    Property(LitStr),
+    Positional,
+    Child(LitStr),
+}
+
+/// What to do if the value of a field is not given
+enum Missing {
+    /// Let the type of the field decide, e.g. `Option` is `None`
+    Type,
+    Default,
+    Expr(Expr),
+}
+
+struct Field {
+    ident: syn::Ident,
+    ty: syn::Type,
+    source: Source,
+    missing: Missing,
+}
+
+impl Field {
+    fn parse(field: &syn::Field) -> syn::Result<Field> {
+        let ident = field.ident.clone().expect("Only named fields are parsed");
+
+        let mut name = None;
+        let mut positional = false;
+        let mut child = false;
+        let mut missing = Missing::Type;
+
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("arg"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    name = Some(meta.value()?.parse::<LitStr>()?);
+                } else if meta.path.is_ident("positional") {
+                    positional = true;
+                } else if meta.path.is_ident("child") {
+                    child = true;
+                } else if meta.path.is_ident("optional") {
+                    missing = Missing::Default;
+                } else if meta.path.is_ident("default") {
+                    missing = Missing::Expr(meta.value()?.parse()?);
+                } else {
+                    return Err(meta.error(
+                        "expected one of `name`, `positional`, `child`, `optional` or `default`",
+                    ));
+                }
+
+                Ok(())
+            })?;
+        }
+
+        let name = name.unwrap_or_else(|| LitStr::new(&ident.to_string(), ident.span()));
+
+        let source = match (positional, child) {
+            (true, true) => {
+                return Err(syn::Error::new(
+                    field.span(),
+                    "a field cannot be both `positional` and a `child`",
+                ));
+            }
+            (true, false) => Source::Positional,
+            (false, true) => Source::Child(name),
+            (false, false) => Source::Property(name),
+        };
+
+        Ok(Field {
+            ident,
+            ty: field.ty.clone(),
+            source,
+            missing,
+        })
+    }
+
+    fn parse_value(&self, positional_count: usize, position: &mut usize) -> TokenStream {
+        let ty = &self.ty;
+
+        let missing = match &self.missing {
+            Missing::Type => None,
+            Missing::Default => Some(quote! { ::core::default::Default::default() }),
+            Missing::Expr(expr) => Some(quote! { #expr }),
+        };
+
+        match &self.source {
+            Source::Positional => {
+                *position += 1;
+                let position = *position;
+
+                let parse = quote! {
+                    <#ty as ::test_dsl::argument::VerbArgument>::parse_positional(
+                        __node,
+                        &mut __args,
+                        #positional_count,
+                        #position,
+                    )?
+                };
+
+                match missing {
+                    None => parse,
+                    Some(missing) => quote! {
+                        if __args.peek().is_some() { #parse } else { #missing }
+                    },
+                }
+            }
+            Source::Property(name) => {
+                let parse = quote! { ::test_dsl::argument::parse_named::<#ty>(__node, #name)? };
+
+                match missing {
+                    None => parse,
+                    Some(missing) => quote! {
+                        if __node.entry(#name).is_some() { #parse } else { #missing }
+                    },
+                }
+            }
+            Source::Child(name) => {
+                let parse = quote! {
+                    ::test_dsl::argument::parse_named_child::<__H, #ty>(__test_dsl, __node, #name)?
+                };
+
+                match missing {
+                    None => parse,
+                    Some(missing) => quote! {
+                        if __node.iter_children().any(|child| child.name().value() == #name) {
+                            #parse
+                        } else {
+                            #missing
+                        }
+                    },
+                }
+            }
+        }
+    }
+}
+
+fn parse_arguments(input: DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "`ParseArguments` can only be derived for structs",
+        ));
+    };
+
+    let Fields::Named(named) = &data.fields else {
+        return Err(syn::Error::new(
+            data.fields.span(),
+            "`ParseArguments` can only be derived for structs with named fields",
+        ));
+    };
+
+    let fields = named
+        .named
+        .iter()
+        .map(Field::parse)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let positional_count = fields
+        .iter()
+        .filter(|field| matches!(field.source, Source::Positional))
+        .count();
+
+    let mut position = 0;
+    let parse_fields = fields.iter().map(|field| {
+        let ident = &field.ident;
+        let ty = &field.ty;
+        let value = field.parse_value(positional_count, &mut position);
+
+        quote! { let #ident: #ty = #value; }
+    });
+    let idents = fields.iter().map(|field| &field.ident);
+
+    let name = &input.ident;
+    let (_, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut generics = input.generics.clone();
+    generics.params.push(syn::parse_quote!(__H));
+    let (impl_generics, _, _) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::test_dsl::argument::ParseArguments<__H> for #name #ty_generics #where_clause {
+            fn parse(
+                __test_dsl: &::test_dsl::TestDsl<__H>,
+                __node: &::test_dsl::kdl::KdlNode,
+            ) -> ::core::result::Result<Self, ::test_dsl::error::TestErrorCase> {
+                let _ = __test_dsl;
+                let mut __args = ::test_dsl::argument::positional_entries(__node).peekable();
+
+                #(#parse_fields)*
+
+                ::test_dsl::argument::parse_end(&mut __args, #positional_count)?;
+
+                ::core::result::Result::Ok(#name { #(#idents),* })
+            }
+        }
+    })
+}
+
+fn verb_argument(input: DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "`VerbArgument` can only be derived for enums",
+        ));
+    };
+
+    let mut names = vec![];
+    let mut variants = vec![];
+
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(syn::Error::new(
+                variant.fields.span(),
+                "`VerbArgument` can only be derived for enums without fields",
+            ));
+        }
+
+        let mut rename = None;
+
+        for attr in variant
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("arg"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `rename`"))
+                }
+            })?;
+        }
+
+        names.push(rename.unwrap_or_else(|| variant.ident.to_string().to_lowercase()));
+        variants.push(&variant.ident);
+    }
+
+    let name = &input.ident;
+    let type_name = name.to_string();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::test_dsl::argument::VerbArgument for #name #ty_generics #where_clause {
+            fn get_error_type_name() -> &'static str {
+                #type_name
+            }
+
+            fn accepted_values() -> &'static [&'static str] {
+                &[#(#names),*]
+            }
+
+            fn from_value(value: &::test_dsl::kdl::KdlEntry) -> ::core::option::Option<Self> {
+                let value = value.value().as_string()?;
+
+                #(
+                    if value.eq_ignore_ascii_case(#names) {
+                        return ::core::option::Option::Some(#name::#variants);
+                    }
+                )*
+
+                ::core::option::Option::None
+            }
+        }
+    })
+}
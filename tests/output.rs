@@ -1,7 +1,17 @@
 //! Test various error outputs
 
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use test_dsl::TestCaseInput;
+use test_dsl::condition::ApproxEq;
 use test_dsl::condition::FunctionCondition;
+use test_dsl::context::TestContext;
+use test_dsl::verb::AssertChanges;
 use test_dsl::verb::FunctionVerb;
+use test_dsl::verb::Verb;
 
 #[test]
 fn check_invalid() {
@@ -22,6 +32,26 @@ fn check_invalid() {
     insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
 }
 
+#[test]
+fn check_strict_top_level() {
+    let mut ts = test_dsl::TestDsl::<()>::new();
+    ts.add_top_level_node("fixture");
+    ts.add_top_level_node("setup");
+    ts.set_strict(true);
+
+    let tc = ts.parse_testcase(
+        r#"
+            fixture database
+            testcase {
+            }
+            tetcase {
+            }
+        "#,
+    );
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
+}
+
 #[test]
 fn check_unknown() {
     let ts = test_dsl::TestDsl::<()>::new();
@@ -39,6 +69,26 @@ fn check_unknown() {
     insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
 }
 
+#[test]
+fn check_unknown_suggestion() {
+    let ts = test_dsl::TestDsl::<()>::new();
+
+    let tc = ts.parse_testcase(
+        r#"
+            testcase {
+                repaet 2 {
+                    group
+                }
+            }
+            testcase {
+                assert { nto { all; }; }
+            }
+        "#,
+    );
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
+}
+
 #[test]
 fn check_missing_argument() {
     let ts = test_dsl::TestDsl::<()>::new();
@@ -55,6 +105,22 @@ fn check_missing_argument() {
     insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
 }
 
+#[test]
+fn check_unexpected_argument() {
+    let ts = test_dsl::TestDsl::<()>::new();
+
+    let tc = ts.parse_testcase(
+        r#"
+            testcase {
+                repeat 2 3 {
+                }
+            }
+        "#,
+    );
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
+}
+
 #[test]
 fn check_argument_wrong_type() {
     let ts = test_dsl::TestDsl::<()>::new();
@@ -104,6 +170,30 @@ fn check_argument_wrong_type_verb() {
     insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
 }
 
+#[test]
+fn check_condition_argument_wrong_type() {
+    let mut ts = test_dsl::TestDsl::<()>::new();
+
+    ts.add_condition(
+        "is_positive",
+        FunctionCondition::new(|_h: &(), n: isize| Ok(n > 0)),
+    );
+
+    let tc = ts.parse_testcase(
+        r#"
+            testcase {
+                assert {
+                    is_positive two
+                    is_positive 4
+                    is_positive #true
+                }
+            }
+        "#,
+    );
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
+}
+
 #[test]
 fn check_verb_panic_fail() {
     let mut ts = test_dsl::TestDsl::<()>::new();
@@ -123,6 +213,55 @@ fn check_verb_panic_fail() {
         .unwrap()[0]
         .run(&mut ());
 
+    // The location of the panic changes with every edit of this file
+    let report = format!("{:?}", miette::Error::new(tc.unwrap_err()))
+        .lines()
+        .map(|line| match line.find("panicked at ") {
+            Some(start) => format!("{}panicked at [location]", &line[..start]),
+            None => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    insta::assert_snapshot!(report);
+}
+
+#[test]
+fn check_captured_output() {
+    #[derive(Debug, Clone)]
+    struct Shell;
+
+    impl Verb<()> for Shell {
+        type Arguments = (String,);
+
+        fn run(
+            &self,
+            _harness: &mut (),
+            context: &mut TestContext,
+            (command,): &Self::Arguments,
+        ) -> miette::Result<()> {
+            writeln!(context.stdout(), "$ {command}").unwrap();
+            writeln!(context.stderr(), "{command}: command not found").unwrap();
+
+            miette::bail!("exited with status 127")
+        }
+    }
+
+    let mut ts = test_dsl::TestDsl::<()>::new();
+    ts.add_verb("shell", Shell);
+    ts.set_capture_output(true);
+
+    let tc = ts
+        .parse_testcase(
+            r#"
+            testcase {
+                shell "frobnicate"
+            }
+        "#,
+        )
+        .unwrap()[0]
+        .run(&mut ());
+
     insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
 }
 
@@ -214,3 +353,705 @@ fn check_arithmetic() {
     testcases[0].run(&mut 0).unwrap();
     testcases[1].run(&mut 0).unwrap();
 }
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn check_invalid_json() {
+    let mut ts = test_dsl::TestDsl::<()>::new();
+
+    ts.add_condition("valid_json", test_dsl::json::ValidJson);
+
+    let testcases = ts
+        .parse_testcase(
+            r#"
+            testcase {
+                assert {
+                    valid_json "{\"status\": 200,}"
+                }
+            }
+        "#,
+        )
+        .unwrap();
+
+    let invalid = testcases[0].run(&mut ());
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(invalid.unwrap_err())));
+}
+
+#[test]
+fn check_included_source_name() {
+    let ts = test_dsl::TestDsl::<()>::new();
+
+    let parent = TestCaseInput::FromFile {
+        filepath: Arc::from("main.kdl"),
+        contents: Arc::from(""),
+    };
+
+    let tc = ts.parse_testcase(TestCaseInput::included(
+        "common.kdl",
+        r#"
+            testcase {
+                not_found
+            }
+        "#,
+        &parent,
+    ));
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
+}
+
+#[test]
+fn check_deprecated_verb() {
+    let mut ts = test_dsl::TestDsl::<usize>::new();
+
+    ts.add_verb(
+        "increment",
+        FunctionVerb::new(|h: &mut usize| {
+            *h += 1;
+            Ok(())
+        }),
+    );
+    ts.deprecate_verb("increment", "Use `add 1` instead");
+
+    let warnings = Rc::new(RefCell::new(vec![]));
+    ts.set_warning_handler({
+        let warnings = warnings.clone();
+        move |warning| {
+            warnings
+                .borrow_mut()
+                .push(format!("{:?}", miette::Error::new(warning)))
+        }
+    });
+
+    let tc = ts
+        .parse_testcase(
+            r#"
+            testcase {
+                repeat 2 {
+                    increment
+                }
+            }
+        "#,
+        )
+        .unwrap();
+
+    let mut count = 0;
+    tc[0].run(&mut count).unwrap();
+    assert_eq!(count, 2);
+
+    let warnings = warnings.take();
+    assert_eq!(warnings.len(), 1);
+    insta::assert_snapshot!(warnings[0]);
+}
+
+#[test]
+fn check_assert_changes() {
+    let mut ts = test_dsl::TestDsl::<usize>::new();
+
+    ts.add_verb(
+        "add",
+        FunctionVerb::new(|h: &mut usize, n: usize| {
+            *h += n;
+            Ok(())
+        }),
+    );
+    ts.add_verb("assert_changes", AssertChanges::new(|h: &usize| *h));
+
+    let testcases = ts
+        .parse_testcase(
+            r#"
+            testcase {
+                assert_changes {
+                    add 1
+                }
+            }
+            testcase {
+                assert_changes {
+                    add 0
+                }
+            }
+        "#,
+        )
+        .unwrap();
+
+    testcases[0].run(&mut 0).unwrap();
+
+    let unchanged = testcases[1].run(&mut 0);
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(unchanged.unwrap_err())));
+}
+
+#[test]
+fn check_fail() {
+    let ts = test_dsl::TestDsl::<()>::new();
+
+    let testcases = ts
+        .parse_testcase(
+            r#"
+            testcase {
+                group
+                fail "This branch should not be reached"
+            }
+            testcase {
+                fail
+            }
+        "#,
+        )
+        .unwrap();
+
+    let with_message = testcases[0].run(&mut ());
+    let without_message = testcases[1].run(&mut ());
+
+    insta::assert_snapshot!(format!(
+        "{:?}",
+        miette::Error::new(with_message.unwrap_err())
+    ));
+    insta::assert_snapshot!(format!(
+        "{:?}",
+        miette::Error::new(without_message.unwrap_err())
+    ));
+}
+
+#[test]
+fn check_approx_eq() {
+    let mut ts = test_dsl::TestDsl::<f64>::new();
+
+    ts.add_condition("approx_eq", ApproxEq::new(|h: &f64| *h));
+
+    let testcases = ts
+        .parse_testcase(
+            r#"
+            testcase {
+                assert {
+                    approx_eq 1.5 0.001
+                }
+            }
+        "#,
+        )
+        .unwrap();
+
+    testcases[0].run(&mut 1.5004).unwrap();
+
+    let off = testcases[0].run(&mut 1.6);
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(off.unwrap_err())));
+}
+
+#[test]
+fn check_assert_fails() {
+    let mut ts = test_dsl::TestDsl::<()>::new();
+
+    ts.add_verb(
+        "reject",
+        FunctionVerb::new(|_: &mut (), input: String| {
+            miette::bail!("Rejected {input}");
+        }),
+    );
+
+    let testcases = ts
+        .parse_testcase(
+            r#"
+            testcase {
+                assert_fails {
+                    group
+                    reject "bad input"
+                }
+                assert_fails {
+                    fail
+                }
+            }
+            testcase {
+                assert_fails {
+                    group
+                }
+            }
+        "#,
+        )
+        .unwrap();
+
+    testcases[0].run(&mut ()).unwrap();
+
+    let succeeded = testcases[1].run(&mut ());
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(succeeded.unwrap_err())));
+}
+
+#[test]
+fn check_run_collect() {
+    let ts = test_dsl::TestDsl::<()>::new();
+
+    let testcases = ts
+        .parse_testcase(
+            r#"
+            testcase {
+                fail "first"
+                group
+                fail "second"
+            }
+        "#,
+        )
+        .unwrap();
+
+    let errors = testcases[0].run_collect(&mut ()).unwrap_err();
+    assert_eq!(errors.errors().len(), 2);
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(errors)));
+}
+
+#[test]
+fn check_parse_file() {
+    let ts = test_dsl::TestDsl::<()>::new();
+
+    let testcases = ts.parse_file("tests/cases/basic.kdl").unwrap();
+
+    assert_eq!(testcases.len(), 2);
+    assert_eq!(testcases[0].path(), Some("tests/cases/basic.kdl"));
+
+    let tc = ts.parse_file("tests/invalid_cases/unknown_verb.kdl");
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
+
+    let missing = ts.parse_file("tests/cases/does_not_exist.kdl");
+
+    assert!(missing.is_err());
+}
+
+#[test]
+fn check_parse_directory() {
+    let ts = test_dsl::TestDsl::<()>::new();
+
+    let testcases = ts.parse_directory("tests/cases", "*.kdl").unwrap();
+
+    let paths = testcases
+        .iter()
+        .map(|testcase| testcase.path().unwrap().replace('\\', "/"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        [
+            "tests/cases/basic.kdl",
+            "tests/cases/basic.kdl",
+            "tests/cases/nested/repeat.kdl"
+        ]
+    );
+
+    let tc = ts.parse_directory("tests/invalid_cases", "*.kdl");
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
+}
+
+#[cfg(unix)]
+#[test]
+fn check_parse_directory_symlink_cycle() {
+    let dir = std::env::temp_dir().join(format!("test-dsl-symlink-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+    std::fs::write(dir.join("nested/case.kdl"), "testcase {}").unwrap();
+    std::os::unix::fs::symlink(&dir, dir.join("nested/parent")).unwrap();
+
+    let ts = test_dsl::TestDsl::<()>::new();
+    let testcases = ts.parse_directory(&dir, "*.kdl");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(testcases.unwrap().len(), 1);
+}
+
+#[test]
+fn check_include() {
+    let mut ts = test_dsl::TestDsl::<()>::new();
+    ts.set_allow_include(true);
+
+    let testcases = ts.parse_file("tests/include_cases/main.kdl").unwrap();
+
+    let paths = testcases
+        .iter()
+        .map(|testcase| testcase.path().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        [
+            "tests/include_cases/common.kdl",
+            "tests/include_cases/main.kdl"
+        ]
+    );
+
+    let tc = ts.parse_file("tests/include_cases/broken.kdl");
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
+}
+
+#[test]
+fn check_include_cycle() {
+    let mut ts = test_dsl::TestDsl::<()>::new();
+    ts.set_allow_include(true);
+
+    let tc = ts.parse_file("tests/include_cases/cycle_a.kdl");
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
+}
+
+#[test]
+fn check_junit_report() {
+    let ts = test_dsl::TestDsl::<()>::new();
+
+    let testcases = ts
+        .parse_testcase(
+            r#"
+            testcase "passes" {
+                group
+            }
+            testcase "fails <sometimes>" {
+                fail "Something & something else"
+            }
+            testcase {
+                group
+            }
+            testcase "offline" {
+                skip_if "Needs a <network>" { all }
+            }
+        "#,
+        )
+        .unwrap();
+
+    let results: Vec<_> = testcases
+        .iter()
+        .map(|testcase| testcase.run_outcome_with_seed(&mut (), 42))
+        .collect();
+
+    insta::assert_snapshot!(test_dsl::report::to_junit_xml(
+        testcases.iter().zip(&results)
+    ));
+}
+
+#[test]
+fn check_tap_report() {
+    let ts = test_dsl::TestDsl::<()>::new();
+
+    let testcases = ts
+        .parse_testcase(
+            r#"
+            testcase "passes" {
+                group
+            }
+            testcase "fails #1" {
+                fail "Something \"quoted\""
+            }
+            testcase {
+                assert { not { all } }
+            }
+            testcase "offline" {
+                skip_if "Needs a network" { all }
+            }
+        "#,
+        )
+        .unwrap();
+
+    let results: Vec<_> = testcases
+        .iter()
+        .map(|testcase| testcase.run_outcome_with_seed(&mut (), 42))
+        .collect();
+
+    insta::assert_snapshot!(test_dsl::report::to_tap(testcases.iter().zip(&results)));
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn check_derive_parse_arguments() {
+    use test_dsl::argument::ParseArguments;
+    use test_dsl::context::TestContext;
+    use test_dsl::verb::Verb;
+
+    #[derive(Debug, Clone, ParseArguments)]
+    struct Headers {
+        accept: String,
+    }
+
+    #[derive(Debug, Clone, ParseArguments)]
+    struct Request {
+        #[arg(positional)]
+        url: String,
+        #[arg(positional, optional)]
+        body: String,
+        method: Option<String>,
+        #[arg(name = "retries", default = 1)]
+        attempts: usize,
+        #[arg(child, default = Headers { accept: String::from("*/*") })]
+        headers: Headers,
+    }
+
+    #[derive(Debug, Clone)]
+    struct Send;
+
+    impl Verb<Vec<String>> for Send {
+        type Arguments = Request;
+
+        fn run(
+            &self,
+            requests: &mut Vec<String>,
+            _context: &mut TestContext,
+            request: &Self::Arguments,
+        ) -> miette::Result<()> {
+            requests.push(format!(
+                "{} {:?} {:?} {} {}",
+                request.url, request.body, request.method, request.attempts, request.headers.accept
+            ));
+            Ok(())
+        }
+    }
+
+    let mut ts = test_dsl::TestDsl::<Vec<String>>::new();
+    ts.add_verb("send", Send);
+
+    let tc = ts
+        .parse_testcase(
+            r#"
+            testcase {
+                send "/"
+                send "/users" "{}" method="POST" retries=3 {
+                    headers accept="application/json"
+                }
+            }
+        "#,
+        )
+        .unwrap();
+
+    let mut requests = vec![];
+    tc[0].run(&mut requests).unwrap();
+
+    assert_eq!(
+        requests,
+        [
+            r#"/ "" None 1 */*"#,
+            r#"/users "{}" Some("POST") 3 application/json"#,
+        ]
+    );
+
+    let tc = ts.parse_testcase(
+        r#"
+            testcase {
+                send retries="many"
+            }
+        "#,
+    );
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn check_derive_verb_argument() {
+    use test_dsl::argument::VerbArgument;
+
+    #[derive(Debug, Clone, PartialEq, VerbArgument)]
+    enum Color {
+        Red,
+        Green,
+        #[arg(rename = "dark-blue")]
+        DarkBlue,
+    }
+
+    let mut ts = test_dsl::TestDsl::<Vec<Color>>::new();
+    ts.add_verb(
+        "paint",
+        FunctionVerb::new(|colors: &mut Vec<Color>, color: Color| {
+            colors.push(color);
+            Ok(())
+        }),
+    );
+
+    let tc = ts
+        .parse_testcase(
+            r#"
+            testcase {
+                paint red
+                paint "GREEN"
+                paint dark-blue
+            }
+        "#,
+        )
+        .unwrap();
+
+    let mut colors = vec![];
+    tc[0].run(&mut colors).unwrap();
+    assert_eq!(colors, [Color::Red, Color::Green, Color::DarkBlue]);
+
+    let tc = ts.parse_testcase(
+        r#"
+            testcase {
+                paint purple
+            }
+        "#,
+    );
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
+}
+
+#[test]
+fn check_group_label() {
+    let ts = test_dsl::TestDsl::<()>::new();
+
+    let tc = ts
+        .parse_testcase(
+            r#"
+            testcase {
+                group "setup database" {
+                    group
+                    fail "Could not connect"
+                }
+            }
+        "#,
+        )
+        .unwrap();
+
+    insta::assert_snapshot!(format!(
+        "{:?}",
+        miette::Error::new(tc[0].run(&mut ()).unwrap_err())
+    ));
+}
+
+#[test]
+fn check_assert_all() {
+    let ts = test_dsl::TestDsl::<()>::new();
+
+    let tc = ts
+        .parse_testcase(
+            r#"
+            testcase {
+                assert_all {
+                    not { all; }
+                    all
+                    any
+                }
+            }
+        "#,
+        )
+        .unwrap();
+
+    insta::assert_snapshot!(format!(
+        "{:?}",
+        miette::Error::new(tc[0].run(&mut ()).unwrap_err())
+    ));
+}
+
+#[test]
+fn check_unsupported_condition() {
+    let mut ts = test_dsl::TestDsl::<()>::new();
+
+    ts.add_condition("ready", FunctionCondition::new_now(|_: &()| Ok(true)));
+    ts.add_condition("event", FunctionCondition::new_wait(|_: &()| Ok(true)));
+
+    let tc = ts.parse_testcase(
+        r#"
+            testcase {
+                assert {
+                    ready
+                    event
+                }
+                wait_for {
+                    ready
+                }
+            }
+        "#,
+    );
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn check_matches() {
+    let mut ts = test_dsl::TestDsl::<String>::new();
+
+    ts.add_condition(
+        "matches",
+        test_dsl::condition::Matches::new(|log: &String| log.as_str()),
+    );
+
+    let tc = ts
+        .parse_testcase(
+            r#"
+            testcase {
+                assert {
+                    matches "ERROR.*timeout"
+                }
+            }
+        "#,
+        )
+        .unwrap();
+
+    insta::assert_snapshot!(format!(
+        "{:?}",
+        miette::Error::new(
+            tc[0]
+                .run(&mut String::from("INFO: request done"))
+                .unwrap_err()
+        )
+    ));
+
+    let tc = ts.parse_testcase(
+        r#"
+            testcase {
+                assert {
+                    matches "ERROR.*("
+                }
+            }
+        "#,
+    );
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
+}
+
+#[test]
+fn check_argument_reason() {
+    #[derive(Debug, Clone)]
+    struct Port;
+
+    impl test_dsl::argument::VerbArgument for Port {
+        fn get_error_type_name() -> &'static str {
+            "port"
+        }
+
+        fn from_value(value: &test_dsl::kdl::KdlEntry) -> Option<Self> {
+            Self::from_value_detailed(value).ok()
+        }
+
+        fn from_value_detailed(value: &test_dsl::kdl::KdlEntry) -> Result<Self, Option<String>> {
+            match value.value().as_integer() {
+                Some(1..=65535) => Ok(Port),
+                Some(_) => Err(Some(String::from("A port has to be between 1 and 65535."))),
+                None => Err(None),
+            }
+        }
+    }
+
+    let mut ts = test_dsl::TestDsl::<()>::new();
+
+    ts.add_verb("listen", FunctionVerb::new(|_: &mut (), _: Port| Ok(())));
+
+    let tc = ts.parse_testcase(
+        r#"
+            testcase {
+                listen 70000
+            }
+        "#,
+    );
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
+}
+
+#[test]
+fn check_null_argument() {
+    let mut ts = test_dsl::TestDsl::<()>::new();
+
+    ts.add_verb("foobar", FunctionVerb::new(|_: &mut (), _: usize| Ok(())));
+
+    let tc = ts.parse_testcase(
+        r#"
+            testcase {
+                foobar #null
+            }
+        "#,
+    );
+
+    insta::assert_snapshot!(format!("{:?}", miette::Error::new(tc.unwrap_err())));
+}